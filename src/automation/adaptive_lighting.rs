@@ -0,0 +1,508 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::automation::sun::solar_elevation;
+use crate::automation::tolerance::tolerance;
+use crate::command::Command;
+use crate::event::Event;
+use crate::model::{kelvin_to_mirek, Device, Property, PropertyValue};
+
+/// Time given to the bridge to report the applied values before a difference is considered a manual change.
+const SETTLE_GRACE: Duration = Duration::from_secs(5);
+
+/// Maps the elevation of the sun to a colour temperature and brightness. At or below `night_elevation` lights are at
+/// their warmest and dimmest, at or above `day_elevation` at their coolest and brightest, and in between they follow
+/// a smoothstep so the change is slow around dawn and dusk. If `day_elevation` is not above `night_elevation` there is
+/// no in between, lights switch at `day_elevation`.
+#[derive(Clone, Debug)]
+pub struct CircadianCurve {
+    min_kelvin: usize,
+    max_kelvin: usize,
+    min_brightness: usize,
+    max_brightness: usize,
+    night_elevation: f64,
+    day_elevation: f64,
+}
+
+impl CircadianCurve {
+    pub fn new(
+        min_kelvin: usize,
+        max_kelvin: usize,
+        min_brightness: usize,
+        max_brightness: usize,
+        night_elevation: f64,
+        day_elevation: f64,
+    ) -> Self {
+        CircadianCurve {
+            min_kelvin,
+            max_kelvin,
+            min_brightness,
+            max_brightness,
+            night_elevation,
+            day_elevation,
+        }
+    }
+
+    /// Returns the target colour temperature in Kelvin and brightness in percent for the given sun elevation.
+    pub fn target(&self, elevation: f64) -> (usize, usize) {
+        let progress = if self.day_elevation > self.night_elevation {
+            ((elevation - self.night_elevation) / (self.day_elevation - self.night_elevation))
+                .clamp(0.0, 1.0)
+        } else if elevation >= self.day_elevation {
+            1.0
+        } else {
+            0.0
+        };
+        let factor = progress * progress * (3.0 - 2.0 * progress);
+
+        let interpolate = |min: usize, max: usize| {
+            (min as f64 + factor * (max as f64 - min as f64)).round() as usize
+        };
+        (
+            interpolate(self.min_kelvin, self.max_kelvin),
+            interpolate(self.min_brightness, self.max_brightness),
+        )
+    }
+}
+
+impl Default for CircadianCurve {
+    fn default() -> Self {
+        CircadianCurve::new(2200, 5000, 30, 100, -6.0, 30.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AdaptiveLightingConfig {
+    latitude: f64,
+    longitude: f64,
+    targets: Vec<String>,
+    curve: CircadianCurve,
+    interval: Duration,
+    transition: Duration,
+}
+
+impl AdaptiveLightingConfig {
+    /// `targets` contains the ids or names of the devices and the names of the rooms that opt in.
+    pub fn new(latitude: f64, longitude: f64, targets: Vec<String>) -> Self {
+        AdaptiveLightingConfig {
+            latitude,
+            longitude,
+            targets,
+            curve: CircadianCurve::default(),
+            interval: Duration::from_secs(300),
+            transition: Duration::from_secs(60),
+        }
+    }
+
+    pub fn with_curve(mut self, curve: CircadianCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_transition(mut self, transition: Duration) -> Self {
+        self.transition = transition;
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn targets(&self, id: &str, name: &str) -> bool {
+        self.targets
+            .iter()
+            .any(|target| target == id || target == name)
+    }
+}
+
+/// Keeps the colour temperature and brightness of opted-in lights in line with the [`CircadianCurve`]. Lights are
+/// adapted as soon as they turn on and every interval after that. A light that is changed by someone else is left
+/// alone until it is turned off again.
+pub struct AdaptiveLighting {
+    config: AdaptiveLightingConfig,
+    lights: HashMap<String, LightState>,
+    opted_in: HashSet<String>,
+}
+
+struct LightState {
+    on: bool,
+    mirek_minimum: usize,
+    mirek_maximum: usize,
+    applied: Option<Applied>,
+    suspended: bool,
+}
+
+struct Applied {
+    brightness: usize,
+    mirek: usize,
+    settles_at: DateTime<Utc>,
+}
+
+impl AdaptiveLighting {
+    pub fn new(config: AdaptiveLightingConfig) -> AdaptiveLighting {
+        AdaptiveLighting {
+            config,
+            lights: HashMap::new(),
+            opted_in: HashSet::new(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.config.interval()
+    }
+
+    pub fn is_suspended(&self, device_id: &str) -> bool {
+        self.lights
+            .get(device_id)
+            .is_some_and(|light| light.suspended)
+    }
+
+    pub fn handle_event(&mut self, event: &Event, now: DateTime<Utc>) -> Vec<Command> {
+        match event {
+            Event::DiscoveredDevices(devices) => {
+                for device in devices {
+                    self.discover_device(device);
+                }
+                self.tick(now)
+            }
+            Event::DiscoveredRooms(rooms) => {
+                for room in rooms {
+                    if self.config.targets(room.id(), room.name()) {
+                        self.opted_in.extend(room.device_ids().iter().cloned());
+                    }
+                }
+                self.tick(now)
+            }
+            Event::PropertiesChanged {
+                device_id,
+                properties,
+            } => self
+                .properties_changed(device_id, properties, now)
+                .into_iter()
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Moves every opted-in light that is on towards the current target, skipping lights that already have it.
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<Command> {
+        let (kelvin, brightness) = self.current_target(now);
+        let transition = self.config.transition;
+        let mut device_ids: Vec<String> = self.opted_in.iter().cloned().collect();
+        device_ids.sort();
+
+        device_ids
+            .iter()
+            .filter_map(|device_id| {
+                self.adapt(device_id, kelvin, brightness, Some(transition), now)
+            })
+            .collect()
+    }
+
+    fn discover_device(&mut self, device: &Device) {
+        let on = match device.properties().get("on") {
+            Some(Property::Boolean(on)) => on.value(),
+            _ => return,
+        };
+        let (mirek_minimum, mirek_maximum) = match device.properties().get("color_temperature") {
+            Some(Property::Number(color_temperature)) => (
+                color_temperature
+                    .minimum()
+                    .map_or(153, |m| m.ceil() as usize),
+                color_temperature
+                    .maximum()
+                    .map_or(500, |m| m.floor() as usize),
+            ),
+            _ => return,
+        };
+        // Rounding inwards crosses over for a range narrower than a mirek, the minimum must not exceed the maximum
+        let mirek_minimum = mirek_minimum.min(mirek_maximum);
+
+        self.lights.insert(
+            device.id().clone(),
+            LightState {
+                on,
+                mirek_minimum,
+                mirek_maximum,
+                applied: None,
+                suspended: false,
+            },
+        );
+        if self.config.targets(device.id(), device.name()) {
+            self.opted_in.insert(device.id().clone());
+        }
+    }
+
+    fn properties_changed(
+        &mut self,
+        device_id: &String,
+        properties: &HashMap<String, PropertyValue>,
+        now: DateTime<Utc>,
+    ) -> Option<Command> {
+        let light = self.lights.get_mut(device_id)?;
+
+        match properties.get("on") {
+            Some(PropertyValue::Boolean(true)) if !light.on => {
+                light.on = true;
+                light.applied = None;
+                light.suspended = false;
+                let (kelvin, brightness) = self.current_target(now);
+                return self.adapt(device_id, kelvin, brightness, None, now);
+            }
+            Some(PropertyValue::Boolean(false)) => {
+                light.on = false;
+                light.applied = None;
+                light.suspended = false;
+                return None;
+            }
+            _ => {}
+        }
+
+        if let Some(applied) = light.applied.as_ref().filter(|a| now >= a.settles_at) {
+            let brightness_changed = matches!(
+                properties.get("brightness"),
+                Some(PropertyValue::Number(Some(brightness)))
+                    if (brightness - applied.brightness as f64).abs() > tolerance("brightness")
+            );
+            let mirek_changed = match properties.get("color_temperature") {
+                Some(PropertyValue::Number(Some(mirek))) => {
+                    (mirek - applied.mirek as f64).abs() > tolerance("color_temperature")
+                }
+                Some(PropertyValue::Number(None)) => true, // Switched to a colour
+                _ => false,
+            };
+            if light.on && (brightness_changed || mirek_changed) {
+                light.suspended = true;
+            }
+        }
+
+        None
+    }
+
+    fn adapt(
+        &mut self,
+        device_id: &String,
+        kelvin: usize,
+        brightness: usize,
+        transition: Option<Duration>,
+        now: DateTime<Utc>,
+    ) -> Option<Command> {
+        if !self.opted_in.contains(device_id) {
+            return None;
+        }
+        let light = self.lights.get_mut(device_id)?;
+        if !light.on || light.suspended {
+            return None;
+        }
+
+        let mirek = (kelvin_to_mirek(kelvin.max(1) as f64).round() as usize)
+            .clamp(light.mirek_minimum, light.mirek_maximum);
+        if let Some(applied) = &light.applied {
+            if applied.brightness == brightness && applied.mirek == mirek {
+                return None;
+            }
+        }
+
+        light.applied = Some(Applied {
+            brightness,
+            mirek,
+            settles_at: now + transition.unwrap_or_default() + SETTLE_GRACE,
+        });
+
+        Some(Command::ControlDevice {
+            device_id: device_id.clone(),
+            properties: HashMap::from([
+                (
+                    "brightness".to_string(),
+                    PropertyValue::Number(Some(brightness as f64)),
+                ),
+                (
+                    "color_temperature".to_string(),
+                    PropertyValue::Number(Some(mirek as f64)),
+                ),
+            ]),
+            transition,
+        })
+    }
+
+    fn current_target(&self, now: DateTime<Utc>) -> (usize, usize) {
+        let elevation = solar_elevation(self.config.latitude, self.config.longitude, now);
+        self.config.curve.target(elevation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Room;
+    use crate::test_support;
+    use chrono::TimeZone;
+
+    fn light(id: &str, on: bool, mirek_minimum: f64, mirek_maximum: f64) -> Device {
+        test_support::light(id)
+            .on(on)
+            .color_temperature(None, mirek_minimum, mirek_maximum)
+            .build()
+    }
+
+    fn changed(device_id: &str, properties: Vec<(&str, PropertyValue)>) -> Event {
+        Event::PropertiesChanged {
+            device_id: device_id.to_string(),
+            properties: properties
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+
+    fn midnight() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 6, 21, 23, 40, 0).unwrap()
+    }
+
+    fn adaptive_lighting(targets: Vec<&str>) -> AdaptiveLighting {
+        let targets = targets.into_iter().map(String::from).collect();
+        AdaptiveLighting::new(AdaptiveLightingConfig::new(52.37, 4.89, targets))
+    }
+
+    #[test]
+    fn curve_follows_the_sun() {
+        let curve = CircadianCurve::default();
+
+        assert_eq!((2200, 30), curve.target(-20.0));
+        assert_eq!((5000, 100), curve.target(45.0));
+        assert_eq!((3600, 65), curve.target(12.0));
+    }
+
+    #[test]
+    fn curve_switches_when_night_and_day_meet() {
+        let curve = CircadianCurve::new(2200, 5000, 30, 100, 0.0, 0.0);
+
+        assert_eq!((2200, 30), curve.target(-0.5));
+        assert_eq!((5000, 100), curve.target(0.0));
+        assert_eq!((5000, 100), curve.target(0.5));
+    }
+
+    #[test]
+    fn adapts_a_light_with_a_range_narrower_than_a_mirek() {
+        let mut adaptive_lighting = adaptive_lighting(vec!["1"]);
+        adaptive_lighting.handle_event(
+            &Event::DiscoveredDevices(vec![light("1", false, 300.2, 300.8)]),
+            midnight(),
+        );
+
+        let commands = adaptive_lighting.handle_event(
+            &changed("1", vec![("on", PropertyValue::Boolean(true))]),
+            midnight(),
+        );
+
+        assert!(matches!(
+            &commands[..],
+            [Command::ControlDevice { properties, .. }]
+                if properties["color_temperature"] == PropertyValue::Number(Some(300.0))
+        ));
+    }
+
+    #[test]
+    fn adapts_an_opted_in_light_when_it_turns_on() {
+        let mut adaptive_lighting = adaptive_lighting(vec!["1"]);
+        let discovered = Event::DiscoveredDevices(vec![
+            light("1", false, 153.0, 400.0),
+            light("2", false, 153.0, 500.0),
+        ]);
+        assert!(adaptive_lighting
+            .handle_event(&discovered, midnight())
+            .is_empty());
+
+        let commands = adaptive_lighting.handle_event(
+            &changed("1", vec![("on", PropertyValue::Boolean(true))]),
+            midnight(),
+        );
+
+        assert_eq!(
+            vec![Command::ControlDevice {
+                device_id: "1".to_string(),
+                properties: HashMap::from([
+                    ("brightness".to_string(), PropertyValue::Number(Some(30.0))),
+                    // 2200 K is 454 mirek, clamped to the maximum of the lamp
+                    (
+                        "color_temperature".to_string(),
+                        PropertyValue::Number(Some(400.0))
+                    ),
+                ]),
+                transition: None,
+            }],
+            commands
+        );
+        assert!(adaptive_lighting
+            .handle_event(
+                &changed("2", vec![("on", PropertyValue::Boolean(true))]),
+                midnight()
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn opts_in_the_lights_of_a_room() {
+        let mut adaptive_lighting = adaptive_lighting(vec!["Living room"]);
+        adaptive_lighting.handle_event(
+            &Event::DiscoveredDevices(vec![light("1", true, 153.0, 500.0)]),
+            midnight(),
+        );
+
+        let commands = adaptive_lighting.handle_event(
+            &Event::DiscoveredRooms(vec![Room::new(
+                "room".to_string(),
+                "Living room".to_string(),
+                vec!["1".to_string()],
+            )]),
+            midnight(),
+        );
+
+        assert_eq!(1, commands.len());
+        assert!(adaptive_lighting.tick(midnight()).is_empty());
+    }
+
+    #[test]
+    fn suspends_after_a_manual_change_until_turned_off() {
+        let mut adaptive_lighting = adaptive_lighting(vec!["1"]);
+        adaptive_lighting.handle_event(
+            &Event::DiscoveredDevices(vec![light("1", true, 153.0, 500.0)]),
+            midnight(),
+        );
+
+        // The bridge echoes the applied values, which is not a manual change
+        let settled = midnight() + Duration::from_secs(120);
+        adaptive_lighting.handle_event(
+            &changed("1", vec![("brightness", PropertyValue::Number(Some(30.0)))]),
+            settled,
+        );
+        assert!(!adaptive_lighting.is_suspended("1"));
+
+        adaptive_lighting.handle_event(
+            &changed("1", vec![("brightness", PropertyValue::Number(Some(80.0)))]),
+            settled,
+        );
+        assert!(adaptive_lighting.is_suspended("1"));
+        assert!(adaptive_lighting
+            .tick(settled + Duration::from_secs(3600))
+            .is_empty());
+
+        adaptive_lighting.handle_event(
+            &changed("1", vec![("on", PropertyValue::Boolean(false))]),
+            settled,
+        );
+        let commands = adaptive_lighting.handle_event(
+            &changed("1", vec![("on", PropertyValue::Boolean(true))]),
+            settled,
+        );
+        assert!(!adaptive_lighting.is_suspended("1"));
+        assert_eq!(1, commands.len());
+    }
+}
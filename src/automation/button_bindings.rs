@@ -0,0 +1,557 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::model::{ButtonEvent, Presence, PropertyValue};
+use crate::registry::Registry;
+
+/// Short presses that follow each other within this window count as a single multi-press.
+const MULTI_PRESS_WINDOW: Duration = Duration::from_millis(500);
+
+/// `Event` fires as soon as the button reports the event. `Presses` fires once a series of short presses has ended,
+/// so binding a double press does not also trigger the single press binding of the same button.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonTrigger {
+    Event(ButtonEvent),
+    Presses(u8),
+}
+
+/// Targets are rooms or devices, given by id or name.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonAction {
+    /// Turns every light of the target off if any of them is on, otherwise turns them all on.
+    Toggle(String),
+    /// Recalls the next scene of the list on every trigger. Scene names are looked up within the room, if given.
+    CycleScenes {
+        room: Option<String>,
+        scenes: Vec<String>,
+    },
+    /// Changes the brightness of the lights of the target that are on by `step` percent. Bound to
+    /// [`ButtonEvent::Repeat`] it fades smoothly, as each step transitions for the button's repeat interval.
+    Dim { target: String, step: i32 },
+    /// Sets whether anyone is home, e.g. a switch by the front door that marks the house as away.
+    SetPresence(Presence),
+    /// Slowly changes a number property of the lights of the target, e.g. a 30 minute wake-up ramp of the brightness.
+    /// The fade stops for a light as soon as someone changes it.
+    Fade {
+        target: String,
+        property: String,
+        value: f64,
+        seconds: u64,
+    },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ButtonBinding {
+    device: String,
+    control_id: u8,
+    trigger: ButtonTrigger,
+    action: ButtonAction,
+}
+
+impl ButtonBinding {
+    /// `device` is the id or name of the switch, `control_id` the number of the button on it.
+    pub fn new(
+        device: String,
+        control_id: u8,
+        trigger: ButtonTrigger,
+        action: ButtonAction,
+    ) -> Self {
+        ButtonBinding {
+            device,
+            control_id,
+            trigger,
+            action,
+        }
+    }
+}
+
+/// Maps button events of switches to actions, independent of what the switch is configured to do in the Hue app.
+pub struct ButtonBindings {
+    bindings: Vec<ButtonBinding>,
+    presses: HashMap<(String, u8), Presses>,
+    scene_positions: HashMap<usize, usize>,
+    dimmed: HashMap<String, (usize, DateTime<Utc>)>,
+}
+
+struct Presses {
+    count: u8,
+    last_release: DateTime<Utc>,
+}
+
+impl ButtonBindings {
+    pub fn new(bindings: Vec<ButtonBinding>) -> ButtonBindings {
+        ButtonBindings {
+            bindings,
+            presses: HashMap::new(),
+            scene_positions: HashMap::new(),
+            dimmed: HashMap::new(),
+        }
+    }
+
+    pub fn handle_event(
+        &mut self,
+        event: &Event,
+        registry: &Registry,
+        now: DateTime<Utc>,
+    ) -> Vec<Command> {
+        let Event::ButtonPressed {
+            device_id,
+            control_id,
+            event,
+            repeat_interval,
+        } = event
+        else {
+            return vec![];
+        };
+
+        let bindings = self.bindings_for(device_id, *control_id, registry);
+        let mut commands = vec![];
+        for index in self.triggered(&bindings, ButtonTrigger::Event(*event)) {
+            commands.extend(self.execute(index, registry, *repeat_interval, now));
+        }
+
+        let pressed = match event {
+            ButtonEvent::ShortRelease => 1,
+            ButtonEvent::DoubleShortRelease => 2,
+            _ => return commands,
+        };
+        let key = (device_id.clone(), *control_id);
+        let count = match self.presses.get(&key) {
+            Some(presses) if now <= presses.last_release + MULTI_PRESS_WINDOW => {
+                presses.count + pressed
+            }
+            _ => pressed,
+        };
+
+        let awaits_more = bindings.iter().any(
+            |&index| matches!(self.bindings[index].trigger, ButtonTrigger::Presses(n) if n > count),
+        );
+        if awaits_more {
+            self.presses.insert(
+                key,
+                Presses {
+                    count,
+                    last_release: now,
+                },
+            );
+        } else {
+            self.presses.remove(&key);
+            commands.extend(self.fire_presses(&bindings, count, registry, now));
+        }
+
+        commands
+    }
+
+    /// Fires the bindings of press series that ended, call this more often than the multi-press window.
+    pub fn poll(&mut self, registry: &Registry, now: DateTime<Utc>) -> Vec<Command> {
+        let ended: Vec<((String, u8), u8)> = self
+            .presses
+            .iter()
+            .filter(|(_, presses)| now > presses.last_release + MULTI_PRESS_WINDOW)
+            .map(|(key, presses)| (key.clone(), presses.count))
+            .collect();
+
+        let mut commands = vec![];
+        for ((device_id, control_id), count) in ended {
+            self.presses.remove(&(device_id.clone(), control_id));
+            let bindings = self.bindings_for(&device_id, control_id, registry);
+            commands.extend(self.fire_presses(&bindings, count, registry, now));
+        }
+        commands
+    }
+
+    fn bindings_for(&self, device_id: &str, control_id: u8, registry: &Registry) -> Vec<usize> {
+        let name = registry
+            .device(device_id)
+            .map(|device| device.name().as_str());
+        self.bindings
+            .iter()
+            .enumerate()
+            .filter(|(_, binding)| {
+                binding.control_id == control_id
+                    && (binding.device == device_id || Some(binding.device.as_str()) == name)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn triggered(&self, bindings: &[usize], trigger: ButtonTrigger) -> Vec<usize> {
+        bindings
+            .iter()
+            .copied()
+            .filter(|&index| self.bindings[index].trigger == trigger)
+            .collect()
+    }
+
+    fn fire_presses(
+        &mut self,
+        bindings: &[usize],
+        count: u8,
+        registry: &Registry,
+        now: DateTime<Utc>,
+    ) -> Vec<Command> {
+        let mut commands = vec![];
+        for index in self.triggered(bindings, ButtonTrigger::Presses(count)) {
+            commands.extend(self.execute(index, registry, Duration::ZERO, now));
+        }
+        commands
+    }
+
+    fn execute(
+        &mut self,
+        index: usize,
+        registry: &Registry,
+        repeat_interval: Duration,
+        now: DateTime<Utc>,
+    ) -> Vec<Command> {
+        match self.bindings[index].action.clone() {
+            ButtonAction::Toggle(target) => toggle(&target, registry),
+            ButtonAction::CycleScenes { room, scenes } => {
+                let position = self.scene_positions.entry(index).or_insert(0);
+                let Some(scene) = scenes.get(*position % scenes.len().max(1)) else {
+                    return vec![];
+                };
+                *position = (*position + 1) % scenes.len();
+
+                let room_id = room
+                    .and_then(|room| registry.find_room(&room))
+                    .map(|room| room.id());
+                registry
+                    .find_scene(scene, room_id)
+                    .map(|scene| Command::RecallScene {
+                        scene_id: scene.id().clone(),
+                        transition: None,
+                    })
+                    .into_iter()
+                    .collect()
+            }
+            ButtonAction::Dim { target, step } => {
+                self.dim(&target, step, registry, repeat_interval, now)
+            }
+            ButtonAction::SetPresence(presence) => vec![Command::SetPresence(presence)],
+            ButtonAction::Fade {
+                target,
+                property,
+                value,
+                seconds,
+            } => registry
+                .resolve_device_ids(&target)
+                .into_iter()
+                .map(|device_id| Command::StartFade {
+                    device_id,
+                    targets: HashMap::from([(property.clone(), value)]),
+                    duration: Duration::from_secs(seconds),
+                })
+                .collect(),
+        }
+    }
+
+    fn dim(
+        &mut self,
+        target: &str,
+        step: i32,
+        registry: &Registry,
+        transition: Duration,
+        now: DateTime<Utc>,
+    ) -> Vec<Command> {
+        let mut commands = vec![];
+        for device_id in registry.resolve_device_ids(target) {
+            let Some(device) = registry.device(&device_id) else {
+                continue;
+            };
+            let on = device.properties().get("on").map(|on| on.value());
+            let brightness = match device.properties().get("brightness").map(|b| b.value()) {
+                Some(PropertyValue::Number(Some(brightness))) => brightness.round() as usize,
+                _ => continue,
+            };
+            if on != Some(PropertyValue::Boolean(true)) {
+                continue;
+            }
+
+            // The bridge reports the new brightness after the transition, so rely on what was sent until then
+            let brightness = match self.dimmed.get(&device_id) {
+                Some((dimmed, at)) if now <= *at + transition * 2 => *dimmed,
+                _ => brightness,
+            };
+            let dimmed = (brightness as i32 + step).clamp(1, 100) as usize;
+            if dimmed == brightness {
+                continue;
+            }
+
+            self.dimmed.insert(device_id.clone(), (dimmed, now));
+            commands.push(Command::ControlDevice {
+                device_id,
+                properties: HashMap::from([(
+                    "brightness".to_string(),
+                    PropertyValue::Number(Some(dimmed as f64)),
+                )]),
+                transition: Some(transition).filter(|t| !t.is_zero()),
+            });
+        }
+        commands
+    }
+}
+
+fn toggle(target: &str, registry: &Registry) -> Vec<Command> {
+    let lights: Vec<(String, PropertyValue)> = registry
+        .resolve_device_ids(target)
+        .into_iter()
+        .filter_map(|id| {
+            let on = registry.device(&id)?.properties().get("on")?.value();
+            Some((id, on))
+        })
+        .collect();
+    let any_on = lights
+        .iter()
+        .any(|(_, on)| *on == PropertyValue::Boolean(true));
+
+    lights
+        .into_iter()
+        .map(|(device_id, _)| Command::ControlDevice {
+            device_id,
+            properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(!any_on))]),
+            transition: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Device, Room, Scene};
+    use crate::test_support;
+    use chrono::TimeZone;
+
+    fn light(id: &str, on: bool, brightness: f64) -> Device {
+        test_support::light(id)
+            .on(on)
+            .brightness(brightness)
+            .build()
+    }
+
+    fn registry(lights: Vec<Device>) -> Registry {
+        let mut registry = Registry::new();
+        let device_ids = lights.iter().map(|light| light.id().clone()).collect();
+        registry.apply(&Event::DiscoveredDevices(lights));
+        registry.apply(&Event::DiscoveredRooms(vec![Room::new(
+            "room".to_string(),
+            "Living room".to_string(),
+            device_ids,
+        )]));
+        registry.apply(&Event::DiscoveredScenes(vec![
+            Scene::new(
+                "relax".to_string(),
+                "Relax".to_string(),
+                Some("room".to_string()),
+            ),
+            Scene::new(
+                "read".to_string(),
+                "Read".to_string(),
+                Some("room".to_string()),
+            ),
+        ]));
+        registry
+    }
+
+    fn pressed(event: ButtonEvent) -> Event {
+        Event::ButtonPressed {
+            device_id: "dimmer".to_string(),
+            control_id: 1,
+            event,
+            repeat_interval: Duration::from_millis(800),
+        }
+    }
+
+    fn binding(trigger: ButtonTrigger, action: ButtonAction) -> ButtonBinding {
+        ButtonBinding::new("dimmer".to_string(), 1, trigger, action)
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 11, 14, 22, 13, 20).unwrap()
+    }
+
+    fn on(device_id: &str, on: bool) -> Command {
+        Command::ControlDevice {
+            device_id: device_id.to_string(),
+            properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(on))]),
+            transition: None,
+        }
+    }
+
+    #[test]
+    fn toggles_a_room_off_when_any_light_is_on() {
+        let registry = registry(vec![light("1", true, 50.0), light("2", false, 50.0)]);
+        let mut bindings = ButtonBindings::new(vec![binding(
+            ButtonTrigger::Event(ButtonEvent::ShortRelease),
+            ButtonAction::Toggle("Living room".to_string()),
+        )]);
+
+        let mut commands =
+            bindings.handle_event(&pressed(ButtonEvent::ShortRelease), &registry, now());
+        commands.sort_by_key(|c| format!("{:?}", c));
+
+        assert_eq!(vec![on("1", false), on("2", false)], commands);
+    }
+
+    #[test]
+    fn starts_a_fade_for_every_light_of_the_target() {
+        let registry = registry(vec![light("1", false, 0.0), light("2", false, 0.0)]);
+        let mut bindings = ButtonBindings::new(vec![binding(
+            ButtonTrigger::Event(ButtonEvent::LongRelease),
+            ButtonAction::Fade {
+                target: "Living room".to_string(),
+                property: "brightness".to_string(),
+                value: 100.0,
+                seconds: 1800,
+            },
+        )]);
+
+        let mut commands =
+            bindings.handle_event(&pressed(ButtonEvent::LongRelease), &registry, now());
+        commands.sort_by_key(|c| format!("{:?}", c));
+
+        let fade = |device_id: &str| Command::StartFade {
+            device_id: device_id.to_string(),
+            targets: HashMap::from([("brightness".to_string(), 100.0)]),
+            duration: Duration::from_secs(1800),
+        };
+        assert_eq!(vec![fade("1"), fade("2")], commands);
+    }
+
+    #[test]
+    fn cycles_through_scenes() {
+        let registry = registry(vec![]);
+        let mut bindings = ButtonBindings::new(vec![binding(
+            ButtonTrigger::Event(ButtonEvent::InitialPress),
+            ButtonAction::CycleScenes {
+                room: Some("Living room".to_string()),
+                scenes: vec!["Relax".to_string(), "Read".to_string()],
+            },
+        )]);
+
+        let recalled: Vec<Command> = (0..3)
+            .flat_map(|_| {
+                bindings.handle_event(&pressed(ButtonEvent::InitialPress), &registry, now())
+            })
+            .collect();
+
+        let recall = |scene_id: &str| Command::RecallScene {
+            scene_id: scene_id.to_string(),
+            transition: None,
+        };
+        assert_eq!(
+            vec![recall("relax"), recall("read"), recall("relax")],
+            recalled
+        );
+    }
+
+    #[test]
+    fn dims_on_repeat_using_the_repeat_interval() {
+        let registry = registry(vec![light("1", true, 95.0), light("2", false, 50.0)]);
+        let mut bindings = ButtonBindings::new(vec![binding(
+            ButtonTrigger::Event(ButtonEvent::Repeat),
+            ButtonAction::Dim {
+                target: "Living room".to_string(),
+                step: 10,
+            },
+        )]);
+
+        let first = bindings.handle_event(&pressed(ButtonEvent::Repeat), &registry, now());
+        let second = bindings.handle_event(
+            &pressed(ButtonEvent::Repeat),
+            &registry,
+            now() + Duration::from_millis(800),
+        );
+
+        assert_eq!(
+            vec![Command::ControlDevice {
+                device_id: "1".to_string(),
+                properties: HashMap::from([(
+                    "brightness".to_string(),
+                    PropertyValue::Number(Some(100.0))
+                )]),
+                transition: Some(Duration::from_millis(800)),
+            }],
+            first
+        );
+        assert!(second.is_empty(), "already at the maximum brightness");
+    }
+
+    #[test]
+    fn waits_for_the_end_of_a_multi_press() {
+        let registry = registry(vec![light("1", false, 50.0)]);
+        let mut bindings = ButtonBindings::new(vec![
+            binding(
+                ButtonTrigger::Presses(1),
+                ButtonAction::Toggle("1".to_string()),
+            ),
+            binding(
+                ButtonTrigger::Presses(2),
+                ButtonAction::CycleScenes {
+                    room: None,
+                    scenes: vec!["relax".to_string()],
+                },
+            ),
+        ]);
+
+        let single = bindings.handle_event(&pressed(ButtonEvent::ShortRelease), &registry, now());
+        assert!(single.is_empty());
+        assert!(bindings.poll(&registry, now()).is_empty());
+        assert_eq!(
+            vec![on("1", true)],
+            bindings.poll(&registry, now() + Duration::from_secs(1))
+        );
+
+        bindings.handle_event(&pressed(ButtonEvent::ShortRelease), &registry, now());
+        let double = bindings.handle_event(
+            &pressed(ButtonEvent::ShortRelease),
+            &registry,
+            now() + Duration::from_millis(300),
+        );
+        assert_eq!(
+            vec![Command::RecallScene {
+                scene_id: "relax".to_string(),
+                transition: None,
+            }],
+            double
+        );
+        assert!(bindings
+            .poll(&registry, now() + Duration::from_secs(1))
+            .is_empty());
+    }
+
+    #[test]
+    fn deserializes_bindings() -> Result<(), serde_json::Error> {
+        let bindings: Vec<ButtonBinding> = serde_json::from_str(
+            r#"[
+                {"device": "Dimmer", "control_id": 1, "trigger": {"presses": 2}, "action": {"toggle": "Living room"}},
+                {"device": "Dimmer", "control_id": 2, "trigger": {"event": "repeat"}, "action": {"dim": {"target": "Living room", "step": 10}}}
+            ]"#,
+        )?;
+
+        assert_eq!(ButtonTrigger::Presses(2), bindings[0].trigger);
+        assert_eq!(
+            ButtonAction::Toggle("Living room".to_string()),
+            bindings[0].action
+        );
+        assert_eq!(
+            ButtonTrigger::Event(ButtonEvent::Repeat),
+            bindings[1].trigger
+        );
+        assert_eq!(
+            ButtonAction::Dim {
+                target: "Living room".to_string(),
+                step: 10
+            },
+            bindings[1].action
+        );
+        Ok(())
+    }
+}
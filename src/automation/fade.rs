@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::automation::tolerance::tolerance;
+use crate::command::Command;
+use crate::event::Event;
+use crate::model::{NumberProperty, Property, PropertyError, PropertyValue};
+use crate::registry::Registry;
+
+/// Long fades are sent as steps of at most this long. The bridge forgets a transition when the light briefly drops
+/// off the network, a step only loses a little of the fade.
+const MAX_STEP: Duration = Duration::from_secs(30);
+
+/// Fades number properties of lights over a long time, like a 30 minute wake-up ramp, by sending a step with a
+/// transition of its own every [`MAX_STEP`]. A fade is cancelled as soon as the light reports a value the fade did
+/// not send, i.e. someone changed it, or is turned off.
+#[derive(Default)]
+pub struct Fades {
+    fades: HashMap<String, Fade>,
+}
+
+struct Fade {
+    properties: Vec<FadedProperty>,
+    turn_on: bool,
+    started: DateTime<Utc>,
+    duration: Duration,
+    steps: u32,
+    sent: u32,
+}
+
+struct FadedProperty {
+    name: String,
+    from: f64,
+    to: f64,
+    /// The property as the fade started, which the values are rounded to the precision of.
+    property: NumberProperty,
+}
+
+impl Fades {
+    pub fn new() -> Self {
+        Fades::default()
+    }
+
+    /// Starts fading the properties of a device to `targets` over `duration`, replacing a fade it already had. A
+    /// light that is off is turned on and fades its brightness up from the minimum.
+    pub fn start(
+        &mut self,
+        device_id: &str,
+        targets: HashMap<String, f64>,
+        duration: Duration,
+        registry: &Registry,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Command>, PropertyError> {
+        let device = registry
+            .device(device_id)
+            .ok_or_else(|| PropertyError::UnknownDevice(device_id.to_string()))?;
+        let turn_on = matches!(
+            device.properties().get("on").map(Property::desired_value),
+            Some(PropertyValue::Boolean(false))
+        );
+
+        let mut properties = vec![];
+        for (name, to) in targets {
+            device.validate(&name, &PropertyValue::Number(Some(to)))?;
+            let Some(entry @ Property::Number(property)) = device.properties().get(&name) else {
+                return Err(PropertyError::WrongKind(name));
+            };
+            let current = match entry.desired_value() {
+                PropertyValue::Number(value) => value,
+                _ => None,
+            };
+            let from = match (turn_on && name == "brightness", current) {
+                (true, _) | (false, None) => property.minimum().unwrap_or(to),
+                (false, Some(current)) => current,
+            };
+            properties.push(FadedProperty {
+                name,
+                from,
+                to,
+                property: property.clone(),
+            });
+        }
+
+        let steps = duration.as_secs_f64() / MAX_STEP.as_secs_f64();
+        self.fades.insert(
+            device_id.to_string(),
+            Fade {
+                properties,
+                turn_on,
+                started: now,
+                duration,
+                steps: (steps.ceil() as u32).max(1),
+                sent: 0,
+            },
+        );
+        Ok(self.tick(now))
+    }
+
+    pub fn is_fading(&self, device_id: &str) -> bool {
+        self.fades.contains_key(device_id)
+    }
+
+    pub fn cancel(&mut self, device_id: &str) {
+        self.fades.remove(device_id);
+    }
+
+    /// Cancels the fades of lights that were changed by someone else.
+    pub fn handle_event(&mut self, event: &Event) {
+        let Event::PropertiesChanged {
+            device_id,
+            properties,
+        } = event
+        else {
+            return;
+        };
+        let Some(fade) = self.fades.get(device_id) else {
+            return;
+        };
+        let turned_off = properties.get("on") == Some(&PropertyValue::Boolean(false));
+        let changed = fade.properties.iter().any(|property| {
+            match properties.get(&property.name) {
+                // The light may report any value between the previous step and the one in progress
+                Some(PropertyValue::Number(Some(value))) => {
+                    let previous = property.value(fade.sent.saturating_sub(1), fade.steps);
+                    let current = property.value(fade.sent, fade.steps);
+                    let tolerance = tolerance(&property.name);
+                    *value < previous.min(current) - tolerance
+                        || *value > previous.max(current) + tolerance
+                }
+                Some(_) => true,
+                None => false,
+            }
+        });
+        if turned_off || changed {
+            self.fades.remove(device_id);
+        }
+    }
+
+    /// Sends the steps that are due, a step that was missed is skipped in favour of the one after it.
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<Command> {
+        let mut commands = vec![];
+        for (device_id, fade) in &mut self.fades {
+            let step_duration = fade.duration / fade.steps;
+            let elapsed = (now - fade.started).to_std().unwrap_or_default();
+            let due = if step_duration.is_zero() {
+                fade.steps
+            } else {
+                ((elapsed.as_secs_f64() / step_duration.as_secs_f64()) as u32 + 1).min(fade.steps)
+            };
+            if due <= fade.sent {
+                continue;
+            }
+
+            let mut properties: HashMap<String, PropertyValue> = fade
+                .properties
+                .iter()
+                .map(|property| {
+                    let value = property.value(due, fade.steps);
+                    (property.name.clone(), PropertyValue::Number(Some(value)))
+                })
+                .collect();
+            if fade.turn_on && fade.sent == 0 {
+                properties.insert("on".to_string(), PropertyValue::Boolean(true));
+            }
+            let ends_at = fade.started + step_duration * due;
+            commands.push(Command::ControlDevice {
+                device_id: device_id.clone(),
+                properties,
+                transition: Some((ends_at - now).to_std().unwrap_or_default())
+                    .filter(|transition| !transition.is_zero()),
+            });
+            fade.sent = due;
+        }
+        self.fades.retain(|_, fade| fade.sent < fade.steps);
+        commands
+    }
+}
+
+impl FadedProperty {
+    /// The value at the end of `step`, step 0 being the start of the fade.
+    fn value(&self, step: u32, steps: u32) -> f64 {
+        self.property
+            .round(self.from + (self.to - self.from) * step as f64 / steps as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    use crate::model::Device;
+    use crate::test_support;
+
+    fn light(on: bool, brightness: f64) -> Device {
+        test_support::light("1")
+            .on(on)
+            .brightness(brightness)
+            .build()
+    }
+
+    fn registry(light: Device) -> Registry {
+        let mut registry = Registry::new();
+        registry.apply(&Event::DiscoveredDevices(vec![light]));
+        registry
+    }
+
+    fn brightness(value: f64) -> HashMap<String, f64> {
+        HashMap::from([("brightness".to_string(), value)])
+    }
+
+    fn changed(name: &str, value: PropertyValue) -> Event {
+        Event::PropertiesChanged {
+            device_id: "1".to_string(),
+            properties: HashMap::from([(name.to_string(), value)]),
+        }
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 15, 6, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn wakes_up_a_light_in_steps() -> Result<(), PropertyError> {
+        let mut fades = Fades::new();
+        let registry = registry(light(false, 60.0));
+
+        let first = fades.start(
+            "1",
+            brightness(100.0),
+            Duration::from_secs(30 * 60),
+            &registry,
+            start(),
+        )?;
+
+        // 60 steps of 30 seconds from the minimum brightness of 2%
+        assert_eq!(
+            vec![Command::ControlDevice {
+                device_id: "1".to_string(),
+                properties: HashMap::from([
+                    ("on".to_string(), PropertyValue::Boolean(true)),
+                    (
+                        "brightness".to_string(),
+                        PropertyValue::Number(Some(2.0 + 98.0 / 60.0))
+                    ),
+                ]),
+                transition: Some(Duration::from_secs(30)),
+            }],
+            first
+        );
+        assert!(fades.tick(start() + Duration::from_secs(29)).is_empty());
+
+        let second = fades.tick(start() + Duration::from_secs(30));
+        assert_eq!(
+            vec![Command::ControlDevice {
+                device_id: "1".to_string(),
+                properties: HashMap::from([(
+                    "brightness".to_string(),
+                    PropertyValue::Number(Some(2.0 + 2.0 * 98.0 / 60.0))
+                )]),
+                transition: Some(Duration::from_secs(30)),
+            }],
+            second
+        );
+
+        let last = fades.tick(start() + Duration::from_secs(30 * 60));
+        assert_eq!(1, last.len());
+        assert!(!fades.is_fading("1"));
+        Ok(())
+    }
+
+    #[test]
+    fn is_cancelled_by_a_manual_change() -> Result<(), PropertyError> {
+        let mut fades = Fades::new();
+        let registry = registry(light(true, 10.0));
+        fades.start(
+            "1",
+            brightness(70.0),
+            Duration::from_secs(300),
+            &registry,
+            start(),
+        )?;
+        fades.tick(start() + Duration::from_secs(30));
+
+        // The reports of the fade's own steps are no manual change
+        fades.handle_event(&changed("brightness", PropertyValue::Number(Some(16.0))));
+        fades.handle_event(&changed("brightness", PropertyValue::Number(Some(22.0))));
+        assert!(fades.is_fading("1"));
+
+        fades.handle_event(&changed("brightness", PropertyValue::Number(Some(100.0))));
+        assert!(!fades.is_fading("1"));
+        assert!(fades.tick(start() + Duration::from_secs(60)).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn is_cancelled_when_the_light_turns_off() -> Result<(), PropertyError> {
+        let mut fades = Fades::new();
+        let registry = registry(light(true, 10.0));
+        fades.start(
+            "1",
+            brightness(70.0),
+            Duration::from_secs(300),
+            &registry,
+            start(),
+        )?;
+
+        fades.handle_event(&changed("on", PropertyValue::Boolean(false)));
+
+        assert!(!fades.is_fading("1"));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_targets_out_of_range() {
+        let mut fades = Fades::new();
+        let registry = registry(light(true, 10.0));
+
+        let result = fades.start(
+            "1",
+            brightness(120.0),
+            Duration::from_secs(300),
+            &registry,
+            start(),
+        );
+
+        assert!(matches!(result, Err(PropertyError::OutOfRange { .. })));
+        assert!(!fades.is_fading("1"));
+    }
+}
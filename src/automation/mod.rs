@@ -0,0 +1,14 @@
+mod adaptive_lighting;
+mod button_bindings;
+mod fade;
+mod occupancy;
+mod sun;
+mod tolerance;
+mod vacation;
+
+pub use adaptive_lighting::{AdaptiveLighting, AdaptiveLightingConfig, CircadianCurve};
+pub use button_bindings::{ButtonAction, ButtonBinding, ButtonBindings, ButtonTrigger};
+pub use fade::Fades;
+pub use occupancy::{Occupancy, OccupancyConfig, ScheduledScene};
+pub use sun::solar_elevation;
+pub use vacation::{HistoryEntry, LightHistory, Vacation, VacationConfig};
@@ -0,0 +1,639 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use serde::Deserialize;
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::model::{Property, PropertyValue};
+use crate::registry::Registry;
+
+/// A scene that is used for motion from `from` (local time) until the next scheduled scene starts.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ScheduledScene {
+    from: NaiveTime,
+    scene: String,
+}
+
+impl ScheduledScene {
+    pub fn new(from: NaiveTime, scene: String) -> Self {
+        ScheduledScene { from, scene }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct OccupancyConfig {
+    room: String,
+    #[serde(default)]
+    scenes: Vec<ScheduledScene>,
+    /// Lights only turn on when a motion sensor in the room reports a light level at or below this value.
+    max_light_level: Option<usize>,
+    #[serde(default = "default_timeout_seconds")]
+    timeout_seconds: u64,
+    #[serde(default = "default_warning_seconds")]
+    warning_seconds: u64,
+    #[serde(default = "default_pause_seconds")]
+    pause_seconds: u64,
+}
+
+fn default_timeout_seconds() -> u64 {
+    300
+}
+
+fn default_warning_seconds() -> u64 {
+    30
+}
+
+fn default_pause_seconds() -> u64 {
+    3600
+}
+
+impl OccupancyConfig {
+    /// `room` is the id or name of the room whose motion sensors control its lights.
+    pub fn new(room: String) -> Self {
+        OccupancyConfig {
+            room,
+            scenes: vec![],
+            max_light_level: None,
+            timeout_seconds: default_timeout_seconds(),
+            warning_seconds: default_warning_seconds(),
+            pause_seconds: default_pause_seconds(),
+        }
+    }
+
+    pub fn with_scenes(mut self, scenes: Vec<ScheduledScene>) -> Self {
+        self.scenes = scenes;
+        self
+    }
+
+    pub fn with_max_light_level(mut self, max_light_level: usize) -> Self {
+        self.max_light_level = Some(max_light_level);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration, warning: Duration) -> Self {
+        self.timeout_seconds = timeout.as_secs();
+        self.warning_seconds = warning.as_secs();
+        self
+    }
+
+    pub fn with_pause(mut self, pause: Duration) -> Self {
+        self.pause_seconds = pause.as_secs();
+        self
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds)
+    }
+
+    fn warning(&self) -> Duration {
+        Duration::from_secs(self.warning_seconds.min(self.timeout_seconds))
+    }
+
+    fn pause(&self) -> Duration {
+        Duration::from_secs(self.pause_seconds)
+    }
+}
+
+/// Turns the lights of a room on when its motion sensors detect motion, and off again once there has been no motion
+/// for the timeout. The lights are dimmed shortly before they are turned off, so anyone still there can move to keep
+/// them on. Pressing a switch in the room pauses the automation.
+pub struct Occupancy {
+    rooms: Vec<RoomOccupancy>,
+}
+
+struct RoomOccupancy {
+    config: OccupancyConfig,
+    state: OccupancyState,
+    active_sensors: HashSet<String>,
+    last_motion: Option<DateTime<Utc>>,
+    paused_until: Option<DateTime<Utc>>,
+}
+
+#[derive(PartialEq, Debug)]
+enum OccupancyState {
+    Vacant,
+    Occupied,
+    /// Dimmed before switching off, holds the brightness to restore.
+    Warned(HashMap<String, f64>),
+}
+
+impl Occupancy {
+    pub fn new(configs: Vec<OccupancyConfig>) -> Occupancy {
+        Occupancy {
+            rooms: configs
+                .into_iter()
+                .map(|config| RoomOccupancy {
+                    config,
+                    state: OccupancyState::Vacant,
+                    active_sensors: HashSet::new(),
+                    last_motion: None,
+                    paused_until: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_paused(&self, room: &str) -> bool {
+        self.rooms
+            .iter()
+            .any(|r| r.config.room == room && r.paused_until.is_some())
+    }
+
+    pub fn handle_event(
+        &mut self,
+        event: &Event,
+        registry: &Registry,
+        now: DateTime<Utc>,
+    ) -> Vec<Command> {
+        let mut commands = vec![];
+        for room in self.rooms.iter_mut() {
+            let Some(device_ids) = registry
+                .find_room(&room.config.room)
+                .map(|r| r.device_ids().clone())
+            else {
+                continue;
+            };
+
+            match event {
+                Event::PropertiesChanged {
+                    device_id,
+                    properties,
+                } if device_ids.contains(device_id) => {
+                    if let Some(PropertyValue::Boolean(motion)) = properties.get("motion") {
+                        commands.extend(room.motion(device_id, *motion, registry, now));
+                    }
+                    if properties.contains_key("on")
+                        && room.state != OccupancyState::Vacant
+                        && !any_light_on(&room.config.room, registry)
+                    {
+                        room.state = OccupancyState::Vacant;
+                    }
+                }
+                Event::ButtonPressed { device_id, .. } if device_ids.contains(device_id) => {
+                    room.paused_until = Some(now + room.config.pause());
+                    room.state = OccupancyState::Vacant;
+                }
+                _ => {}
+            }
+        }
+        commands
+    }
+
+    pub fn tick(&mut self, registry: &Registry, now: DateTime<Utc>) -> Vec<Command> {
+        self.rooms
+            .iter_mut()
+            .flat_map(|room| room.tick(registry, now))
+            .collect()
+    }
+}
+
+impl RoomOccupancy {
+    fn motion(
+        &mut self,
+        sensor_id: &str,
+        motion: bool,
+        registry: &Registry,
+        now: DateTime<Utc>,
+    ) -> Vec<Command> {
+        self.last_motion = Some(now);
+        if !motion {
+            self.active_sensors.remove(sensor_id);
+            return vec![];
+        }
+
+        self.active_sensors.insert(sensor_id.to_string());
+        if self.paused_until.is_some() {
+            return vec![];
+        }
+
+        match &self.state {
+            OccupancyState::Vacant if self.is_dark(registry) => {
+                self.state = OccupancyState::Occupied;
+                self.turn_on(registry, now)
+            }
+            OccupancyState::Warned(brightness) => {
+                let commands = brightness
+                    .iter()
+                    .map(|(device_id, brightness)| set_brightness(device_id, *brightness))
+                    .collect();
+                self.state = OccupancyState::Occupied;
+                commands
+            }
+            _ => vec![],
+        }
+    }
+
+    fn tick(&mut self, registry: &Registry, now: DateTime<Utc>) -> Vec<Command> {
+        if let Some(paused_until) = self.paused_until {
+            if now < paused_until {
+                return vec![];
+            }
+            self.paused_until = None;
+            if any_light_on(&self.config.room, registry) {
+                self.state = OccupancyState::Occupied;
+                self.last_motion = Some(now);
+            }
+        }
+
+        let Some(last_motion) = self.last_motion else {
+            return vec![];
+        };
+        if self.state == OccupancyState::Vacant || !self.active_sensors.is_empty() {
+            return vec![];
+        }
+
+        if now >= last_motion + self.config.timeout() {
+            self.state = OccupancyState::Vacant;
+            lights(&self.config.room, registry)
+                .into_iter()
+                .map(|(device_id, _)| Command::ControlDevice {
+                    device_id,
+                    properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(false))]),
+                    transition: None,
+                })
+                .collect()
+        } else if self.state == OccupancyState::Occupied
+            && now >= last_motion + self.config.timeout() - self.config.warning()
+        {
+            let brightness: HashMap<String, f64> = lights(&self.config.room, registry)
+                .into_iter()
+                .filter_map(|(device_id, brightness)| Some((device_id, brightness?)))
+                .collect();
+            let commands = brightness
+                .iter()
+                .map(|(device_id, brightness)| {
+                    let minimum = minimum_brightness(device_id, registry);
+                    set_brightness(device_id, (brightness / 2.0).round().max(minimum))
+                })
+                .collect();
+            self.state = OccupancyState::Warned(brightness);
+            commands
+        } else {
+            vec![]
+        }
+    }
+
+    fn is_dark(&self, registry: &Registry) -> bool {
+        let Some(max_light_level) = self.config.max_light_level else {
+            return true;
+        };
+        let light_levels: Vec<f64> = registry
+            .resolve_device_ids(&self.config.room)
+            .iter()
+            .filter_map(|id| {
+                match registry
+                    .device(id)?
+                    .properties()
+                    .get("light_level")?
+                    .value()
+                {
+                    PropertyValue::Number(light_level) => light_level,
+                    _ => None,
+                }
+            })
+            .collect();
+
+        light_levels.is_empty() || light_levels.iter().any(|l| *l <= max_light_level as f64)
+    }
+
+    fn turn_on(&self, registry: &Registry, now: DateTime<Utc>) -> Vec<Command> {
+        let time = now.with_timezone(&Local).time();
+        let room_id = registry.find_room(&self.config.room).map(|room| room.id());
+        let scene = select_scene(&self.config.scenes, time)
+            .and_then(|scene| registry.find_scene(&scene.scene, room_id));
+
+        if let Some(scene) = scene {
+            return vec![Command::RecallScene {
+                scene_id: scene.id().clone(),
+                transition: None,
+            }];
+        }
+        lights(&self.config.room, registry)
+            .into_iter()
+            .map(|(device_id, _)| Command::ControlDevice {
+                device_id,
+                properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(true))]),
+                transition: None,
+            })
+            .collect()
+    }
+}
+
+/// Picks the scene that started last before `time`, wrapping around to the last scene of the previous day.
+fn select_scene(scenes: &[ScheduledScene], time: NaiveTime) -> Option<&ScheduledScene> {
+    scenes
+        .iter()
+        .filter(|scene| scene.from <= time)
+        .max_by_key(|scene| scene.from)
+        .or_else(|| scenes.iter().max_by_key(|scene| scene.from))
+}
+
+/// Returns the lights of a room with their brightness if they are on.
+fn lights(room: &str, registry: &Registry) -> Vec<(String, Option<f64>)> {
+    registry
+        .resolve_device_ids(room)
+        .into_iter()
+        .filter_map(|id| {
+            let device = registry.device(&id)?;
+            let on = match device.properties().get("on")? {
+                Property::Boolean(on) => on.value(),
+                _ => return None,
+            };
+            let brightness = match device.properties().get("brightness").map(|b| b.value()) {
+                Some(PropertyValue::Number(brightness)) if on => brightness,
+                _ => None,
+            };
+            Some((id, brightness))
+        })
+        .collect()
+}
+
+/// The lowest brightness a light can be dimmed to, e.g. the `min_dim_level` of a Hue light.
+fn minimum_brightness(device_id: &str, registry: &Registry) -> f64 {
+    match registry
+        .device(device_id)
+        .and_then(|device| device.properties().get("brightness"))
+    {
+        Some(Property::Number(brightness)) => brightness.minimum().unwrap_or(1.0),
+        _ => 1.0,
+    }
+}
+
+fn any_light_on(room: &str, registry: &Registry) -> bool {
+    registry.resolve_device_ids(room).iter().any(|id| {
+        registry
+            .device(id)
+            .and_then(|device| device.properties().get("on"))
+            .is_some_and(|on| on.value() == PropertyValue::Boolean(true))
+    })
+}
+
+fn set_brightness(device_id: &str, brightness: f64) -> Command {
+    Command::ControlDevice {
+        device_id: device_id.to_string(),
+        properties: HashMap::from([(
+            "brightness".to_string(),
+            PropertyValue::Number(Some(brightness)),
+        )]),
+        transition: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BooleanProperty, NumberProperty, PropertyType, Room, Scene, Unit};
+    use crate::test_support;
+    use chrono::TimeZone;
+
+    fn boolean(name: &str, property_type: PropertyType, value: bool) -> Property {
+        Property::Boolean(BooleanProperty::new(
+            name.to_string(),
+            false,
+            property_type,
+            None,
+            value,
+        ))
+    }
+
+    fn number(name: &str, property_type: PropertyType, value: f64) -> Property {
+        Property::Number(NumberProperty::new(
+            name.to_string(),
+            false,
+            property_type,
+            None,
+            Unit::Percentage,
+            Some(value),
+            None,
+            None,
+        ))
+    }
+
+    fn registry(light_level: f64) -> Registry {
+        let mut registry = Registry::new();
+        registry.apply(&Event::DiscoveredDevices(vec![
+            test_support::light("light")
+                .on(false)
+                .brightness(80.0)
+                .build(),
+            test_support::sensor("sensor")
+                .motion(false)
+                .property(number("light_level", PropertyType::LightLevel, light_level))
+                .build(),
+            test_support::switch("dimmer")
+                .property(boolean("button_1", PropertyType::Button, false))
+                .build(),
+        ]));
+        registry.apply(&Event::DiscoveredRooms(vec![Room::new(
+            "hallway".to_string(),
+            "Hallway".to_string(),
+            vec![
+                "light".to_string(),
+                "sensor".to_string(),
+                "dimmer".to_string(),
+            ],
+        )]));
+        registry.apply(&Event::DiscoveredScenes(vec![Scene::new(
+            "bright".to_string(),
+            "Bright".to_string(),
+            Some("hallway".to_string()),
+        )]));
+        registry
+    }
+
+    fn changed(
+        registry: &mut Registry,
+        device_id: &str,
+        name: &str,
+        value: PropertyValue,
+    ) -> Event {
+        let event = Event::PropertiesChanged {
+            device_id: device_id.to_string(),
+            properties: HashMap::from([(name.to_string(), value)]),
+        };
+        registry.apply(&event);
+        event
+    }
+
+    fn occupancy() -> Occupancy {
+        Occupancy::new(vec![OccupancyConfig::new("Hallway".to_string())
+            .with_scenes(vec![ScheduledScene::new(
+                NaiveTime::MIN,
+                "Bright".to_string(),
+            )])
+            .with_max_light_level(10000)
+            .with_timeout(Duration::from_secs(60), Duration::from_secs(10))])
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 11, 14, 22, 13, 20).unwrap()
+    }
+
+    fn seconds(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn recalls_a_scene_on_motion_in_the_dark() {
+        let mut registry = registry(5000.0);
+        let mut occupancy = occupancy();
+
+        let motion = changed(
+            &mut registry,
+            "sensor",
+            "motion",
+            PropertyValue::Boolean(true),
+        );
+        let commands = occupancy.handle_event(&motion, &registry, now());
+
+        assert_eq!(
+            vec![Command::RecallScene {
+                scene_id: "bright".to_string(),
+                transition: None,
+            }],
+            commands
+        );
+    }
+
+    #[test]
+    fn ignores_motion_when_the_room_is_bright() {
+        let mut registry = registry(20000.0);
+        let mut occupancy = occupancy();
+
+        let motion = changed(
+            &mut registry,
+            "sensor",
+            "motion",
+            PropertyValue::Boolean(true),
+        );
+
+        assert!(occupancy.handle_event(&motion, &registry, now()).is_empty());
+    }
+
+    #[test]
+    fn dims_before_switching_off() {
+        let mut registry = registry(5000.0);
+        let mut occupancy = occupancy();
+        let motion = changed(
+            &mut registry,
+            "sensor",
+            "motion",
+            PropertyValue::Boolean(true),
+        );
+        occupancy.handle_event(&motion, &registry, now());
+        changed(&mut registry, "light", "on", PropertyValue::Boolean(true));
+        let no_motion = changed(
+            &mut registry,
+            "sensor",
+            "motion",
+            PropertyValue::Boolean(false),
+        );
+        occupancy.handle_event(&no_motion, &registry, now());
+
+        assert!(occupancy.tick(&registry, now() + seconds(49)).is_empty());
+        assert_eq!(
+            vec![set_brightness("light", 40.0)],
+            occupancy.tick(&registry, now() + seconds(50))
+        );
+
+        // Motion during the warning restores the brightness
+        let motion = changed(
+            &mut registry,
+            "sensor",
+            "motion",
+            PropertyValue::Boolean(true),
+        );
+        assert_eq!(
+            vec![set_brightness("light", 80.0)],
+            occupancy.handle_event(&motion, &registry, now() + seconds(55))
+        );
+        let no_motion = changed(
+            &mut registry,
+            "sensor",
+            "motion",
+            PropertyValue::Boolean(false),
+        );
+        occupancy.handle_event(&no_motion, &registry, now() + seconds(60));
+
+        occupancy.tick(&registry, now() + seconds(110));
+        assert_eq!(
+            vec![Command::ControlDevice {
+                device_id: "light".to_string(),
+                properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(false))]),
+                transition: None,
+            }],
+            occupancy.tick(&registry, now() + seconds(120))
+        );
+    }
+
+    #[test]
+    fn dims_no_further_than_the_minimum_of_a_light() {
+        let mut registry = registry(5000.0);
+        registry.apply(&Event::DiscoveredDevices(vec![test_support::light(
+            "light",
+        )
+        .on(true)
+        .brightness_from(10.0, 8.0)
+        .build()]));
+        let mut occupancy = occupancy();
+        let motion = changed(
+            &mut registry,
+            "sensor",
+            "motion",
+            PropertyValue::Boolean(true),
+        );
+        occupancy.handle_event(&motion, &registry, now());
+        let no_motion = changed(
+            &mut registry,
+            "sensor",
+            "motion",
+            PropertyValue::Boolean(false),
+        );
+        occupancy.handle_event(&no_motion, &registry, now());
+
+        assert_eq!(
+            vec![set_brightness("light", 8.0)],
+            occupancy.tick(&registry, now() + seconds(50))
+        );
+    }
+
+    #[test]
+    fn pauses_after_a_button_press() {
+        let mut registry = registry(5000.0);
+        let mut occupancy = occupancy();
+        let pressed = Event::ButtonPressed {
+            device_id: "dimmer".to_string(),
+            control_id: 1,
+            event: crate::model::ButtonEvent::ShortRelease,
+            repeat_interval: Duration::from_millis(800),
+        };
+
+        occupancy.handle_event(&pressed, &registry, now());
+        let motion = changed(
+            &mut registry,
+            "sensor",
+            "motion",
+            PropertyValue::Boolean(true),
+        );
+
+        assert!(occupancy.is_paused("Hallway"));
+        assert!(occupancy.handle_event(&motion, &registry, now()).is_empty());
+        occupancy.tick(&registry, now() + seconds(3600));
+        assert!(!occupancy.is_paused("Hallway"));
+    }
+
+    #[test]
+    fn selects_the_scene_by_time_of_day() {
+        let time = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        let scenes = vec![
+            ScheduledScene::new(time(7), "Energize".to_string()),
+            ScheduledScene::new(time(22), "Nightlight".to_string()),
+        ];
+
+        assert_eq!("Energize", select_scene(&scenes, time(12)).unwrap().scene);
+        assert_eq!("Nightlight", select_scene(&scenes, time(23)).unwrap().scene);
+        assert_eq!("Nightlight", select_scene(&scenes, time(3)).unwrap().scene);
+    }
+}
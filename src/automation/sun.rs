@@ -0,0 +1,71 @@
+use std::f64::consts::PI;
+
+use chrono::{DateTime, Utc};
+
+/// Returns the elevation of the sun above the horizon in degrees, using the low precision algorithm from the
+/// Astronomical Almanac which is accurate to about a degree — plenty for deciding how warm a lamp should be.
+pub fn solar_elevation(latitude: f64, longitude: f64, time: DateTime<Utc>) -> f64 {
+    let days = time.timestamp() as f64 / 86_400.0 + 2_440_587.5 - 2_451_545.0; // Since J2000.0
+
+    let mean_longitude = (280.460 + 0.985_647_4 * days).rem_euclid(360.0);
+    let mean_anomaly = (357.528 + 0.985_600_3 * days)
+        .rem_euclid(360.0)
+        .to_radians();
+    let ecliptic_longitude =
+        (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin())
+            .to_radians();
+    let obliquity = (23.439 - 0.000_000_4 * days).to_radians();
+
+    let right_ascension =
+        (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+
+    let sidereal_time = (18.697_374_558 + 24.065_709_824_419_08 * days).rem_euclid(24.0) * 15.0;
+    let hour_angle = (sidereal_time + longitude).to_radians() - right_ascension;
+
+    let latitude = latitude.to_radians();
+    let elevation = (latitude.sin() * declination.sin()
+        + latitude.cos() * declination.cos() * hour_angle.cos())
+    .asin();
+
+    elevation * 180.0 / PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const AMSTERDAM: (f64, f64) = (52.37, 4.89);
+
+    #[test]
+    fn sun_is_high_at_noon_in_summer() {
+        let noon = Utc.with_ymd_and_hms(2023, 6, 21, 11, 40, 0).unwrap();
+        let elevation = solar_elevation(AMSTERDAM.0, AMSTERDAM.1, noon);
+
+        assert!(
+            (60.0..62.0).contains(&elevation),
+            "elevation = {}",
+            elevation
+        );
+    }
+
+    #[test]
+    fn sun_is_low_at_noon_in_winter() {
+        let noon = Utc.with_ymd_and_hms(2023, 12, 21, 11, 40, 0).unwrap();
+        let elevation = solar_elevation(AMSTERDAM.0, AMSTERDAM.1, noon);
+
+        assert!(
+            (13.0..15.0).contains(&elevation),
+            "elevation = {}",
+            elevation
+        );
+    }
+
+    #[test]
+    fn sun_is_below_the_horizon_at_midnight() {
+        let midnight = Utc.with_ymd_and_hms(2023, 6, 21, 23, 40, 0).unwrap();
+
+        assert!(solar_elevation(AMSTERDAM.0, AMSTERDAM.1, midnight) < 0.0);
+    }
+}
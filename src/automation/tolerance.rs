@@ -0,0 +1,12 @@
+/// Reported values may differ slightly from what was sent, the bridge rounds brightness and clamps mirek.
+const BRIGHTNESS_TOLERANCE: f64 = 2.0;
+const MIREK_TOLERANCE: f64 = 5.0;
+
+/// How far the value a light reports for the number property `name` may be from the one it was sent, before the
+/// difference means that someone else changed it.
+pub(super) fn tolerance(name: &str) -> f64 {
+    match name {
+        "color_temperature" => MIREK_TOLERANCE,
+        _ => BRIGHTNESS_TOLERANCE,
+    }
+}
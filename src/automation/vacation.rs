@@ -0,0 +1,471 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::model::{Presence, PropertyValue};
+use crate::registry::Registry;
+
+/// A light is never switched again within this time of its previous replayed switch, so jitter cannot reorder them.
+const MIN_SWITCH_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+/// How far ahead a replayed pattern is planned. Each day of a vacation replays a randomly chosen previous week.
+const PLAN_WINDOW: StdDuration = StdDuration::from_secs(24 * 60 * 60);
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct VacationConfig {
+    /// Rooms or devices, given by id or name, whose lights are recorded and replayed. Empty means every light.
+    #[serde(default)]
+    targets: Vec<String>,
+    #[serde(default = "default_weeks")]
+    weeks: u32,
+    #[serde(default = "default_jitter_minutes")]
+    jitter_minutes: u32,
+}
+
+fn default_weeks() -> u32 {
+    3
+}
+
+fn default_jitter_minutes() -> u32 {
+    15
+}
+
+impl Default for VacationConfig {
+    fn default() -> Self {
+        VacationConfig {
+            targets: vec![],
+            weeks: default_weeks(),
+            jitter_minutes: default_jitter_minutes(),
+        }
+    }
+}
+
+impl VacationConfig {
+    pub fn new() -> Self {
+        VacationConfig::default()
+    }
+
+    pub fn with_targets(mut self, targets: Vec<String>) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    /// The number of previous weeks of history that are kept and replayed from.
+    pub fn with_weeks(mut self, weeks: u32) -> Self {
+        self.weeks = weeks.max(1);
+        self
+    }
+
+    /// Every replayed switch happens up to this many minutes earlier or later than it did in the recorded week.
+    pub fn with_jitter(mut self, minutes: u32) -> Self {
+        self.jitter_minutes = minutes;
+        self
+    }
+
+    fn history_length(&self) -> Duration {
+        Duration::weeks(self.weeks.max(1) as i64)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct HistoryEntry {
+    device_id: String,
+    on: bool,
+    time: DateTime<Utc>,
+}
+
+impl HistoryEntry {
+    pub fn new(device_id: String, on: bool, time: DateTime<Utc>) -> Self {
+        HistoryEntry {
+            device_id,
+            on,
+            time,
+        }
+    }
+
+    pub fn device_id(&self) -> &String {
+        &self.device_id
+    }
+
+    pub fn on(&self) -> bool {
+        self.on
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+}
+
+/// Every time a light was switched on or off, oldest first. Serializes to a list, so it can be kept across restarts.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(transparent)]
+pub struct LightHistory {
+    entries: Vec<HistoryEntry>,
+}
+
+impl LightHistory {
+    pub fn new(mut entries: Vec<HistoryEntry>) -> Self {
+        entries.sort_by_key(|e| e.time);
+        LightHistory { entries }
+    }
+
+    pub fn entries(&self) -> &Vec<HistoryEntry> {
+        &self.entries
+    }
+
+    fn record(&mut self, device_id: &str, on: bool, time: DateTime<Utc>) {
+        self.entries
+            .push(HistoryEntry::new(device_id.to_string(), on, time));
+    }
+
+    fn prune(&mut self, before: DateTime<Utc>) {
+        self.entries.retain(|e| e.time >= before);
+    }
+
+    fn device_ids(&self) -> HashSet<&String> {
+        self.entries.iter().map(|e| &e.device_id).collect()
+    }
+
+    /// The state of the light at `time`, i.e. the last switch before it.
+    fn state_at(&self, device_id: &str, time: DateTime<Utc>) -> Option<bool> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.device_id == device_id && e.time < time)
+            .map(|e| e.on)
+    }
+
+    fn between(
+        &self,
+        device_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Iterator<Item = &HistoryEntry> {
+        let device_id = device_id.to_string();
+        self.entries
+            .iter()
+            .filter(move |e| e.device_id == device_id && e.time >= from && e.time < to)
+    }
+}
+
+/// Records when lights are switched while someone is home, and replays that while everyone is away so the house looks
+/// occupied. Each light follows what it did in a randomly chosen previous week, shifted by a random jitter. The
+/// replay stops as soon as the presence changes back, leaving the lights as they are.
+pub struct Vacation {
+    config: VacationConfig,
+    history: LightHistory,
+    presence: Presence,
+    plan: Vec<HistoryEntry>,
+    planned_until: Option<DateTime<Utc>>,
+    rng: StdRng,
+}
+
+impl Vacation {
+    pub fn new(config: VacationConfig, history: LightHistory) -> Self {
+        Vacation {
+            config,
+            history,
+            presence: Presence::default(),
+            plan: vec![],
+            planned_until: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn history(&self) -> &LightHistory {
+        &self.history
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.presence == Presence::Away
+    }
+
+    pub fn handle_event(
+        &mut self,
+        event: &Event,
+        registry: &Registry,
+        now: DateTime<Utc>,
+    ) -> Vec<Command> {
+        match event {
+            Event::PropertiesChanged {
+                device_id,
+                properties,
+            } if !self.is_active() => {
+                if let Some(PropertyValue::Boolean(on)) = properties.get("on") {
+                    if self.targets(device_id, registry) {
+                        self.history.record(device_id, *on, now);
+                        self.history.prune(now - self.config.history_length());
+                    }
+                }
+                vec![]
+            }
+            Event::PresenceChanged(presence) if *presence != self.presence => {
+                self.presence = *presence;
+                self.plan.clear();
+                self.planned_until = None;
+                self.tick(registry, now)
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Switches the lights whose replayed time has come, planning the next day once the current one is over.
+    pub fn tick(&mut self, registry: &Registry, now: DateTime<Utc>) -> Vec<Command> {
+        if !self.is_active() {
+            return vec![];
+        }
+        // Start over from now if ticks stopped for longer than a window, rather than catching up on every missed switch
+        let from = match self.planned_until {
+            Some(until) if now < until + PLAN_WINDOW => until,
+            _ => now,
+        };
+        if now >= from {
+            self.plan_window(from, from + PLAN_WINDOW);
+        }
+
+        let due = self.plan.iter().take_while(|e| e.time <= now).count();
+        self.plan
+            .drain(..due)
+            .filter(|e| {
+                let current = registry
+                    .device(&e.device_id)
+                    .and_then(|device| device.properties().get("on"))
+                    .map(|on| on.value());
+                current.is_some() && current != Some(PropertyValue::Boolean(e.on))
+            })
+            .map(|e| Command::ControlDevice {
+                device_id: e.device_id,
+                properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(e.on))]),
+                transition: None,
+            })
+            .collect()
+    }
+
+    fn targets(&self, device_id: &str, registry: &Registry) -> bool {
+        self.config.targets.is_empty()
+            || self.config.targets.iter().any(|target| {
+                registry
+                    .resolve_device_ids(target)
+                    .iter()
+                    .any(|id| id == device_id)
+            })
+    }
+
+    fn plan_window(&mut self, from: DateTime<Utc>, to: DateTime<Utc>) {
+        let jitter = self.config.jitter_minutes as i64;
+        let mut device_ids: Vec<String> = self.history.device_ids().into_iter().cloned().collect();
+        device_ids.sort();
+
+        for device_id in device_ids {
+            let weeks_with_history: Vec<u32> = (1..=self.config.weeks)
+                .filter(|weeks| {
+                    let offset = Duration::weeks(*weeks as i64);
+                    self.history.state_at(&device_id, from - offset).is_some()
+                        || self
+                            .history
+                            .between(&device_id, from - offset, to - offset)
+                            .next()
+                            .is_some()
+                })
+                .collect();
+            if weeks_with_history.is_empty() {
+                continue;
+            }
+            let weeks = weeks_with_history[self.rng.gen_range(0..weeks_with_history.len())];
+            let offset = Duration::weeks(weeks as i64);
+
+            let mut previous = from;
+            if let Some(on) = self.history.state_at(&device_id, from - offset) {
+                self.plan
+                    .push(HistoryEntry::new(device_id.clone(), on, from));
+                previous = from + MIN_SWITCH_INTERVAL;
+            }
+            let recorded: Vec<HistoryEntry> = self
+                .history
+                .between(&device_id, from - offset, to - offset)
+                .cloned()
+                .collect();
+            for entry in recorded {
+                let jitter = Duration::minutes(self.rng.gen_range(-jitter..=jitter));
+                let time = (entry.time + offset + jitter).max(previous);
+                self.plan
+                    .push(HistoryEntry::new(device_id.clone(), entry.on, time));
+                previous = time + MIN_SWITCH_INTERVAL;
+            }
+        }
+
+        self.plan.sort_by_key(|e| e.time);
+        self.planned_until = Some(to);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Device, Room};
+    use crate::test_support;
+    use chrono::TimeZone;
+
+    fn light(id: &str, on: bool) -> Device {
+        test_support::light(id).on(on).build()
+    }
+
+    fn registry(lights: Vec<Device>) -> Registry {
+        let mut registry = Registry::new();
+        registry.apply(&Event::DiscoveredDevices(lights));
+        registry.apply(&Event::DiscoveredRooms(vec![Room::new(
+            "room".to_string(),
+            "Living room".to_string(),
+            vec!["1".to_string()],
+        )]));
+        registry
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 15, 12, 0, 0).unwrap()
+    }
+
+    fn switched(device_id: &str, on: bool) -> Event {
+        Event::PropertiesChanged {
+            device_id: device_id.to_string(),
+            properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(on))]),
+        }
+    }
+
+    fn on(device_id: &str, on: bool) -> Command {
+        Command::ControlDevice {
+            device_id: device_id.to_string(),
+            properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(on))]),
+            transition: None,
+        }
+    }
+
+    fn vacation(history: Vec<HistoryEntry>) -> Vacation {
+        Vacation::new(
+            VacationConfig::new().with_weeks(1).with_jitter(0),
+            LightHistory::new(history),
+        )
+    }
+
+    #[test]
+    fn records_targeted_lights_while_home() {
+        let registry = registry(vec![light("1", false), light("2", false)]);
+        let mut vacation = Vacation::new(
+            VacationConfig::new().with_targets(vec!["Living room".to_string()]),
+            LightHistory::default(),
+        );
+
+        vacation.handle_event(&switched("1", true), &registry, now());
+        vacation.handle_event(&switched("2", true), &registry, now());
+        vacation.handle_event(&Event::PresenceChanged(Presence::Away), &registry, now());
+        vacation.handle_event(&switched("1", false), &registry, now());
+
+        assert_eq!(
+            &vec![HistoryEntry::new("1".to_string(), true, now())],
+            vacation.history().entries()
+        );
+    }
+
+    #[test]
+    fn replays_the_previous_week_while_away() {
+        let registry = registry(vec![light("1", false)]);
+        let week_ago = now() - Duration::weeks(1);
+        let mut vacation = vacation(vec![
+            HistoryEntry::new("1".to_string(), true, week_ago + Duration::hours(6)),
+            HistoryEntry::new("1".to_string(), false, week_ago + Duration::hours(10)),
+        ]);
+
+        let commands =
+            vacation.handle_event(&Event::PresenceChanged(Presence::Away), &registry, now());
+        assert!(commands.is_empty());
+
+        let commands = vacation.tick(&registry, now() + Duration::hours(6));
+        assert_eq!(vec![on("1", true)], commands);
+
+        let registry = self::registry(vec![light("1", true)]);
+        assert!(vacation
+            .tick(&registry, now() + Duration::hours(7))
+            .is_empty());
+        let commands = vacation.tick(&registry, now() + Duration::hours(10));
+        assert_eq!(vec![on("1", false)], commands);
+    }
+
+    #[test]
+    fn restores_the_state_of_the_previous_week_when_leaving() {
+        let registry = registry(vec![light("1", false)]);
+        let mut vacation = vacation(vec![HistoryEntry::new(
+            "1".to_string(),
+            true,
+            now() - Duration::weeks(1) - Duration::hours(1),
+        )]);
+
+        let commands =
+            vacation.handle_event(&Event::PresenceChanged(Presence::Away), &registry, now());
+
+        assert_eq!(vec![on("1", true)], commands);
+    }
+
+    #[test]
+    fn stops_as_soon_as_someone_is_home() {
+        let registry = registry(vec![light("1", false)]);
+        let mut vacation = vacation(vec![HistoryEntry::new(
+            "1".to_string(),
+            true,
+            now() - Duration::weeks(1) + Duration::hours(6),
+        )]);
+
+        vacation.handle_event(&Event::PresenceChanged(Presence::Away), &registry, now());
+        vacation.handle_event(
+            &Event::PresenceChanged(Presence::Home),
+            &registry,
+            now() + Duration::hours(1),
+        );
+
+        assert!(!vacation.is_active());
+        assert!(vacation
+            .tick(&registry, now() + Duration::hours(6))
+            .is_empty());
+    }
+
+    #[test]
+    fn keeps_the_order_of_switches_with_jitter() {
+        let registry = registry(vec![light("1", false)]);
+        let week_ago = now() - Duration::weeks(1);
+        let mut vacation = Vacation::new(
+            VacationConfig::new().with_weeks(1).with_jitter(30),
+            LightHistory::new(vec![
+                HistoryEntry::new("1".to_string(), true, week_ago + Duration::hours(1)),
+                HistoryEntry::new(
+                    "1".to_string(),
+                    false,
+                    week_ago + Duration::hours(1) + Duration::minutes(5),
+                ),
+            ]),
+        );
+
+        vacation.handle_event(&Event::PresenceChanged(Presence::Away), &registry, now());
+
+        let plan: Vec<bool> = vacation.plan.iter().map(|e| e.on).collect();
+        assert_eq!(vec![true, false], plan);
+        assert!(vacation.plan[0].time + MIN_SWITCH_INTERVAL <= vacation.plan[1].time);
+    }
+
+    #[test]
+    fn deserializes_the_history() -> Result<(), serde_json::Error> {
+        let history: LightHistory = serde_json::from_str(
+            r#"[{"device_id": "1", "on": true, "time": "2024-03-15T12:00:00Z"}]"#,
+        )?;
+
+        assert_eq!(
+            &vec![HistoryEntry::new("1".to_string(), true, now())],
+            history.entries()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,869 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Duration;
+
+use chrono::Utc;
+use serde_json::{json, Map, Value};
+use tokio::sync::mpsc;
+use tokio::time::{interval, sleep};
+
+use chambrier::automation::Fades;
+use chambrier::command::Command;
+use chambrier::event::Event;
+use chambrier::hue::{
+    self, EntertainmentArea, EntertainmentChannel, Frame, HueClient, HueClientError, HueController,
+    HueEntertainment, HueObserver,
+};
+use chambrier::model::{
+    Device, DeviceType, Hsv, Property, PropertyValue, Rgb, Room, Unit, UpdateState, Xy,
+};
+use chambrier::registry::{Registry, Snapshot};
+
+/// How long to wait for the link button to be pressed, the bridge then hands out keys for 30 seconds.
+const PAIRING_ATTEMPTS: usize = 30;
+const PAIRING_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The devices, rooms and scenes of the bridge configured by the environment, along with a controller that knows them.
+async fn connect() -> Result<(Registry, HueController), Box<dyn Error>> {
+    let client = HueClient::new()?;
+    let mut registry = Registry::new();
+    let mut controller = HueController::new(client.clone());
+    for event in HueObserver::new(client).discover().await? {
+        registry.apply(&event);
+        controller.handle_event(&event);
+    }
+    Ok((registry, controller))
+}
+
+pub async fn list_devices(json: bool) -> Result<(), Box<dyn Error>> {
+    let (registry, _) = connect().await?;
+    let mut devices = registry.devices();
+    devices.sort_by(|a, b| a.name().cmp(b.name()));
+
+    if json {
+        let devices: Vec<Value> = devices.iter().map(|d| device_json(d, &registry)).collect();
+        println!("{}", serde_json::to_string_pretty(&devices)?);
+        return Ok(());
+    }
+    let rows: Vec<[String; 4]> = devices
+        .iter()
+        .map(|device| {
+            [
+                device.name().clone(),
+                format!("{:?}", device.device_type()).to_lowercase(),
+                room_of(&registry, device).map_or("-".to_string(), |r| r.name().clone()),
+                sorted_properties(device)
+                    .iter()
+                    .map(|(name, property)| format!("{}={}", name, format_property(property)))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ]
+        })
+        .collect();
+    print_table(["NAME", "TYPE", "ROOM", "PROPERTIES"], &rows);
+    Ok(())
+}
+
+/// Lists the firmware of every device and whether an update is on its way.
+pub async fn inventory(json: bool) -> Result<(), Box<dyn Error>> {
+    let (registry, _) = connect().await?;
+    let mut devices = registry.devices();
+    devices.sort_by(|a, b| a.name().cmp(b.name()));
+
+    if json {
+        let devices: Vec<Value> = devices
+            .iter()
+            .map(|device| {
+                json!({
+                    "id": device.id(),
+                    "name": device.name(),
+                    "manufacturer": device.manufacturer(),
+                    "model_id": device.model_id(),
+                    "product_name": device.product_name(),
+                    "firmware": device.firmware(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&devices)?);
+        return Ok(());
+    }
+    let rows: Vec<[String; 5]> = devices
+        .iter()
+        .map(|device| {
+            let firmware = device.firmware();
+            [
+                device.name().clone(),
+                device.product_name().clone(),
+                device.model_id().clone(),
+                firmware.map_or("-".to_string(), |f| f.version().clone()),
+                firmware
+                    .map_or("-", |f| format_update(f.update()))
+                    .to_string(),
+            ]
+        })
+        .collect();
+    print_table(["NAME", "PRODUCT", "MODEL", "FIRMWARE", "UPDATE"], &rows);
+    Ok(())
+}
+
+pub async fn show_device(query: &str, json: bool) -> Result<(), Box<dyn Error>> {
+    let (registry, _) = connect().await?;
+    let device = find_device(&registry, query)?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&device_json(device, &registry))?
+        );
+        return Ok(());
+    }
+    println!("{}", device.name());
+    println!("  id:           {}", device.id());
+    println!(
+        "  type:         {}",
+        format!("{:?}", device.device_type()).to_lowercase()
+    );
+    println!(
+        "  room:         {}",
+        room_of(&registry, device).map_or("-", |r| r.name())
+    );
+    println!(
+        "  availability: {}",
+        format!("{:?}", device.availability()).to_lowercase()
+    );
+    println!("  product:      {}", device.product_name());
+    println!(
+        "  model:        {} ({})",
+        device.model_id(),
+        device.manufacturer()
+    );
+    if let Some(firmware) = device.firmware() {
+        println!(
+            "  firmware:     {} ({})",
+            firmware.version(),
+            format_update(firmware.update())
+        );
+    }
+    println!("  properties:");
+    for (name, property) in sorted_properties(device) {
+        let mode = match property {
+            _ if property.readonly() => "read-only".to_string(),
+            Property::Enum(property) => format!("writable: {}", property.values().join(", ")),
+            _ => "writable".to_string(),
+        };
+        println!(
+            "    {:<20} {:<12} {}",
+            name,
+            format_property(property),
+            mode
+        );
+    }
+    Ok(())
+}
+
+pub async fn set_property(
+    query: &str,
+    property_name: &str,
+    value: &str,
+    transition: Option<Duration>,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (registry, controller) = connect().await?;
+    let device = find_device(&registry, query)?;
+    let property = find_property(device, property_name)?;
+    let value = parse_value(property, value)?;
+    device.validate(property_name, &value)?;
+
+    controller
+        .execute(&Command::ControlDevice {
+            device_id: device.id().clone(),
+            properties: HashMap::from([(property_name.to_string(), value.clone())]),
+            transition,
+        })
+        .await?;
+    if json {
+        println!(
+            "{}",
+            json!({ "device_id": device.id(), "property": property_name, "value": value_json(&value) })
+        );
+    } else {
+        println!(
+            "Set {} of {} to {}",
+            property_name,
+            device.name(),
+            format_value(&value)
+        );
+    }
+    Ok(())
+}
+
+/// Installs the firmware update of a device, which the device must have ready to install.
+pub async fn install_update(query: &str, json: bool) -> Result<(), Box<dyn Error>> {
+    let (registry, controller) = connect().await?;
+    let device = find_device(&registry, query)?;
+    match device.firmware().map(|f| f.update()) {
+        Some(UpdateState::ReadyToInstall) => {}
+        Some(update) => {
+            return Err(format!(
+                "'{}' has no update ready to install ({})",
+                device.name(),
+                format_update(update)
+            )
+            .into())
+        }
+        None => return Err(format!("'{}' does not report its firmware", device.name()).into()),
+    }
+
+    controller
+        .execute(&Command::InstallUpdate {
+            device_id: device.id().clone(),
+        })
+        .await?;
+    if json {
+        println!(
+            "{}",
+            json!({ "device_id": device.id(), "update": UpdateState::Installing })
+        );
+    } else {
+        println!("Installing the update of {}", device.name());
+    }
+    Ok(())
+}
+
+/// Makes a light breathe, so it can be told apart from the others.
+pub async fn identify(query: &str, json: bool) -> Result<(), Box<dyn Error>> {
+    let (registry, controller) = connect().await?;
+    let device = find_device(&registry, query)?;
+    if !matches!(device.device_type(), DeviceType::Light) {
+        return Err(format!("'{}' is not a light", device.name()).into());
+    }
+
+    controller
+        .execute(&Command::Alert {
+            device_id: device.id().clone(),
+        })
+        .await?;
+    if json {
+        println!(
+            "{}",
+            json!({ "device_id": device.id(), "alert": "breathe" })
+        );
+    } else {
+        println!("{} is breathing", device.name());
+    }
+    Ok(())
+}
+
+/// Fades a number property of a device to `value` over `duration`, until it is done or someone changes the device.
+pub async fn fade(
+    query: &str,
+    property_name: &str,
+    value: &str,
+    duration: Duration,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (mut registry, controller) = connect().await?;
+    let device = find_device(&registry, query)?;
+    let (device_id, device_name) = (device.id().clone(), device.name().clone());
+    let target = match parse_value(find_property(device, property_name)?, value)? {
+        PropertyValue::Number(Some(target)) => target,
+        _ => return Err(format!("'{}' is not a number that can fade", property_name).into()),
+    };
+
+    let client = HueClient::new()?;
+    let (sender, mut receiver) = mpsc::channel(100);
+    let observer = tokio::spawn(async move { HueObserver::new(client).observe(sender).await });
+
+    let mut fades = Fades::new();
+    let mut commands = fades.start(
+        &device_id,
+        HashMap::from([(property_name.to_string(), target)]),
+        duration,
+        &registry,
+        Utc::now(),
+    )?;
+    let mut ticks = interval(Duration::from_secs(1));
+    let finished = loop {
+        for command in commands.drain(..) {
+            controller.execute(&command).await?;
+        }
+        if !fades.is_fading(&device_id) {
+            break true;
+        }
+        tokio::select! {
+            event = receiver.recv() => {
+                let Some(event) = event else { break false };
+                registry.apply(&event);
+                fades.handle_event(&event);
+                if !fades.is_fading(&device_id) {
+                    break false;
+                }
+            }
+            _ = ticks.tick() => commands = fades.tick(Utc::now()),
+        }
+    };
+    observer.abort();
+
+    if json {
+        println!(
+            "{}",
+            json!({ "device_id": device_id, "property": property_name, "value": target, "finished": finished })
+        );
+    } else if finished {
+        println!("Faded {} of {} to {}", property_name, device_name, target);
+    } else {
+        println!("Stopped fading {}, it was changed", device_name);
+    }
+    Ok(())
+}
+
+pub async fn recall_scene(
+    query: &str,
+    room: Option<&str>,
+    transition: Option<Duration>,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (registry, controller) = connect().await?;
+    let room = room.map(|room| find_room(&registry, room)).transpose()?;
+    let scenes = registry.search_scenes(query, room.map(|r| r.id()));
+    let scene = single(query, "scene", scenes, |s| {
+        let room = s.room_id().and_then(|id| registry.find_room(id));
+        match room {
+            Some(room) => format!("{} ({})", s.name(), room.name()),
+            None => s.name().clone(),
+        }
+    })?;
+
+    controller
+        .execute(&Command::RecallScene {
+            scene_id: scene.id().clone(),
+            transition,
+        })
+        .await?;
+    if json {
+        println!(
+            "{}",
+            json!({ "scene_id": scene.id(), "name": scene.name() })
+        );
+    } else {
+        println!("Recalled {}", scene.name());
+    }
+    Ok(())
+}
+
+pub async fn entertainment_areas(json: bool) -> Result<(), Box<dyn Error>> {
+    let areas = HueEntertainment::new()?.areas().await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&areas)?);
+        return Ok(());
+    }
+    let rows: Vec<[String; 5]> = areas
+        .iter()
+        .map(|area| {
+            let channels = area
+                .channels()
+                .iter()
+                .map(|c| format!("{} ({:.2}, {:.2}, {:.2})", c.id(), c.x(), c.y(), c.z()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let status = if area.active() { "streaming" } else { "idle" };
+            [
+                area.id().clone(),
+                area.name().clone(),
+                area.kind().clone(),
+                status.to_string(),
+                channels,
+            ]
+        })
+        .collect();
+    print_table(["ID", "NAME", "KIND", "STATUS", "CHANNELS"], &rows);
+    Ok(())
+}
+
+/// Streams a rainbow that moves across the area from left to right, a full turn of the colour wheel every 5 seconds.
+pub async fn sweep(
+    query: &str,
+    over: Duration,
+    rate: u32,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let entertainment = HueEntertainment::new()?;
+    let areas = entertainment.areas().await?;
+    let found: Vec<&EntertainmentArea> = areas
+        .iter()
+        .filter(|area| {
+            area.id() == query || area.name().to_lowercase().contains(&query.to_lowercase())
+        })
+        .collect();
+    let area = single(query, "entertainment area", found, |a| a.name().clone())?;
+
+    let mut stream = entertainment.start(area).await?;
+    if !json {
+        println!("Streaming to {} for {}s", area.name(), over.as_secs());
+    }
+    let mut producer = |elapsed: Duration, channels: &[EntertainmentChannel]| {
+        (elapsed < over).then(|| {
+            Frame::rgb(channels.iter().map(|channel| {
+                let hue = elapsed.as_secs_f64() * 72.0 + (channel.x() + 1.0) * 90.0;
+                (channel.id(), Rgb::from_hsv(Hsv::new(hue, 1.0, 1.0)))
+            }))
+        })
+    };
+    let streamed = stream.run(&mut producer, rate).await;
+    stream.stop().await?;
+    streamed?;
+    if json {
+        println!(
+            "{}",
+            json!({ "area_id": area.id(), "name": area.name(), "seconds": over.as_secs_f64() })
+        );
+    }
+    Ok(())
+}
+
+/// Prints every event of the bridge as it happens, until interrupted.
+pub async fn watch(json: bool) -> Result<(), Box<dyn Error>> {
+    let client = HueClient::new()?;
+    let (sender, mut receiver) = mpsc::channel(100);
+    let observer = tokio::spawn(async move { HueObserver::new(client).observe(sender).await });
+
+    let mut registry = Registry::new();
+    while let Some(event) = receiver.recv().await {
+        registry.apply(&event);
+        if json {
+            println!("{}", event_json(&event));
+        } else {
+            println!(
+                "{} {}",
+                Utc::now().format("%H:%M:%S%.3f"),
+                describe_event(&event, &registry)
+            );
+        }
+    }
+    observer.await??;
+    Ok(())
+}
+
+/// Creates an application key on the bridge at `bridge`, or on the first bridge found on the network.
+pub async fn pair(bridge: Option<String>, json: bool) -> Result<(), Box<dyn Error>> {
+    let endpoint = match bridge {
+        Some(bridge) => bridge,
+        None => hue::discover_bridges()
+            .await?
+            .first()
+            .map(|b| b.endpoint())
+            .ok_or("no bridge found on the network, pass its address with --bridge")?,
+    };
+    let device_type = format!(
+        "chambrier#{}",
+        std::env::var("HOSTNAME").unwrap_or_else(|_| "cli".to_string())
+    );
+
+    eprintln!("Press the link button on the bridge at {}...", endpoint);
+    for _ in 0..PAIRING_ATTEMPTS {
+        match hue::pair(&endpoint, &device_type).await {
+            Ok(key) => {
+                if json {
+                    println!(
+                        "{}",
+                        json!({
+                            "endpoint": endpoint,
+                            "application_key": key.application_key(),
+                            "client_key": key.client_key(),
+                        })
+                    );
+                } else {
+                    println!("HUE_ENDPOINT={}", endpoint);
+                    println!("HUE_APP_KEY={}", key.application_key());
+                    if let Some(client_key) = key.client_key() {
+                        println!("HUE_CLIENT_KEY={}", client_key);
+                    }
+                }
+                return Ok(());
+            }
+            Err(HueClientError::LinkButtonNotPressed) => sleep(PAIRING_INTERVAL).await,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err("the link button was not pressed in time".into())
+}
+
+pub async fn discover(json: bool) -> Result<(), Box<dyn Error>> {
+    let bridges = hue::discover_bridges().await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&bridges)?);
+        return Ok(());
+    }
+    let rows: Vec<[String; 2]> = bridges
+        .iter()
+        .map(|b| [b.id().clone(), b.endpoint()])
+        .collect();
+    print_table(["ID", "ENDPOINT"], &rows);
+    Ok(())
+}
+
+/// Snapshots are JSON, so `--json` makes no difference.
+pub async fn export() -> Result<(), Box<dyn Error>> {
+    let (registry, _) = connect().await?;
+    println!("{}", serde_json::to_string_pretty(&registry.snapshot())?);
+    Ok(())
+}
+
+pub fn schema() -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string_pretty(&Snapshot::schema())?);
+    Ok(())
+}
+
+fn find_device<'a>(registry: &'a Registry, query: &str) -> Result<&'a Device, Box<dyn Error>> {
+    single(query, "device", registry.search_devices(query), |d| {
+        d.name().clone()
+    })
+}
+
+fn find_room<'a>(registry: &'a Registry, query: &str) -> Result<&'a Room, Box<dyn Error>> {
+    single(query, "room", registry.search_rooms(query), |r| {
+        r.name().clone()
+    })
+}
+
+/// The only match of a search, an ambiguous query lists the candidates so it can be refined.
+fn single<'a, T>(
+    query: &str,
+    kind: &str,
+    found: Vec<&'a T>,
+    describe: impl Fn(&T) -> String,
+) -> Result<&'a T, Box<dyn Error>> {
+    match found.as_slice() {
+        [] => Err(format!("no {} matches '{}'", kind, query).into()),
+        [item] => Ok(item),
+        items => Err(format!(
+            "'{}' matches more than one {}: {}",
+            query,
+            kind,
+            items
+                .iter()
+                .map(|item| describe(item))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
+pub fn room_of<'a>(registry: &'a Registry, device: &Device) -> Option<&'a Room> {
+    registry
+        .rooms()
+        .into_iter()
+        .find(|room| room.device_ids().contains(device.id()))
+}
+
+fn find_property<'a>(device: &'a Device, name: &str) -> Result<&'a Property, Box<dyn Error>> {
+    device.properties().get(name).ok_or_else(|| {
+        format!(
+            "'{}' has no property '{}', it has: {}",
+            device.name(),
+            name,
+            sorted_properties(device)
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()
+    })
+}
+
+fn sorted_properties(device: &Device) -> Vec<(&String, &Property)> {
+    let mut properties: Vec<_> = device.properties().iter().collect();
+    properties.sort_by_key(|(name, _)| *name);
+    properties
+}
+
+/// Parses a duration like `500ms`, `2s`, `30m` or `1h`, a number without a unit is in seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim().to_lowercase();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a duration", value))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" | "min" => number * 60.0,
+        "h" => number * 3600.0,
+        unit => return Err(format!("unknown unit '{}', use ms, s, m or h", unit)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+fn parse_value(property: &Property, value: &str) -> Result<PropertyValue, Box<dyn Error>> {
+    match property {
+        Property::Boolean(_) => match value.to_lowercase().as_str() {
+            "on" | "true" | "yes" | "1" => Ok(PropertyValue::Boolean(true)),
+            "off" | "false" | "no" | "0" => Ok(PropertyValue::Boolean(false)),
+            _ => Err(format!("'{}' is not on or off", value).into()),
+        },
+        Property::Number(_) => {
+            let split = value
+                .char_indices()
+                .find(|(i, c)| !(c.is_ascii_digit() || *c == '.' || (*i == 0 && *c == '-')))
+                .map_or(value.len(), |(i, _)| i);
+            let (number, unit) = value.split_at(split);
+            let number: f64 = number
+                .parse()
+                .map_err(|_| format!("'{}' is not a number", value))?;
+            let number = match unit {
+                "" => number,
+                unit => property.convert(number, &parse_unit(unit)?)?,
+            };
+            Ok(PropertyValue::Number(Some(number)))
+        }
+        Property::Color(_) => parse_color(value).map(PropertyValue::Color),
+        Property::Enum(_) | Property::Text(_) => Ok(PropertyValue::Text(value.to_string())),
+    }
+}
+
+/// Parses a colour as hex (`#ff8800`), as CIE xy (`0.54,0.41`) or as a colour temperature (`2700K`).
+fn parse_color(value: &str) -> Result<Xy, Box<dyn Error>> {
+    let value = value.trim().to_lowercase();
+    if let Some((x, y)) = value.split_once(',') {
+        let coordinate = |c: &str| {
+            c.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not a coordinate", c))
+        };
+        return Ok(Xy::new(coordinate(x)?, coordinate(y)?));
+    }
+    if let Some(kelvin) = value.strip_suffix('k') {
+        let kelvin: f64 = kelvin
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a colour temperature", value))?;
+        return Ok(Xy::from_kelvin(kelvin));
+    }
+    Ok(Xy::from_rgb(Rgb::from_hex(&value)?))
+}
+
+fn parse_unit(unit: &str) -> Result<Unit, Box<dyn Error>> {
+    match unit.trim().to_lowercase().as_str() {
+        "%" => Ok(Unit::Percentage),
+        "light_level" => Ok(Unit::LightLevel),
+        "lx" | "lux" => Ok(Unit::Lux),
+        "°c" | "c" => Ok(Unit::Celcius),
+        "°f" | "f" => Ok(Unit::Fahrenheit),
+        "k" => Ok(Unit::Kelvin),
+        "mired" | "mirek" => Ok(Unit::Mirek),
+        _ => Err(format!("unknown unit '{}'", unit).into()),
+    }
+}
+
+/// The reported value of a property, followed by the value it is being set to if a write is pending.
+pub fn format_property(property: &Property) -> String {
+    let format = |value: &PropertyValue| match (property, value) {
+        (Property::Number(property), PropertyValue::Number(Some(value))) => match property.unit() {
+            Unit::Percentage => format!("{}%", value),
+            Unit::LightLevel => format!("{}", value),
+            Unit::Lux => format!("{}lx", value),
+            Unit::Celcius => format!("{}°C", value),
+            Unit::Fahrenheit => format!("{}°F", value),
+            Unit::Kelvin => format!("{}K", value),
+            Unit::Mirek => format!("{}mired", value),
+        },
+        _ => format_value(value),
+    };
+    match property.pending() {
+        Some(pending) => format!("{}→{}", format(&property.value()), format(pending.value())),
+        None => format(&property.value()),
+    }
+}
+
+fn format_update(update: UpdateState) -> &'static str {
+    match update {
+        UpdateState::NoUpdate => "up to date",
+        UpdateState::UpdatePending => "update pending",
+        UpdateState::ReadyToInstall => "ready to install",
+        UpdateState::Installing => "installing",
+    }
+}
+
+fn format_value(value: &PropertyValue) -> String {
+    match value {
+        PropertyValue::Boolean(true) => "on".to_string(),
+        PropertyValue::Boolean(false) => "off".to_string(),
+        PropertyValue::Number(Some(value)) => value.to_string(),
+        PropertyValue::Number(None) => "-".to_string(),
+        PropertyValue::Color(xy) => xy.to_rgb().to_hex(),
+        PropertyValue::Text(text) => text.clone(),
+    }
+}
+
+fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut widths = header.map(|h| h.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: [&str; N]| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", line(header));
+    for row in rows {
+        println!("{}", line(row.each_ref().map(|cell| cell.as_str())));
+    }
+}
+
+pub fn describe_event(event: &Event, registry: &Registry) -> String {
+    let name = |id: &String| {
+        registry
+            .device(id)
+            .map_or(id.clone(), |device| device.name().clone())
+    };
+    match event {
+        Event::DiscoveredDevices(devices) => format!("discovered {} devices", devices.len()),
+        Event::DevicesRemoved(device_ids) => format!("removed devices {}", device_ids.join(", ")),
+        Event::DiscoveredRooms(rooms) => format!("discovered {} rooms", rooms.len()),
+        Event::DiscoveredScenes(scenes) => format!("discovered {} scenes", scenes.len()),
+        Event::PropertiesChanged {
+            device_id,
+            properties,
+        } => {
+            let mut properties: Vec<_> = properties
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, format_value(value)))
+                .collect();
+            properties.sort();
+            format!("{}: {}", name(device_id), properties.join(" "))
+        }
+        Event::ButtonPressed {
+            device_id,
+            control_id,
+            event,
+            ..
+        } => format!("{}: button {} {:?}", name(device_id), control_id, event),
+        Event::PresenceChanged(presence) => format!("presence {:?}", presence),
+        Event::AvailabilityChanged {
+            device_id,
+            availability,
+        } => format!(
+            "{}: {}",
+            name(device_id),
+            format!("{:?}", availability).to_lowercase()
+        ),
+        Event::PropertyWriteFailed {
+            device_id,
+            property,
+            error,
+        } => format!(
+            "{}: setting {} failed, {}",
+            name(device_id),
+            property,
+            error
+        ),
+        Event::UpdateStateChanged { device_id, update } => {
+            format!("{}: firmware {}", name(device_id), format_update(*update))
+        }
+    }
+}
+
+/// A device as in snapshots, along with the name of its room.
+fn device_json(device: &Device, registry: &Registry) -> Value {
+    let mut json = serde_json::to_value(device).unwrap_or_default();
+    json["room"] = json!(room_of(registry, device).map(|r| r.name()));
+    json
+}
+
+fn value_json(value: &PropertyValue) -> Value {
+    match value {
+        PropertyValue::Boolean(value) => json!(value),
+        PropertyValue::Number(value) => json!(value),
+        PropertyValue::Color(xy) => json!(xy),
+        PropertyValue::Text(text) => json!(text),
+    }
+}
+
+fn event_json(event: &Event) -> Value {
+    let time = Utc::now().to_rfc3339();
+    match event {
+        Event::DiscoveredDevices(devices) => {
+            json!({ "time": time, "type": "discovered_devices", "count": devices.len() })
+        }
+        Event::DevicesRemoved(device_ids) => {
+            json!({ "time": time, "type": "devices_removed", "device_ids": device_ids })
+        }
+        Event::DiscoveredRooms(rooms) => {
+            json!({ "time": time, "type": "discovered_rooms", "count": rooms.len() })
+        }
+        Event::DiscoveredScenes(scenes) => {
+            json!({ "time": time, "type": "discovered_scenes", "count": scenes.len() })
+        }
+        Event::PropertiesChanged {
+            device_id,
+            properties,
+        } => {
+            let properties: Map<String, Value> = properties
+                .iter()
+                .map(|(name, value)| (name.clone(), value_json(value)))
+                .collect();
+            json!({
+                "time": time,
+                "type": "properties_changed",
+                "device_id": device_id,
+                "properties": properties,
+            })
+        }
+        Event::ButtonPressed {
+            device_id,
+            control_id,
+            event,
+            ..
+        } => json!({
+            "time": time,
+            "type": "button_pressed",
+            "device_id": device_id,
+            "control_id": control_id,
+            "event": format!("{:?}", event),
+        }),
+        Event::PresenceChanged(presence) => json!({
+            "time": time,
+            "type": "presence_changed",
+            "presence": format!("{:?}", presence).to_lowercase(),
+        }),
+        Event::AvailabilityChanged {
+            device_id,
+            availability,
+        } => json!({
+            "time": time,
+            "type": "availability_changed",
+            "device_id": device_id,
+            "availability": availability,
+        }),
+        Event::PropertyWriteFailed {
+            device_id,
+            property,
+            error,
+        } => json!({
+            "time": time,
+            "type": "property_write_failed",
+            "device_id": device_id,
+            "property": property,
+            "error": error,
+        }),
+        Event::UpdateStateChanged { device_id, update } => json!({
+            "time": time,
+            "type": "update_state_changed",
+            "device_id": device_id,
+            "update": update,
+        }),
+    }
+}
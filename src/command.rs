@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::model::{Presence, PropertyValue};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    ControlDevice {
+        device_id: String,
+        properties: HashMap<String, PropertyValue>,
+        transition: Option<Duration>,
+    },
+    RecallScene {
+        scene_id: String,
+        transition: Option<Duration>,
+    },
+    /// Makes a light breathe for a few seconds, to identify it or to draw attention.
+    Alert { device_id: String },
+    /// Not sent to the bridge, the application turns it into an [`Event::PresenceChanged`](crate::event::Event).
+    SetPresence(Presence),
+    /// Installs a firmware update that is ready to install.
+    InstallUpdate { device_id: String },
+    /// Slowly changes number properties of a device to `targets` over `duration`. Not sent to the bridge, the
+    /// application sends the fade in steps with [`Fades`](crate::automation::Fades).
+    StartFade {
+        device_id: String,
+        targets: HashMap<String, f64>,
+        duration: Duration,
+    },
+}
+
+impl Command {
+    /// The name of the kind of command, for logs and metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Command::ControlDevice { .. } => "control_device",
+            Command::RecallScene { .. } => "recall_scene",
+            Command::Alert { .. } => "alert",
+            Command::SetPresence(_) => "set_presence",
+            Command::InstallUpdate { .. } => "install_update",
+            Command::StartFade { .. } => "start_fade",
+        }
+    }
+}
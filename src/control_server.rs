@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use thiserror::Error;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::command::Command;
+use crate::model::{Presence, PropertyError, PropertyValue};
+
+/// Accepts requests that change the house, on an address of its own so that it is not exposed along with the metrics.
+/// It has no authentication, so it must only listen where every client may control the house.
+///
+/// Whoever knows whether anyone is home, e.g. a phone leaving the house, reports it with `PUT /presence` and a JSON
+/// body such as `"away"`. It is passed on as a command, like a button bound to a presence would. Devices, virtual
+/// switches included, are controlled with `PUT /devices/<id>` and the properties to set, e.g. `{"on": true}`. Writes
+/// the device does not accept are answered with `400 Bad Request`.
+pub struct ControlServer {
+    address: SocketAddr,
+    server: JoinHandle<()>,
+}
+
+impl ControlServer {
+    pub async fn start(
+        address: SocketAddr,
+        commands: Sender<ControlRequest>,
+    ) -> Result<ControlServer, ControlServerError> {
+        let server = Server::try_bind(&address)?.serve(make_service_fn(move |_| {
+            let commands = commands.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let commands = commands.clone();
+                    async move { Ok::<_, Infallible>(handle(request, &commands).await) }
+                }))
+            }
+        }));
+        let address = server.local_addr();
+        let server = tokio::spawn(async move {
+            let _ = server.await;
+        });
+        Ok(ControlServer { address, server })
+    }
+
+    /// The address the server listens on, with the actual port if port 0 was asked for.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// A command from a client, who waits to learn whether it was accepted.
+#[derive(Debug)]
+pub struct ControlRequest {
+    command: Command,
+    reply: oneshot::Sender<Result<(), PropertyError>>,
+}
+
+impl ControlRequest {
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    /// Tells the client whether the command was accepted, nothing happens if it is no longer waiting.
+    pub fn reply(self, result: Result<(), PropertyError>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+async fn handle(request: Request<Body>, commands: &Sender<ControlRequest>) -> Response<Body> {
+    let response = Response::builder();
+    match (request.method(), request.uri().path()) {
+        (&Method::PUT, "/presence") => {
+            let presence = match hyper::body::to_bytes(request.into_body()).await {
+                Ok(body) => serde_json::from_slice::<Presence>(&body).ok(),
+                Err(_) => None,
+            };
+            match presence {
+                Some(presence) => send(response, commands, Command::SetPresence(presence)).await,
+                None => response
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(r#"Expected "home", "away" or "sleeping""#.into()),
+            }
+        }
+        (&Method::PUT, path) if path.starts_with("/devices/") => {
+            let device_id = path.trim_start_matches("/devices/").to_string();
+            let properties = match hyper::body::to_bytes(request.into_body()).await {
+                Ok(body) => serde_json::from_slice::<HashMap<String, PropertyValue>>(&body).ok(),
+                Err(_) => None,
+            };
+            match properties {
+                Some(properties) if !device_id.is_empty() && !properties.is_empty() => {
+                    let command = Command::ControlDevice {
+                        device_id,
+                        properties,
+                        transition: None,
+                    };
+                    send(response, commands, command).await
+                }
+                _ => response
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(r#"Expected the properties to set, e.g. {"on": true}"#.into()),
+            }
+        }
+        _ => response
+            .status(StatusCode::NOT_FOUND)
+            .body("Not Found".into()),
+    }
+    .expect("a valid response")
+}
+
+/// Passes a command on to the automations and waits for them to accept it. They have stopped if nobody receives it
+/// or replies anymore.
+async fn send(
+    response: hyper::http::response::Builder,
+    commands: &Sender<ControlRequest>,
+    command: Command,
+) -> Result<Response<Body>, hyper::http::Error> {
+    let (reply, result) = oneshot::channel();
+    let result = match commands.send(ControlRequest { command, reply }).await {
+        Ok(()) => result.await.ok(),
+        Err(_) => None,
+    };
+    match result {
+        Some(Ok(())) => response.status(StatusCode::NO_CONTENT).body(Body::empty()),
+        Some(Err(e)) => response
+            .status(StatusCode::BAD_REQUEST)
+            .body(e.to_string().into()),
+        None => response
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body("Not running automations".into()),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ControlServerError {
+    #[error("cannot listen for requests")]
+    BindError(#[from] hyper::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::{self, Receiver};
+
+    use super::*;
+
+    async fn server() -> (ControlServer, Receiver<ControlRequest>) {
+        let (sender, receiver) = mpsc::channel(10);
+        let server = ControlServer::start("127.0.0.1:0".parse().unwrap(), sender)
+            .await
+            .expect("the server starts");
+        (server, receiver)
+    }
+
+    /// Puts `body` to `path` while the automations answer the request with `result`, returning the status and the
+    /// command they received.
+    async fn put(
+        path: &str,
+        body: &'static str,
+        result: Result<(), PropertyError>,
+    ) -> Result<(reqwest::StatusCode, Option<Command>), Box<dyn std::error::Error>> {
+        let (server, mut requests) = server().await;
+        let response = reqwest::Client::new()
+            .put(format!("http://{}{}", server.address(), path))
+            .body(body)
+            .send();
+        let automations = async move {
+            let request = requests.recv().await?;
+            let command = request.command().clone();
+            request.reply(result);
+            Some(command)
+        };
+
+        let (response, command) = tokio::join!(response, automations);
+        Ok((response?.status(), command))
+    }
+
+    #[tokio::test]
+    async fn passes_on_the_presence() -> Result<(), Box<dyn std::error::Error>> {
+        let (status, command) = put("/presence", r#""away""#, Ok(())).await?;
+
+        assert_eq!(reqwest::StatusCode::NO_CONTENT, status);
+        assert_eq!(Some(Command::SetPresence(Presence::Away)), command);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_an_unknown_presence() -> Result<(), Box<dyn std::error::Error>> {
+        let (server, mut requests) = server().await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .put(format!("http://{}/presence", server.address()))
+            .body(r#""abroad""#)
+            .send()
+            .await?;
+
+        assert_eq!(reqwest::StatusCode::BAD_REQUEST, response.status());
+        assert!(requests.try_recv().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn controls_a_device() -> Result<(), Box<dyn std::error::Error>> {
+        let (status, command) = put("/devices/guest-mode", r#"{"on": true}"#, Ok(())).await?;
+
+        assert_eq!(reqwest::StatusCode::NO_CONTENT, status);
+        assert_eq!(
+            Some(Command::ControlDevice {
+                device_id: "guest-mode".to_string(),
+                properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(true))]),
+                transition: None,
+            }),
+            command
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_a_write_the_device_does_not_accept() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let rejection = Err(PropertyError::UnknownProperty("brightness".to_string()));
+
+        let (status, _) = put("/devices/guest-mode", r#"{"brightness": 50}"#, rejection).await?;
+
+        assert_eq!(reqwest::StatusCode::BAD_REQUEST, status);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_a_device_without_properties() -> Result<(), Box<dyn std::error::Error>> {
+        let (server, mut requests) = server().await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .put(format!("http://{}/devices/guest-mode", server.address()))
+            .body("{}")
+            .send()
+            .await?;
+
+        assert_eq!(reqwest::StatusCode::BAD_REQUEST, response.status());
+        assert!(requests.try_recv().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,683 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use crossterm::event::{Event as TerminalEvent, EventStream, KeyCode, KeyEventKind};
+use futures_util::StreamExt;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::sync::mpsc;
+use tokio::time::interval;
+
+use chambrier::command::Command;
+use chambrier::event::Event;
+use chambrier::hue::{HueClient, HueController, HueObserver};
+use chambrier::model::{Availability, Device, DeviceType, Property, PropertyValue, Scene};
+use chambrier::registry::Registry;
+
+use crate::cli::{describe_event, format_property};
+use crate::logging;
+
+const LOG_LENGTH: usize = 500;
+const BRIGHTNESS_STEP: f64 = 10.0;
+const MIREK_STEP: f64 = 25.0;
+/// How long a light gets to report a value it was set to before the write is shown as failed.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shows the house live and controls it from the terminal, until `q` is pressed.
+pub async fn run() -> Result<(), Box<dyn Error>> {
+    let (log_sender, mut logs) = mpsc::unbounded_channel();
+    logging::init("info", move || LogWriter(log_sender.clone()), false)?;
+    let client = HueClient::new()?;
+    let mut controller = HueController::new(client.clone());
+    let (sender, mut receiver) = mpsc::channel(100);
+    let observer = tokio::spawn(async move { HueObserver::new(client).observe(sender).await });
+
+    let mut terminal = ratatui::init();
+    let result = async {
+        let mut dashboard = Dashboard::default();
+        let mut keys = EventStream::new();
+        let mut ticks = interval(Duration::from_secs(1));
+        let mut observing = true;
+        loop {
+            draw(&mut terminal, &dashboard)?;
+            tokio::select! {
+                event = receiver.recv(), if observing => match event {
+                    Some(event) => {
+                        controller.handle_event(&event);
+                        dashboard.handle_event(&event, Utc::now());
+                    }
+                    None => {
+                        observing = false;
+                        dashboard.disconnected();
+                    }
+                },
+                key = keys.next() => {
+                    let Some(TerminalEvent::Key(key)) = key.transpose()? else { continue };
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match dashboard.handle_key(key.code) {
+                        Action::None => {}
+                        Action::Quit => return Ok(()),
+                        Action::Execute(command) => {
+                            dashboard.request(&command, Utc::now());
+                            if let Err(e) = controller.execute(&command).await {
+                                dashboard.command_failed(&command, &e.to_string(), Utc::now());
+                            }
+                        }
+                    }
+                }
+                Some(line) = logs.recv() => dashboard.log(line),
+                _ = ticks.tick() => dashboard.expire_pending(Utc::now()),
+            }
+        }
+    }
+    .await;
+    ratatui::restore();
+    observer.abort();
+    result
+}
+
+/// Sends every log line to the dashboard, as writing them to the terminal would garble it.
+struct LogWriter(mpsc::UnboundedSender<String>);
+
+impl Write for LogWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let line = String::from_utf8_lossy(buffer).trim_end().to_string();
+        let _ = self.0.send(line);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn draw(terminal: &mut DefaultTerminal, dashboard: &Dashboard) -> Result<(), Box<dyn Error>> {
+    terminal.draw(|frame| dashboard.render(frame, Utc::now()))?;
+    Ok(())
+}
+
+enum Action {
+    None,
+    Quit,
+    Execute(Command),
+}
+
+#[derive(PartialEq, Debug)]
+enum Row {
+    Room(String),
+    Device(String),
+}
+
+struct ScenePicker {
+    scene_ids: Vec<String>,
+    selected: usize,
+}
+
+#[derive(Default)]
+struct Health {
+    connected: bool,
+    last_event: Option<DateTime<Utc>>,
+    failed_commands: usize,
+}
+
+#[derive(Default)]
+struct Dashboard {
+    registry: Registry,
+    selected: Option<String>,
+    scene_picker: Option<ScenePicker>,
+    log: VecDeque<String>,
+    log_scroll: usize,
+    health: Health,
+}
+
+impl Dashboard {
+    fn handle_event(&mut self, event: &Event, now: DateTime<Utc>) {
+        self.registry.apply(event);
+        self.health.connected = true;
+        self.health.last_event = Some(now);
+        self.log(format!(
+            "{} {}",
+            now.format("%H:%M:%S"),
+            describe_event(event, &self.registry)
+        ));
+        if self.selected.is_none() {
+            self.selected = self.device_ids().first().cloned();
+        }
+    }
+
+    fn disconnected(&mut self) {
+        self.health.connected = false;
+        self.log("Stopped receiving events from Philips Hue".to_string());
+    }
+
+    /// Shows the values a command sets right away, until the light reports them or the write fails.
+    fn request(&mut self, command: &Command, now: DateTime<Utc>) {
+        if let Command::ControlDevice {
+            device_id,
+            properties,
+            ..
+        } = command
+        {
+            if let Err(e) = self.registry.request(device_id, properties, now) {
+                self.log(format!("{} {}", now.format("%H:%M:%S"), e));
+            }
+        }
+    }
+
+    fn expire_pending(&mut self, now: DateTime<Utc>) {
+        for event in self.registry.expire_pending(now, WRITE_TIMEOUT) {
+            self.health.failed_commands += 1;
+            self.log(format!(
+                "{} {}",
+                now.format("%H:%M:%S"),
+                describe_event(&event, &self.registry)
+            ));
+        }
+    }
+
+    fn command_failed(&mut self, command: &Command, error: &str, now: DateTime<Utc>) {
+        if let Command::ControlDevice {
+            device_id,
+            properties,
+            ..
+        } = command
+        {
+            for property in properties.keys() {
+                self.registry.apply(&Event::PropertyWriteFailed {
+                    device_id: device_id.clone(),
+                    property: property.clone(),
+                    error: error.to_string(),
+                });
+            }
+        }
+        self.health.failed_commands += 1;
+        self.log(format!(
+            "{} executing {:?} failed: {}",
+            now.format("%H:%M:%S"),
+            command,
+            error
+        ));
+    }
+
+    fn log(&mut self, line: String) {
+        self.log.push_front(line);
+        self.log.truncate(LOG_LENGTH);
+    }
+
+    fn handle_key(&mut self, key: KeyCode) -> Action {
+        if let Some(picker) = self.scene_picker.as_mut() {
+            match key {
+                KeyCode::Up => picker.selected = picker.selected.saturating_sub(1),
+                KeyCode::Down => {
+                    picker.selected = (picker.selected + 1).min(picker.scene_ids.len() - 1)
+                }
+                KeyCode::Enter => {
+                    let scene_id = picker.scene_ids[picker.selected].clone();
+                    self.scene_picker = None;
+                    return Action::Execute(Command::RecallScene {
+                        scene_id,
+                        transition: None,
+                    });
+                }
+                KeyCode::Esc | KeyCode::Char('s') => self.scene_picker = None,
+                _ => {}
+            }
+            return Action::None;
+        }
+
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.log_scroll = self.log_scroll.saturating_sub(10),
+            KeyCode::PageDown => {
+                self.log_scroll = (self.log_scroll + 10).min(self.log.len().saturating_sub(1))
+            }
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                if let Some(PropertyValue::Boolean(on)) = self.desired_value("on") {
+                    return self.control(HashMap::from([(
+                        "on".to_string(),
+                        PropertyValue::Boolean(!on),
+                    )]));
+                }
+            }
+            KeyCode::Left => return self.step("brightness", -BRIGHTNESS_STEP),
+            KeyCode::Right => return self.step("brightness", BRIGHTNESS_STEP),
+            // Mirek is the inverse of kelvin, more mirek is warmer
+            KeyCode::Char('[') => return self.step("color_temperature", MIREK_STEP),
+            KeyCode::Char(']') => return self.step("color_temperature", -MIREK_STEP),
+            KeyCode::Char('i') => {
+                if let Some(device) = self
+                    .selected_device()
+                    .filter(|device| matches!(device.device_type(), DeviceType::Light))
+                {
+                    return Action::Execute(Command::Alert {
+                        device_id: device.id().clone(),
+                    });
+                }
+            }
+            KeyCode::Char('s') => {
+                let scene_ids: Vec<String> = self.scenes().iter().map(|s| s.id().clone()).collect();
+                if !scene_ids.is_empty() {
+                    self.scene_picker = Some(ScenePicker {
+                        scene_ids,
+                        selected: 0,
+                    });
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        let ids = self.device_ids();
+        let position = self
+            .selected
+            .as_ref()
+            .and_then(|selected| ids.iter().position(|id| id == selected));
+        let next = match position {
+            Some(position) => position.saturating_add_signed(offset).min(ids.len() - 1),
+            None => 0,
+        };
+        self.selected = ids.get(next).cloned();
+    }
+
+    /// Changes a number property of the selected device by `step` within its bounds, turning the light on if needed.
+    fn step(&self, name: &str, step: f64) -> Action {
+        let Some(Property::Number(property)) = self.selected_property(name) else {
+            return Action::None;
+        };
+        let minimum = property.minimum().unwrap_or(0.0);
+        let maximum = property.maximum().unwrap_or(f64::MAX);
+        let current = match self.desired_value(name) {
+            Some(PropertyValue::Number(value)) => value,
+            _ => property.value(),
+        };
+        let value = property.round((current.unwrap_or(minimum) + step).clamp(minimum, maximum));
+
+        let mut properties =
+            HashMap::from([(name.to_string(), PropertyValue::Number(Some(value)))]);
+        if let Some(PropertyValue::Boolean(false)) = self.desired_value("on") {
+            properties.insert("on".to_string(), PropertyValue::Boolean(true));
+        }
+        self.control(properties)
+    }
+
+    fn control(&self, properties: HashMap<String, PropertyValue>) -> Action {
+        match &self.selected {
+            Some(device_id) => Action::Execute(Command::ControlDevice {
+                device_id: device_id.clone(),
+                properties,
+                transition: None,
+            }),
+            None => Action::None,
+        }
+    }
+
+    fn selected_device(&self) -> Option<&Device> {
+        self.selected
+            .as_ref()
+            .and_then(|id| self.registry.device(id))
+    }
+
+    fn selected_property(&self, name: &str) -> Option<&Property> {
+        self.selected_device()
+            .and_then(|device| device.properties().get(name))
+    }
+
+    /// The value a property of the selected device is about to have, so that keys pressed in quick succession add up.
+    fn desired_value(&self, name: &str) -> Option<PropertyValue> {
+        self.selected_property(name).map(Property::desired_value)
+    }
+
+    /// The scenes of the selected device's room, or every scene if it is in none.
+    fn scenes(&self) -> Vec<&Scene> {
+        let room_id = self.selected_device().and_then(|device| {
+            self.registry
+                .rooms()
+                .into_iter()
+                .find(|room| room.device_ids().contains(device.id()))
+                .map(|room| room.id().clone())
+        });
+        let mut scenes: Vec<&Scene> = self
+            .registry
+            .scenes()
+            .into_iter()
+            .filter(|scene| room_id.is_none() || scene.room_id() == room_id.as_ref())
+            .collect();
+        scenes.sort_by(|a, b| a.name().cmp(b.name()));
+        scenes
+    }
+
+    /// Devices grouped by room, both sorted by name. Devices that are in no room come last.
+    fn rows(&self) -> Vec<Row> {
+        let mut rooms = self.registry.rooms();
+        rooms.sort_by(|a, b| a.name().cmp(b.name()));
+        let mut devices = self.registry.devices();
+        devices.sort_by(|a, b| a.name().cmp(b.name()));
+
+        let mut rows = vec![];
+        for room in &rooms {
+            rows.push(Row::Room(room.name().clone()));
+            for device in devices
+                .iter()
+                .filter(|d| room.device_ids().contains(d.id()))
+            {
+                rows.push(Row::Device(device.id().clone()));
+            }
+        }
+        let other: Vec<&&Device> = devices
+            .iter()
+            .filter(|d| !rooms.iter().any(|r| r.device_ids().contains(d.id())))
+            .collect();
+        if !other.is_empty() {
+            rows.push(Row::Room("Other".to_string()));
+            rows.extend(other.iter().map(|d| Row::Device(d.id().clone())));
+        }
+        rows
+    }
+
+    fn device_ids(&self) -> Vec<String> {
+        self.rows()
+            .into_iter()
+            .filter_map(|row| match row {
+                Row::Device(id) => Some(id),
+                Row::Room(_) => None,
+            })
+            .collect()
+    }
+
+    fn render(&self, frame: &mut Frame, now: DateTime<Utc>) {
+        let [main, log, help] = Layout::vertical([
+            Constraint::Percentage(65),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [devices, side] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Fill(1)]).areas(main);
+        let [health, details] =
+            Layout::vertical([Constraint::Length(5), Constraint::Fill(1)]).areas(side);
+
+        let rows = self.rows();
+        let items: Vec<ListItem> = rows
+            .iter()
+            .map(|row| match row {
+                Row::Room(name) => ListItem::new(Line::from(name.clone().bold())),
+                Row::Device(id) => ListItem::new(self.device_line(id)),
+            })
+            .collect();
+        let mut state =
+            ListState::default().with_selected(rows.iter().position(
+                |row| matches!(row, Row::Device(id) if Some(id) == self.selected.as_ref()),
+            ));
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::bordered().title(" Devices "))
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+            devices,
+            &mut state,
+        );
+
+        let last_event = self.health.last_event.map_or("never".to_string(), |time| {
+            format!("{}s ago", (now - time).num_seconds())
+        });
+        let unavailable = self
+            .registry
+            .devices()
+            .iter()
+            .filter(|device| device.availability() != Availability::Available)
+            .count();
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(format!(
+                    "Philips Hue: {}",
+                    if self.health.connected {
+                        "connected"
+                    } else {
+                        "disconnected"
+                    }
+                )),
+                Line::from(format!("Last event: {}", last_event)),
+                Line::from(format!("Failed commands: {}", self.health.failed_commands)),
+                Line::from(format!("Unavailable devices: {}", unavailable)),
+            ])
+            .block(Block::bordered().title(" Health ")),
+            health,
+        );
+
+        if let Some(picker) = &self.scene_picker {
+            let items: Vec<ListItem> = picker
+                .scene_ids
+                .iter()
+                .filter_map(|id| self.registry.scenes().into_iter().find(|s| s.id() == id))
+                .map(|scene| ListItem::new(scene.name().clone()))
+                .collect();
+            let mut state = ListState::default().with_selected(Some(picker.selected));
+            frame.render_stateful_widget(
+                List::new(items)
+                    .block(Block::bordered().title(" Recall scene "))
+                    .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+                details,
+                &mut state,
+            );
+        } else {
+            let lines = self.selected_device().map_or(vec![], |device| {
+                let mut lines = vec![
+                    Line::from(device.name().clone().bold()),
+                    Line::from(device.product_name().clone()),
+                    Line::from(""),
+                ];
+                let mut properties: Vec<_> = device.properties().iter().collect();
+                properties.sort_by_key(|(name, _)| *name);
+                lines.extend(
+                    properties
+                        .into_iter()
+                        .map(|(name, p)| Line::from(format!("{}: {}", name, format_property(p)))),
+                );
+                lines
+            });
+            frame.render_widget(
+                Paragraph::new(lines).block(Block::bordered().title(" Device ")),
+                details,
+            );
+        }
+
+        let lines: Vec<Line> = self
+            .log
+            .iter()
+            .skip(self.log_scroll)
+            .map(|line| Line::from(line.clone()))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Events ")),
+            log,
+        );
+
+        frame.render_widget(
+            Paragraph::new(
+                "↑↓ select  space toggle  ←→ brightness  [ ] colour temperature  i identify  s scenes  PgUp/PgDn log  q quit",
+            )
+            .dim(),
+            help,
+        );
+    }
+
+    fn device_line(&self, id: &str) -> Line<'static> {
+        let Some(device) = self.registry.device(id) else {
+            return Line::from(id.to_string());
+        };
+        let mut summary = vec![];
+        for name in [
+            "on",
+            "brightness",
+            "color_temperature",
+            "temperature",
+            "motion",
+        ] {
+            if let Some(property) = device.properties().get(name) {
+                summary.push(format!("{}={}", name, format_property(property)));
+            }
+        }
+        Line::from(format!("  {:<30} {}", device.name(), summary.join(" ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chambrier::model::Room;
+    use chambrier::test_support;
+
+    fn light(id: &str, name: &str, on: bool, brightness: f64) -> Device {
+        test_support::light(id)
+            .named(name)
+            .on(on)
+            .brightness_from(brightness, 0.0)
+            .build()
+    }
+
+    fn dashboard() -> Dashboard {
+        let mut dashboard = Dashboard::default();
+        let now = Utc::now();
+        dashboard.handle_event(
+            &Event::DiscoveredDevices(vec![
+                light("1", "Ceiling", true, 95.0),
+                light("2", "Floor lamp", false, 40.0),
+                light("3", "Hallway", true, 50.0),
+            ]),
+            now,
+        );
+        dashboard.handle_event(
+            &Event::DiscoveredRooms(vec![Room::new(
+                "living".to_string(),
+                "Living room".to_string(),
+                vec!["1".to_string(), "2".to_string()],
+            )]),
+            now,
+        );
+        dashboard.selected = Some("1".to_string());
+        dashboard
+    }
+
+    fn properties(action: Action) -> HashMap<String, PropertyValue> {
+        match action {
+            Action::Execute(Command::ControlDevice { properties, .. }) => properties,
+            _ => panic!("Expected a ControlDevice command"),
+        }
+    }
+
+    #[test]
+    fn groups_devices_by_room() {
+        assert_eq!(
+            vec![
+                Row::Room("Living room".to_string()),
+                Row::Device("1".to_string()),
+                Row::Device("2".to_string()),
+                Row::Room("Other".to_string()),
+                Row::Device("3".to_string()),
+            ],
+            dashboard().rows()
+        );
+    }
+
+    #[test]
+    fn moves_the_selection_across_rooms() {
+        let mut dashboard = dashboard();
+
+        dashboard.handle_key(KeyCode::Down);
+        dashboard.handle_key(KeyCode::Down);
+        dashboard.handle_key(KeyCode::Down);
+
+        assert_eq!(Some("3"), dashboard.selected.as_deref());
+    }
+
+    #[test]
+    fn toggles_the_selected_light() {
+        let mut dashboard = dashboard();
+
+        let properties = properties(dashboard.handle_key(KeyCode::Char(' ')));
+
+        assert_eq!(PropertyValue::Boolean(false), properties["on"]);
+    }
+
+    #[test]
+    fn identifies_the_selected_light() {
+        let mut dashboard = dashboard();
+
+        assert!(matches!(
+            dashboard.handle_key(KeyCode::Char('i')),
+            Action::Execute(Command::Alert { device_id }) if device_id == "1"
+        ));
+    }
+
+    #[test]
+    fn steps_brightness_within_bounds_and_turns_the_light_on() {
+        let mut dashboard = dashboard();
+
+        let brighter = properties(dashboard.handle_key(KeyCode::Right));
+        dashboard.handle_key(KeyCode::Down);
+        let dimmer = properties(dashboard.handle_key(KeyCode::Left));
+
+        assert_eq!(PropertyValue::Number(Some(100.0)), brighter["brightness"]);
+        assert!(!brighter.contains_key("on"));
+        assert_eq!(PropertyValue::Number(Some(30.0)), dimmer["brightness"]);
+        assert_eq!(PropertyValue::Boolean(true), dimmer["on"]);
+    }
+
+    #[test]
+    fn adds_up_steps_that_are_not_confirmed_yet() {
+        let mut dashboard = dashboard();
+        dashboard.selected = Some("3".to_string());
+        let now = Utc::now();
+
+        let Action::Execute(command) = dashboard.handle_key(KeyCode::Left) else {
+            panic!("Expected a command");
+        };
+        dashboard.request(&command, now);
+        let dimmer = properties(dashboard.handle_key(KeyCode::Left));
+
+        assert_eq!(PropertyValue::Number(Some(30.0)), dimmer["brightness"]);
+        assert_eq!(
+            "Hallway                        on=on brightness=50%→40%",
+            dashboard.device_line("3").to_string().trim()
+        );
+
+        dashboard.expire_pending(now + WRITE_TIMEOUT);
+        assert_eq!(1, dashboard.health.failed_commands);
+        assert!(dashboard
+            .selected_property("brightness")
+            .unwrap()
+            .pending()
+            .is_none());
+    }
+
+    #[test]
+    fn renders_rooms_devices_and_events() -> Result<(), Box<dyn Error>> {
+        let dashboard = dashboard();
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(120, 30))?;
+
+        terminal.draw(|frame| dashboard.render(frame, Utc::now()))?;
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Living room"));
+        assert!(screen.contains("Floor lamp"));
+        assert!(screen.contains("discovered 3 devices"));
+        Ok(())
+    }
+}
@@ -1,6 +1,61 @@
-use crate::model::Device;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::model::{
+    Availability, ButtonEvent, Device, Presence, PropertyValue, Room, Scene, UpdateState,
+};
 
 #[derive(Debug)]
-pub(crate) enum Event {
+pub enum Event {
+    /// Devices that were (re)discovered. Devices that are already known keep the writes they have yet to confirm.
     DiscoveredDevices(Vec<Device>),
+    /// Devices that no longer exist, e.g. because they were removed while the event stream was interrupted.
+    DevicesRemoved(Vec<String>),
+    DiscoveredRooms(Vec<Room>),
+    DiscoveredScenes(Vec<Scene>),
+    PropertiesChanged {
+        device_id: String,
+        properties: HashMap<String, PropertyValue>,
+    },
+    ButtonPressed {
+        device_id: String,
+        control_id: u8,
+        event: ButtonEvent,
+        repeat_interval: Duration,
+    },
+    PresenceChanged(Presence),
+    /// A device became unreachable or recovered.
+    AvailabilityChanged {
+        device_id: String,
+        availability: Availability,
+    },
+    /// A write to a property was rejected or not confirmed in time, the property keeps its reported value.
+    PropertyWriteFailed {
+        device_id: String,
+        property: String,
+        error: String,
+    },
+    /// A firmware update became available, started or finished installing.
+    UpdateStateChanged {
+        device_id: String,
+        update: UpdateState,
+    },
+}
+
+impl Event {
+    /// The name of the kind of event, for logs and metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::DiscoveredDevices(_) => "discovered_devices",
+            Event::DevicesRemoved(_) => "devices_removed",
+            Event::DiscoveredRooms(_) => "discovered_rooms",
+            Event::DiscoveredScenes(_) => "discovered_scenes",
+            Event::PropertiesChanged { .. } => "properties_changed",
+            Event::ButtonPressed { .. } => "button_pressed",
+            Event::PresenceChanged(_) => "presence_changed",
+            Event::AvailabilityChanged { .. } => "availability_changed",
+            Event::PropertyWriteFailed { .. } => "property_write_failed",
+            Event::UpdateStateChanged { .. } => "update_state_changed",
+        }
+    }
 }
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::event::Event;
+use crate::model::Availability;
+
+/// The availability of every device and when it was last heard from. Clones share the same state, so it can be
+/// served while the events keep it up to date.
+#[derive(Clone, Default)]
+pub struct DeviceHealth {
+    devices: Arc<Mutex<HashMap<String, DeviceStatus>>>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct DeviceStatus {
+    device_id: String,
+    name: String,
+    availability: Availability,
+    /// When the device last reported anything, unknown until it does after discovery.
+    last_seen: Option<DateTime<Utc>>,
+}
+
+impl DeviceStatus {
+    pub fn device_id(&self) -> &String {
+        &self.device_id
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn availability(&self) -> Availability {
+        self.availability
+    }
+
+    pub fn last_seen(&self) -> Option<DateTime<Utc>> {
+        self.last_seen
+    }
+}
+
+/// How many devices there are and which of them are not fully available.
+#[derive(Serialize, Clone, Debug)]
+pub struct HealthSummary {
+    devices: usize,
+    problems: Vec<DeviceStatus>,
+}
+
+impl HealthSummary {
+    pub fn devices(&self) -> usize {
+        self.devices
+    }
+
+    pub fn problems(&self) -> &Vec<DeviceStatus> {
+        &self.problems
+    }
+}
+
+impl DeviceHealth {
+    pub fn new() -> DeviceHealth {
+        DeviceHealth::default()
+    }
+
+    pub fn handle_event(&self, event: &Event, now: DateTime<Utc>) {
+        let mut devices = self.devices();
+        match event {
+            Event::DiscoveredDevices(discovered) => {
+                for device in discovered {
+                    let status =
+                        devices
+                            .entry(device.id().clone())
+                            .or_insert_with(|| DeviceStatus {
+                                device_id: device.id().clone(),
+                                name: device.name().clone(),
+                                availability: device.availability(),
+                                last_seen: None,
+                            });
+                    status.name = device.name().clone();
+                    status.availability = device.availability();
+                }
+            }
+            Event::DevicesRemoved(device_ids) => {
+                for device_id in device_ids {
+                    devices.remove(device_id);
+                }
+            }
+            Event::PropertiesChanged { device_id, .. } | Event::ButtonPressed { device_id, .. } => {
+                if let Some(status) = devices.get_mut(device_id) {
+                    status.last_seen = Some(now);
+                }
+            }
+            Event::AvailabilityChanged {
+                device_id,
+                availability,
+            } => {
+                if let Some(status) = devices.get_mut(device_id) {
+                    status.availability = *availability;
+                    if *availability != Availability::Unavailable {
+                        status.last_seen = Some(now);
+                    }
+                }
+            }
+            Event::DiscoveredRooms(_)
+            | Event::DiscoveredScenes(_)
+            | Event::PresenceChanged(_)
+            | Event::PropertyWriteFailed { .. }
+            | Event::UpdateStateChanged { .. } => {}
+        }
+    }
+
+    pub fn device(&self, device_id: &str) -> Option<DeviceStatus> {
+        self.devices().get(device_id).cloned()
+    }
+
+    pub fn summary(&self) -> HealthSummary {
+        let devices = self.devices();
+        let mut problems: Vec<DeviceStatus> = devices
+            .values()
+            .filter(|status| status.availability != Availability::Available)
+            .cloned()
+            .collect();
+        problems.sort_by(|a, b| a.name.cmp(&b.name));
+        HealthSummary {
+            devices: devices.len(),
+            problems,
+        }
+    }
+
+    fn devices(&self) -> MutexGuard<'_, HashMap<String, DeviceStatus>> {
+        self.devices.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Device;
+    use crate::test_support;
+
+    fn device(id: &str, name: &str, availability: Availability) -> Device {
+        test_support::sensor(id)
+            .named(name)
+            .availability(availability)
+            .build()
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2024-03-15T12:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn lists_devices_that_are_not_available() {
+        let health = DeviceHealth::new();
+        health.handle_event(
+            &Event::DiscoveredDevices(vec![
+                device("1", "Hallway sensor", Availability::Available),
+                device("2", "Garden sensor", Availability::Degraded),
+                device("3", "Attic sensor", Availability::Available),
+            ]),
+            now(),
+        );
+
+        health.handle_event(
+            &Event::AvailabilityChanged {
+                device_id: "3".to_string(),
+                availability: Availability::Unavailable,
+            },
+            now(),
+        );
+
+        let summary = health.summary();
+        assert_eq!(3, summary.devices());
+        let names: Vec<&String> = summary.problems().iter().map(|s| s.name()).collect();
+        assert_eq!(vec!["Attic sensor", "Garden sensor"], names);
+    }
+
+    #[test]
+    fn remembers_when_a_device_was_last_seen() {
+        let health = DeviceHealth::new();
+        health.handle_event(
+            &Event::DiscoveredDevices(vec![device("1", "Hallway sensor", Availability::Available)]),
+            now(),
+        );
+        assert_eq!(None, health.device("1").and_then(|s| s.last_seen()));
+
+        let later = now() + chrono::Duration::minutes(5);
+        health.handle_event(
+            &Event::PropertiesChanged {
+                device_id: "1".to_string(),
+                properties: HashMap::new(),
+            },
+            later,
+        );
+        health.handle_event(
+            &Event::AvailabilityChanged {
+                device_id: "1".to_string(),
+                availability: Availability::Unavailable,
+            },
+            later + chrono::Duration::minutes(5),
+        );
+
+        let status = health.device("1").unwrap();
+        assert_eq!(Some(later), status.last_seen());
+        assert_eq!(Availability::Unavailable, status.availability());
+    }
+}
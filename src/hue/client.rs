@@ -1,55 +1,390 @@
-use crate::hue::devices_response::DevicesResponse;
-use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::Client;
+use crate::hue::devices_response::{HueError, ResourceType};
+use crate::hue::entertainment_request::EntertainmentConfigurationPut;
+use crate::hue::light_request::LightPut;
+use crate::hue::recording::{Recorder, Traffic};
+use crate::hue::scene_request::ScenePut;
+use crate::hue::software_update_request::SoftwareUpdatePut;
+use crate::metrics::Metrics;
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT};
+use reqwest::{Client, Method, Request, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
 use std::env;
 use std::env::VarError;
+use std::str::FromStr;
+use std::time::Duration;
 use thiserror::Error;
+use tokio::time::{sleep, Instant};
+use tracing::{debug, error, info_span, warn, Instrument};
 
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_RETRIES: u32 = 3;
+/// Retries wait this long, doubled for every retry and between half and one and a half times that at random, so
+/// clients that failed together do not retry together.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Debug)]
 pub struct HueClient {
     client: Client,
     endpoint: String,
+    timeout: Duration,
+    retries: u32,
+    metrics: Option<Metrics>,
+    recorder: Option<Recorder>,
 }
 
 impl HueClient {
+    /// A client for the bridge at `HUE_ENDPOINT` with the key `HUE_APP_KEY`. `HUE_TIMEOUT_MS` and `HUE_RETRIES`
+    /// override the timeout and the number of retries, if set.
     pub fn new() -> Result<HueClient, HueClientError> {
+        Self::from_vars(|key| env::var(key))
+    }
+
+    /// A client configured by `HUE_ENDPOINT`, `HUE_APP_KEY` and optionally `HUE_TIMEOUT_MS` and `HUE_RETRIES`, as
+    /// looked up by `var`.
+    fn from_vars(
+        var: impl Fn(&str) -> Result<String, VarError>,
+    ) -> Result<HueClient, HueClientError> {
+        let required = |key: &str| var(key).map_err(|e| var_error(key, e));
+        let mut client =
+            Self::with_endpoint(&required("HUE_ENDPOINT")?, &required("HUE_APP_KEY")?)?;
+        if let Some(timeout) = optional_number(&var, "HUE_TIMEOUT_MS")? {
+            client = client.with_timeout(Duration::from_millis(timeout));
+        }
+        if let Some(retries) = optional_number(&var, "HUE_RETRIES")? {
+            client = client.with_retries(retries);
+        }
+        Ok(client)
+    }
+
+    /// A client for the bridge at `endpoint`, instead of the one configured by the environment.
+    pub fn with_endpoint(
+        endpoint: &str,
+        hue_application_key: &str,
+    ) -> Result<HueClient, HueClientError> {
         Ok(HueClient {
-            client: HueClient::http_client()?,
-            endpoint: Self::env_var("HUE_ENDPOINT".to_string())?,
+            client: HueClient::http_client(hue_application_key)?,
+            endpoint: endpoint.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            metrics: None,
+            recorder: None,
         })
     }
 
-    pub(in crate::hue) async fn fetch_devices(&self) -> Result<DevicesResponse, HueClientError> {
+    /// How long the bridge gets to respond to a request. The event stream is exempt, it stays open.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How often a request that failed for a transient reason is sent again. Only requests that read from the bridge
+    /// are retried here, the [`crate::hue::HueCommandQueue`] retries writes so they stay in order.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Reports the latency of every request, and the reconnects of the event stream, to `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Records every request that changes a resource with the response of the bridge, next to what the
+    /// [`crate::hue::HueObserver`] records.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub(in crate::hue) fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    pub(in crate::hue) fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// The unparsed response of `/clip/v2/resource`, so it can be recorded as received.
+    pub(in crate::hue) async fn fetch_resources(&self) -> Result<String, HueClientError> {
+        self.fetch("clip/v2/resource".to_string()).await
+    }
+
+    /// The unparsed response of `/clip/v2/resource/{type}`, every resource of one type.
+    pub(in crate::hue) async fn fetch_resources_of_type(
+        &self,
+        resource_type: ResourceType,
+    ) -> Result<String, HueClientError> {
+        self.fetch(format!("clip/v2/resource/{}", resource_type.name()))
+            .await
+    }
+
+    /// The unparsed response of `/clip/v2/resource/{type}/{id}`, a single resource.
+    pub(in crate::hue) async fn fetch_resource(
+        &self,
+        resource_type: ResourceType,
+        id: &str,
+    ) -> Result<String, HueClientError> {
+        self.fetch(format!("clip/v2/resource/{}/{}", resource_type.name(), id))
+            .await
+    }
+
+    async fn fetch(&self, path: String) -> Result<String, HueClientError> {
         let response = self
-            .client
-            .get(format!("https://{}/clip/v2/resource", self.endpoint))
-            .send()
+            .send(
+                self.client
+                    .get(format!("https://{}/{}", self.endpoint, path))
+                    .timeout(self.timeout),
+            )
             .await?
-            .json::<DevicesResponse>()
+            .text()
             .await?;
         Ok(response)
     }
 
-    fn http_client() -> Result<Client, HueClientError> {
-        let hue_application_key = Self::env_var("HUE_APP_KEY".to_string())?;
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            "hue-application-key",
-            HeaderValue::from_str(&hue_application_key)
-                .map_err(|_| HueClientError::InvalidHeaderValue(hue_application_key.to_string()))?,
+    pub(in crate::hue) async fn update_light(
+        &self,
+        id: &str,
+        body: &LightPut,
+    ) -> Result<(), HueClientError> {
+        self.update_resource("light", id, body).await
+    }
+
+    pub(in crate::hue) async fn update_grouped_light(
+        &self,
+        id: &str,
+        body: &LightPut,
+    ) -> Result<(), HueClientError> {
+        self.update_resource("grouped_light", id, body).await
+    }
+
+    pub(in crate::hue) async fn update_scene(
+        &self,
+        id: &str,
+        body: &ScenePut,
+    ) -> Result<(), HueClientError> {
+        self.update_resource("scene", id, body).await
+    }
+
+    pub(in crate::hue) async fn update_entertainment_configuration(
+        &self,
+        id: &str,
+        body: &EntertainmentConfigurationPut,
+    ) -> Result<(), HueClientError> {
+        self.update_resource("entertainment_configuration", id, body)
+            .await
+    }
+
+    pub(in crate::hue) async fn update_software(
+        &self,
+        id: &str,
+        body: &SoftwareUpdatePut,
+    ) -> Result<(), HueClientError> {
+        self.update_resource("device_software_update", id, body)
+            .await
+    }
+
+    async fn update_resource<T: Serialize>(
+        &self,
+        resource_type: &str,
+        id: &str,
+        body: &T,
+    ) -> Result<(), HueClientError> {
+        let path = format!("clip/v2/resource/{}/{}", resource_type, id);
+        let response = self
+            .send(
+                self.client
+                    .put(format!("https://{}/{}", self.endpoint, path))
+                    .timeout(self.timeout)
+                    .json(body),
+            )
+            .await;
+        let response = match response {
+            Ok(response) => response.text().await.map_err(HueClientError::from),
+            Err(e) => Err(e),
+        };
+        self.record_update(path, body, &response);
+        let response: UpdateResponse = serde_json::from_str(&response?)?;
+
+        if response.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(HueClientError::UpdateFailed(response.errors))
+        }
+    }
+
+    fn record_update<T: Serialize>(
+        &self,
+        path: String,
+        body: &T,
+        response: &Result<String, HueClientError>,
+    ) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+        let traffic = Traffic::Update {
+            path,
+            request: serde_json::to_string(body).unwrap_or_default(),
+            response: response.as_ref().ok().cloned(),
+            error: response.as_ref().err().map(ToString::to_string),
+        };
+        if let Err(e) = recorder.record(traffic) {
+            error!(error = %e, "recording bridge traffic failed");
+        }
+    }
+
+    pub(in crate::hue) async fn event_stream(&self) -> Result<Response, HueClientError> {
+        self.send(
+            self.client
+                .get(format!("https://{}/eventstream/clip/v2", self.endpoint))
+                .header(ACCEPT, "text/event-stream"),
+        )
+        .await
+    }
+
+    /// Sends a request, and sends a request that reads from the bridge again while it fails for a transient reason.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, HueClientError> {
+        let request = request.build()?;
+        let retries = if request.method() == Method::GET {
+            self.retries
+        } else {
+            0
+        };
+        let mut attempt = 0;
+        loop {
+            let Some(next) = request.try_clone().filter(|_| attempt < retries) else {
+                return self.send_once(request).await;
+            };
+            match self.send_once(next).await {
+                Err(e) if e.is_transient() => {
+                    let delay = retry_delay(attempt);
+                    warn!(error = %e, attempt, delay_ms = delay.as_millis() as u64, "retrying");
+                    sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends a request in a span of its own, logging its status and how long the bridge took to respond. A response
+    /// with an error status becomes the matching error.
+    async fn send_once(&self, request: Request) -> Result<Response, HueClientError> {
+        let span = info_span!(
+            "bridge_request",
+            method = %request.method(),
+            path = request.url().path(),
         );
+        async {
+            let start = Instant::now();
+            let method = request.method().to_string();
+            let response = self.client.execute(request).await;
+            let latency = start.elapsed();
+            if let Some(metrics) = &self.metrics {
+                let status = response.as_ref().ok().map(|r| r.status().as_u16());
+                metrics.bridge_request(&method, status, latency);
+            }
+            let latency_ms = latency.as_millis() as u64;
+            match &response {
+                Ok(response) => debug!(status = response.status().as_u16(), latency_ms, "response"),
+                Err(e) => warn!(error = %e, latency_ms, "request failed"),
+            }
+            match response {
+                Ok(response) => check_status(response).await,
+                Err(e) if e.is_timeout() => Err(HueClientError::Timeout),
+                Err(e) => Err(HueClientError::RequestError(e)),
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    fn http_client(hue_application_key: &str) -> Result<Client, HueClientError> {
+        let mut key = HeaderValue::from_str(hue_application_key)
+            .map_err(|_| HueClientError::InvalidHeaderValue(hue_application_key.to_string()))?;
+        // Keeps the key out of logs, sensitive values are redacted when the client is debug printed
+        key.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert("hue-application-key", key);
         Ok(Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
             .gzip(true)
             .danger_accept_invalid_certs(true)
             .default_headers(headers)
             .build()?)
     }
 
-    fn env_var(key: String) -> Result<String, HueClientError> {
-        env::var(&key).map_err(|e| match e {
-            VarError::NotPresent => HueClientError::EnvVarNotPresent(key),
-            VarError::NotUnicode(_) => HueClientError::EnvVarNotUnicode(key),
-        })
+    pub(in crate::hue) fn env_var(key: String) -> Result<String, HueClientError> {
+        env::var(&key).map_err(|e| var_error(&key, e))
+    }
+}
+
+fn optional_number<T: FromStr>(
+    var: &impl Fn(&str) -> Result<String, VarError>,
+    key: &str,
+) -> Result<Option<T>, HueClientError> {
+    match var(key) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| HueClientError::EnvVarNotANumber(key.to_string())),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(e) => Err(var_error(key, e)),
+    }
+}
+
+fn var_error(key: &str, error: VarError) -> HueClientError {
+    match error {
+        VarError::NotPresent => HueClientError::EnvVarNotPresent(key.to_string()),
+        VarError::NotUnicode(_) => HueClientError::EnvVarNotUnicode(key.to_string()),
+    }
+}
+
+/// Turns error statuses into errors, with the descriptions the bridge gave if it responded with JSON.
+async fn check_status(response: Response) -> Result<Response, HueClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
     }
+    let path = response.url().path().to_string();
+    let errors = response
+        .json::<UpdateResponse>()
+        .await
+        .map(|response| response.errors)
+        .unwrap_or_default();
+    Err(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => HueClientError::Unauthorized(errors),
+        StatusCode::TOO_MANY_REQUESTS => HueClientError::RateLimited(errors),
+        StatusCode::SERVICE_UNAVAILABLE => HueClientError::BridgeBusy(errors),
+        StatusCode::NOT_FOUND => HueClientError::NotFound { path, errors },
+        status => HueClientError::UnexpectedStatus {
+            status: status.as_u16(),
+            errors,
+        },
+    })
+}
+
+fn retry_delay(attempt: u32) -> Duration {
+    RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .mul_f64(rand::thread_rng().gen_range(0.5..1.5))
+}
+
+/// The descriptions of the errors the bridge responded with, for error messages.
+pub(in crate::hue) fn describe(errors: &[HueError]) -> String {
+    if errors.is_empty() {
+        return String::new();
+    }
+    let descriptions: Vec<&str> = errors.iter().map(HueError::description).collect();
+    format!(": {}", descriptions.join("; "))
+}
+
+#[derive(Deserialize, Debug)]
+struct UpdateResponse {
+    errors: Vec<HueError>,
 }
 
 #[derive(Error, Debug)]
@@ -62,4 +397,82 @@ pub enum HueClientError {
     InvalidHeaderValue(String),
     #[error(transparent)]
     RequestError(#[from] reqwest::Error),
+    #[error("invalid response from the bridge")]
+    InvalidResponse(#[from] serde_json::Error),
+    #[error("the bridge did not respond in time")]
+    Timeout,
+    #[error("update rejected by the bridge{}", describe(.0))]
+    UpdateFailed(Vec<HueError>),
+    #[error("environment variable '{0}' is not a number")]
+    EnvVarNotANumber(String),
+    #[error(
+        "the bridge rejected the application key, pair again to get a new one{}",
+        describe(.0)
+    )]
+    Unauthorized(Vec<HueError>),
+    #[error("the bridge received too many requests{}", describe(.0))]
+    RateLimited(Vec<HueError>),
+    #[error("the bridge is too busy to handle the request{}", describe(.0))]
+    BridgeBusy(Vec<HueError>),
+    #[error("the bridge has no resource at '{path}'{}", describe(errors))]
+    NotFound { path: String, errors: Vec<HueError> },
+    #[error("the bridge responded with status {status}{}", describe(errors))]
+    UnexpectedStatus { status: u16, errors: Vec<HueError> },
+    #[error("the link button of the bridge has not been pressed")]
+    LinkButtonNotPressed,
+    #[error("pairing failed: {0}")]
+    PairingFailed(String),
+}
+
+impl HueClientError {
+    /// Whether the same request may succeed when it is sent again a little later.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            HueClientError::Timeout
+            | HueClientError::RateLimited(_)
+            | HueClientError::BridgeBusy(_) => true,
+            HueClientError::RequestError(e) => e.is_connect(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_the_application_key() -> Result<(), HueClientError> {
+        let client = HueClient::with_endpoint("192.168.2.23", "secret-application-key")?;
+
+        assert!(!format!("{:?}", client).contains("secret-application-key"));
+        Ok(())
+    }
+
+    #[test]
+    fn reads_the_timeout_and_retries_from_the_environment() -> Result<(), HueClientError> {
+        let vars = |retries: &'static str| {
+            move |key: &str| match key {
+                "HUE_ENDPOINT" => Ok("192.168.2.23".to_string()),
+                "HUE_APP_KEY" => Ok("application-key".to_string()),
+                "HUE_TIMEOUT_MS" => Ok("2500".to_string()),
+                "HUE_RETRIES" => Ok(retries.to_string()),
+                _ => Err(VarError::NotPresent),
+            }
+        };
+
+        let client = HueClient::from_vars(vars("0"))?;
+        let invalid = HueClient::from_vars(vars("never"));
+        let defaults = HueClient::from_vars(|key| match key {
+            "HUE_ENDPOINT" | "HUE_APP_KEY" => Ok("set".to_string()),
+            _ => Err(VarError::NotPresent),
+        })?;
+
+        assert_eq!(Duration::from_millis(2500), client.timeout);
+        assert_eq!(0, client.retries);
+        assert!(matches!(invalid, Err(HueClientError::EnvVarNotANumber(_))));
+        assert_eq!(DEFAULT_TIMEOUT, defaults.timeout);
+        assert_eq!(DEFAULT_RETRIES, defaults.retries);
+        Ok(())
+    }
 }
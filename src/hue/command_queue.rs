@@ -0,0 +1,491 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tracing::{info_span, warn, Instrument};
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::hue::controller::{HueController, HueControllerError};
+use crate::metrics::Metrics;
+use crate::model::PropertyValue;
+
+/// The bridge handles about 10 commands for single lights per second, and 1 for a room or a scene.
+const LIGHT_INTERVAL: Duration = Duration::from_millis(100);
+const GROUP_INTERVAL: Duration = Duration::from_secs(1);
+/// A command the bridge was too busy for is sent again up to this many times, waiting twice as long every time.
+const MAX_RETRIES: u32 = 4;
+const FIRST_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Sends commands to the bridge no faster than it accepts them. Successive writes to a light are merged while they
+/// wait, and writes that set every light of a room to the same values are sent to the room at once.
+pub struct HueCommandQueue {
+    controller: HueController,
+    queued: VecDeque<Queued>,
+    next_light: Instant,
+    next_group: Instant,
+    metrics: Option<Metrics>,
+}
+
+struct Queued {
+    command: Command,
+    retries: u32,
+}
+
+/// What is sent next, the commands of every light of a room are sent as one.
+enum Request {
+    Command(Queued),
+    Room {
+        room_id: String,
+        properties: HashMap<String, PropertyValue>,
+        transition: Option<Duration>,
+        commands: Vec<Queued>,
+    },
+}
+
+impl HueCommandQueue {
+    pub fn new(controller: HueController) -> Self {
+        let now = Instant::now();
+        HueCommandQueue {
+            controller,
+            queued: VecDeque::new(),
+            next_light: now,
+            next_group: now,
+            metrics: None,
+        }
+    }
+
+    /// Reports the number of waiting commands to `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        self.controller.handle_event(event);
+    }
+
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    /// Queues a command. Properties of a light that is still waiting for an earlier write are merged into it, unless
+    /// a scene is recalled in between.
+    pub fn push(&mut self, command: Command) {
+        if let Command::ControlDevice {
+            device_id,
+            properties,
+            transition,
+        } = &command
+        {
+            for queued in self.queued.iter_mut().rev() {
+                match &mut queued.command {
+                    Command::ControlDevice {
+                        device_id: queued_id,
+                        properties: queued_properties,
+                        transition: queued_transition,
+                    } if queued_id == device_id => {
+                        queued_properties.extend(properties.clone());
+                        *queued_transition = *transition;
+                        return;
+                    }
+                    Command::RecallScene { .. } => break,
+                    _ => {}
+                }
+            }
+        }
+        self.queued.push_back(Queued {
+            command,
+            retries: 0,
+        });
+        self.report_depth();
+    }
+
+    /// When the next command may be sent, `None` if there is none.
+    pub fn ready_at(&self) -> Option<Instant> {
+        let next = self.queued.front()?;
+        Some(match next.is_group() || self.room_request(next).is_some() {
+            true => self.next_group,
+            false => self.next_light,
+        })
+    }
+
+    /// Sends the next command once it may be sent. Returns the commands that were sent and the result, or `None` if
+    /// there was nothing to send or the bridge was too busy and the commands will be sent again later.
+    pub async fn send_next(&mut self) -> Option<(Vec<Command>, Result<(), HueControllerError>)> {
+        let ready_at = self.ready_at()?;
+        tokio::time::sleep_until(ready_at).await;
+        let now = Instant::now();
+        let request = self.pop(now)?;
+
+        let result = match &request {
+            Request::Command(queued) => {
+                let span = info_span!("command", command = ?queued.command);
+                self.controller
+                    .execute(&queued.command)
+                    .instrument(span)
+                    .await
+            }
+            Request::Room {
+                room_id,
+                properties,
+                transition,
+                ..
+            } => {
+                let span = info_span!("command", room_id, ?properties);
+                self.controller
+                    .control_room(room_id, properties, *transition)
+                    .instrument(span)
+                    .await
+            }
+        };
+
+        match result {
+            Err(HueControllerError::ClientError(e)) if e.is_transient() => {
+                match self.retry(request, Instant::now()) {
+                    Ok(()) => {
+                        warn!(error = %e, "the bridge is busy, retrying");
+                        None
+                    }
+                    Err(commands) => Some((commands, Err(HueControllerError::ClientError(e)))),
+                }
+            }
+            result => Some((request.into_commands(), result)),
+        }
+    }
+
+    /// Takes the next request from the queue and holds back the ones after it so the bridge is not overwhelmed.
+    fn pop(&mut self, now: Instant) -> Option<Request> {
+        let next = self.queued.front()?;
+        let request = match self.room_request(next) {
+            Some((room_id, indices)) => {
+                let Command::ControlDevice {
+                    properties,
+                    transition,
+                    ..
+                } = &next.command
+                else {
+                    return None;
+                };
+                let (properties, transition) = (properties.clone(), *transition);
+                let mut commands: Vec<Queued> = indices
+                    .into_iter()
+                    .rev()
+                    .filter_map(|index| self.queued.remove(index))
+                    .collect();
+                commands.reverse();
+                Request::Room {
+                    room_id,
+                    properties,
+                    transition,
+                    commands,
+                }
+            }
+            None => Request::Command(self.queued.pop_front()?),
+        };
+        match &request {
+            Request::Command(queued) if !queued.is_group() => {
+                self.next_light = now + LIGHT_INTERVAL
+            }
+            _ => self.next_group = now + GROUP_INTERVAL,
+        }
+        self.report_depth();
+        Some(request)
+    }
+
+    /// Puts a request back in front of the queue and pauses it, or gives its commands back when out of retries.
+    fn retry(&mut self, request: Request, now: Instant) -> Result<(), Vec<Command>> {
+        let mut commands = match request {
+            Request::Command(queued) => vec![queued],
+            Request::Room { commands, .. } => commands,
+        };
+        let retries = commands
+            .iter()
+            .map(|queued| queued.retries)
+            .max()
+            .unwrap_or(0);
+        if retries >= MAX_RETRIES {
+            return Err(commands.into_iter().map(|queued| queued.command).collect());
+        }
+
+        let paused_until = now + FIRST_BACKOFF * 2u32.pow(retries);
+        self.next_light = self.next_light.max(paused_until);
+        self.next_group = self.next_group.max(paused_until);
+        for queued in commands.iter_mut() {
+            queued.retries = retries + 1;
+        }
+        for queued in commands.into_iter().rev() {
+            self.queued.push_front(queued);
+        }
+        self.report_depth();
+        Ok(())
+    }
+
+    /// The room of the light `next` controls and where the write of each of its lights waits in the queue, if every one
+    /// of them waits to be set to the same values before the next scene recall. Writes after a recall must follow it.
+    fn room_request(&self, next: &Queued) -> Option<(String, Vec<usize>)> {
+        let Command::ControlDevice {
+            device_id,
+            properties,
+            transition,
+        } = &next.command
+        else {
+            return None;
+        };
+        let (room_id, device_ids) = self.controller.room_of(device_id)?;
+        if device_ids.len() < 2 {
+            return None;
+        }
+        let before_recall: Vec<(usize, &Queued)> = self
+            .queued
+            .iter()
+            .enumerate()
+            .take_while(|(_, queued)| !queued.is_group())
+            .collect();
+        let mut indices = device_ids
+            .iter()
+            .map(|id| {
+                before_recall.iter().find_map(|(index, queued)| {
+                    matches!(&queued.command, Command::ControlDevice {
+                        device_id: queued_id,
+                        properties: queued_properties,
+                        transition: queued_transition,
+                    } if queued_id == *id && queued_properties == properties && queued_transition == transition)
+                    .then_some(*index)
+                })
+            })
+            .collect::<Option<Vec<usize>>>()?;
+        indices.sort_unstable();
+        Some((room_id.clone(), indices))
+    }
+
+    fn report_depth(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.command_queue_depth(self.queued.len());
+        }
+    }
+}
+
+impl Queued {
+    /// Whether the command affects many lights, which the bridge accepts less often.
+    fn is_group(&self) -> bool {
+        matches!(self.command, Command::RecallScene { .. })
+    }
+}
+
+impl Request {
+    fn into_commands(self) -> Vec<Command> {
+        match self {
+            Request::Command(queued) => vec![queued.command],
+            Request::Room { commands, .. } => {
+                commands.into_iter().map(|queued| queued.command).collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hue::HueClient;
+    use crate::model::{Device, Room};
+    use crate::test_support;
+
+    fn light(id: &str) -> Device {
+        test_support::light(id).on(true).build()
+    }
+
+    fn queue() -> HueCommandQueue {
+        let client = HueClient::with_endpoint("192.168.2.23", "application-key").unwrap();
+        let mut queue = HueCommandQueue::new(HueController::new(client));
+        queue.handle_event(&Event::DiscoveredDevices(vec![
+            light("1"),
+            light("2"),
+            light("3"),
+        ]));
+        queue.handle_event(&Event::DiscoveredRooms(vec![Room::new(
+            "kitchen".to_string(),
+            "Kitchen".to_string(),
+            vec!["1".to_string(), "2".to_string()],
+        )
+        .with_external_id("grouped-light-1".to_string())]));
+        queue
+    }
+
+    fn control(device_id: &str, name: &str, value: PropertyValue) -> Command {
+        Command::ControlDevice {
+            device_id: device_id.to_string(),
+            properties: HashMap::from([(name.to_string(), value)]),
+            transition: None,
+        }
+    }
+
+    fn brightness(value: f64) -> PropertyValue {
+        PropertyValue::Number(Some(value))
+    }
+
+    #[test]
+    fn merges_successive_writes_to_a_light() {
+        let mut queue = queue();
+
+        queue.push(control("3", "brightness", brightness(10.0)));
+        queue.push(control("3", "on", PropertyValue::Boolean(true)));
+        queue.push(control("3", "brightness", brightness(20.0)));
+
+        assert_eq!(1, queue.len());
+        let Some(Request::Command(queued)) = queue.pop(Instant::now()) else {
+            panic!("Expected a single command");
+        };
+        assert_eq!(
+            Command::ControlDevice {
+                device_id: "3".to_string(),
+                properties: HashMap::from([
+                    ("brightness".to_string(), brightness(20.0)),
+                    ("on".to_string(), PropertyValue::Boolean(true)),
+                ]),
+                transition: None,
+            },
+            queued.command
+        );
+    }
+
+    #[test]
+    fn does_not_merge_writes_across_a_scene_recall() {
+        let mut queue = queue();
+
+        queue.push(control("3", "brightness", brightness(10.0)));
+        queue.push(Command::RecallScene {
+            scene_id: "relax".to_string(),
+            transition: None,
+        });
+        queue.push(control("3", "brightness", brightness(20.0)));
+
+        assert_eq!(3, queue.len());
+    }
+
+    #[test]
+    fn does_not_send_writes_after_a_scene_recall_to_the_room() {
+        let mut queue = queue();
+        let now = Instant::now();
+
+        queue.push(control("1", "on", PropertyValue::Boolean(true)));
+        queue.push(control("2", "on", PropertyValue::Boolean(true)));
+        queue.push(Command::RecallScene {
+            scene_id: "relax".to_string(),
+            transition: None,
+        });
+        queue.push(control("2", "brightness", brightness(10.0)));
+
+        let Some(Request::Room { commands, .. }) = queue.pop(now) else {
+            panic!("Expected the room to be controlled");
+        };
+        assert_eq!(2, commands.len());
+        assert_eq!(2, queue.len());
+        let Some(Request::Command(recall)) = queue.pop(now + GROUP_INTERVAL) else {
+            panic!("Expected the scene recall");
+        };
+        assert!(matches!(recall.command, Command::RecallScene { .. }));
+        let Some(Request::Command(queued)) = queue.pop(now + GROUP_INTERVAL * 2) else {
+            panic!("Expected the write after the scene recall");
+        };
+        assert_eq!(control("2", "brightness", brightness(10.0)), queued.command);
+    }
+
+    #[test]
+    fn does_not_send_a_room_write_when_a_light_waits_behind_a_scene_recall() {
+        let mut queue = queue();
+
+        queue.push(control("1", "on", PropertyValue::Boolean(true)));
+        queue.push(Command::RecallScene {
+            scene_id: "relax".to_string(),
+            transition: None,
+        });
+        queue.push(control("2", "on", PropertyValue::Boolean(true)));
+
+        assert!(matches!(
+            queue.pop(Instant::now()),
+            Some(Request::Command(_))
+        ));
+        assert_eq!(2, queue.len());
+    }
+
+    #[test]
+    fn sends_the_same_write_to_every_light_of_a_room_at_once() {
+        let mut queue = queue();
+        let now = Instant::now();
+
+        queue.push(control("1", "on", PropertyValue::Boolean(false)));
+        queue.push(control("3", "on", PropertyValue::Boolean(false)));
+        queue.push(control("2", "on", PropertyValue::Boolean(false)));
+
+        assert_eq!(Some(queue.next_group), queue.ready_at());
+        let Some(Request::Room {
+            room_id, commands, ..
+        }) = queue.pop(now)
+        else {
+            panic!("Expected the room to be controlled");
+        };
+        assert_eq!("kitchen", room_id);
+        assert_eq!(2, commands.len());
+        assert_eq!(1, queue.len());
+        // Lights in other rooms do not wait for the room
+        assert!(queue.ready_at().is_some_and(|ready_at| ready_at <= now));
+    }
+
+    #[test]
+    fn limits_the_rate_of_commands() {
+        let mut queue = queue();
+        let now = Instant::now();
+
+        queue.push(control("1", "on", PropertyValue::Boolean(true)));
+        queue.push(control("3", "on", PropertyValue::Boolean(true)));
+        queue.push(Command::RecallScene {
+            scene_id: "relax".to_string(),
+            transition: None,
+        });
+        queue.push(Command::RecallScene {
+            scene_id: "read".to_string(),
+            transition: None,
+        });
+
+        queue.pop(now);
+        assert_eq!(Some(now + LIGHT_INTERVAL), queue.ready_at());
+        queue.pop(now + LIGHT_INTERVAL);
+        queue.pop(now + LIGHT_INTERVAL * 2);
+        assert_eq!(
+            Some(now + LIGHT_INTERVAL * 2 + GROUP_INTERVAL),
+            queue.ready_at()
+        );
+    }
+
+    #[test]
+    fn backs_off_when_the_bridge_is_busy_and_gives_up_eventually() {
+        let mut queue = queue();
+        let now = Instant::now();
+        queue.push(control("3", "on", PropertyValue::Boolean(true)));
+
+        let request = queue.pop(now).unwrap();
+        assert!(queue.retry(request, now).is_ok());
+        assert_eq!(Some(now + FIRST_BACKOFF), queue.ready_at());
+
+        // Writes made while waiting are merged into the retried one
+        queue.push(control("3", "brightness", brightness(50.0)));
+        assert_eq!(1, queue.len());
+
+        for retry in 1..MAX_RETRIES {
+            let request = queue.pop(now).unwrap();
+            assert!(queue.retry(request, now).is_ok());
+            assert_eq!(
+                Some(now + FIRST_BACKOFF * 2u32.pow(retry)),
+                queue.ready_at()
+            );
+        }
+        let request = queue.pop(now).unwrap();
+        assert_eq!(1, queue.retry(request, now).unwrap_err().len());
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::hue::client::{HueClient, HueClientError};
+use crate::hue::light_request::LightPut;
+use crate::hue::scene_request::ScenePut;
+use crate::hue::software_update_request::SoftwareUpdatePut;
+use crate::model::{Property, PropertyValue};
+
+/// The alert every Hue light supports, it breathes for a few seconds.
+const ALERT_ACTION: &str = "breathe";
+
+/// Translates commands into requests to the bridge. Device ids are resolved to the light and software update
+/// resource ids that were discovered by the [`crate::hue::HueObserver`], and room ids to their grouped lights.
+pub struct HueController {
+    client: HueClient,
+    lights: HashMap<String, String>,
+    software_updates: HashMap<String, String>,
+    rooms: HashMap<String, GroupedLight>,
+}
+
+struct GroupedLight {
+    id: String,
+    device_ids: Vec<String>,
+}
+
+impl HueController {
+    pub fn new(client: HueClient) -> HueController {
+        HueController {
+            client,
+            lights: HashMap::new(),
+            software_updates: HashMap::new(),
+            rooms: HashMap::new(),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        if let Event::DiscoveredRooms(rooms) = event {
+            self.rooms = rooms
+                .iter()
+                .filter_map(|room| {
+                    let grouped_light = GroupedLight {
+                        id: room.external_id()?.clone(),
+                        device_ids: room.device_ids().clone(),
+                    };
+                    Some((room.id().clone(), grouped_light))
+                })
+                .collect();
+        }
+        if let Event::DevicesRemoved(device_ids) = event {
+            for device_id in device_ids {
+                self.lights.remove(device_id);
+                self.software_updates.remove(device_id);
+            }
+        }
+        if let Event::DiscoveredDevices(devices) = event {
+            for device in devices {
+                if let Some(Property::Boolean(on)) = device.properties().get("on") {
+                    if let Some(light_id) = on.external_id() {
+                        self.lights.insert(device.id().clone(), light_id.clone());
+                    }
+                }
+                if let Some(update_id) = device.firmware().and_then(|f| f.external_id()) {
+                    self.software_updates
+                        .insert(device.id().clone(), update_id.clone());
+                }
+            }
+        }
+    }
+
+    pub async fn execute(&self, command: &Command) -> Result<(), HueControllerError> {
+        match command {
+            Command::ControlDevice {
+                device_id,
+                properties,
+                transition,
+            } => {
+                let light_id = self
+                    .lights
+                    .get(device_id)
+                    .ok_or_else(|| HueControllerError::UnknownDevice(device_id.clone()))?;
+                let mut body = map_light_put(properties)?;
+                if body.is_empty() {
+                    return Ok(());
+                }
+                if let Some(transition) = transition {
+                    body = body.duration(transition.as_millis());
+                }
+                Ok(self.client.update_light(light_id, &body).await?)
+            }
+            Command::RecallScene {
+                scene_id,
+                transition,
+            } => {
+                let mut body = ScenePut::recall();
+                if let Some(transition) = transition {
+                    body = body.duration(transition.as_millis());
+                }
+                Ok(self.client.update_scene(scene_id, &body).await?)
+            }
+            Command::Alert { device_id } => {
+                let light_id = self
+                    .lights
+                    .get(device_id)
+                    .ok_or_else(|| HueControllerError::UnknownDevice(device_id.clone()))?;
+                let body = LightPut::default().alert(ALERT_ACTION.to_string());
+                Ok(self.client.update_light(light_id, &body).await?)
+            }
+            Command::SetPresence(_) | Command::StartFade { .. } => Ok(()),
+            Command::InstallUpdate { device_id } => {
+                let update_id = self
+                    .software_updates
+                    .get(device_id)
+                    .ok_or_else(|| HueControllerError::NotUpdatable(device_id.clone()))?;
+                Ok(self
+                    .client
+                    .update_software(update_id, &SoftwareUpdatePut::install())
+                    .await?)
+            }
+        }
+    }
+
+    /// The room a light is in and the lights of that room, if the room can be controlled as a whole.
+    pub(in crate::hue) fn room_of(&self, device_id: &str) -> Option<(&String, Vec<&String>)> {
+        self.rooms.iter().find_map(|(room_id, grouped_light)| {
+            grouped_light
+                .device_ids
+                .contains(&device_id.to_string())
+                .then(|| {
+                    let lights = grouped_light
+                        .device_ids
+                        .iter()
+                        .filter(|id| self.lights.contains_key(*id))
+                        .collect();
+                    (room_id, lights)
+                })
+        })
+    }
+
+    /// Sets the properties of every light in a room at once.
+    pub(in crate::hue) async fn control_room(
+        &self,
+        room_id: &str,
+        properties: &HashMap<String, PropertyValue>,
+        transition: Option<Duration>,
+    ) -> Result<(), HueControllerError> {
+        let grouped_light = self
+            .rooms
+            .get(room_id)
+            .ok_or_else(|| HueControllerError::UnknownRoom(room_id.to_string()))?;
+        let mut body = map_light_put(properties)?;
+        if body.is_empty() {
+            return Ok(());
+        }
+        if let Some(transition) = transition {
+            body = body.duration(transition.as_millis());
+        }
+        Ok(self
+            .client
+            .update_grouped_light(&grouped_light.id, &body)
+            .await?)
+    }
+}
+
+fn map_light_put(
+    properties: &HashMap<String, PropertyValue>,
+) -> Result<LightPut, HueControllerError> {
+    properties
+        .iter()
+        .try_fold(LightPut::default(), |body, (name, value)| {
+            match (name.as_str(), value) {
+                ("on", PropertyValue::Boolean(on)) => Ok(body.on(*on)),
+                ("brightness", PropertyValue::Number(Some(brightness))) => {
+                    Ok(body.brightness(*brightness))
+                }
+                ("color_temperature", PropertyValue::Number(Some(mirek))) => {
+                    Ok(body.mirek(mirek.round() as usize))
+                }
+                ("color", PropertyValue::Color(xy)) => Ok(body.xy(xy.x(), xy.y())),
+                ("dynamics_speed", PropertyValue::Number(Some(speed))) => {
+                    Ok(body.speed(speed / 100.0))
+                }
+                ("effect", PropertyValue::Text(effect)) => Ok(body.effect(effect.clone())),
+                ("timed_effect", PropertyValue::Text(effect)) => {
+                    Ok(body.timed_effect(effect.clone()))
+                }
+                _ => Err(HueControllerError::UnsupportedProperty(name.clone())),
+            }
+        })
+}
+
+#[derive(Error, Debug)]
+pub enum HueControllerError {
+    #[error(transparent)]
+    ClientError(#[from] HueClientError),
+    #[error("device '{0}' is not a known light")]
+    UnknownDevice(String),
+    #[error("property '{0}' cannot be set on a light")]
+    UnsupportedProperty(String),
+    #[error("device '{0}' cannot be updated")]
+    NotUpdatable(String),
+    #[error("room '{0}' cannot be controlled as a whole")]
+    UnknownRoom(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Xy;
+
+    #[test]
+    fn maps_properties_to_a_light_put() -> Result<(), HueControllerError> {
+        let properties = HashMap::from([
+            ("on".to_string(), PropertyValue::Boolean(true)),
+            ("brightness".to_string(), PropertyValue::Number(Some(42.0))),
+            (
+                "color_temperature".to_string(),
+                PropertyValue::Number(Some(366.0)),
+            ),
+            (
+                "color".to_string(),
+                PropertyValue::Color(Xy::new(0.5336, 0.4145)),
+            ),
+        ]);
+
+        let body = map_light_put(&properties)?;
+
+        assert_eq!(
+            LightPut::default()
+                .on(true)
+                .brightness(42.0)
+                .mirek(366)
+                .xy(0.5336, 0.4145),
+            body
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn uses_the_transition_as_the_duration_of_a_timed_effect() -> Result<(), HueControllerError> {
+        let properties = HashMap::from([(
+            "timed_effect".to_string(),
+            PropertyValue::Text("sunrise".to_string()),
+        )]);
+
+        let body = map_light_put(&properties)?.duration(60_000);
+
+        assert_eq!(
+            serde_json::json!({ "timed_effects": { "effect": "sunrise", "duration": 60000 } }),
+            serde_json::to_value(&body).unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unsupported_properties() {
+        let properties = HashMap::from([("motion".to_string(), PropertyValue::Boolean(true))]);
+
+        assert!(matches!(
+            map_light_put(&properties),
+            Err(HueControllerError::UnsupportedProperty(name)) if name == "motion"
+        ));
+    }
+}
@@ -1,5 +1,5 @@
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 // Names come from the [Hue API v2](https://developers.meethue.com/develop/hue-api-v2/api-reference/#resource).
@@ -29,10 +29,39 @@ impl DevicesResponse {
     pub fn devices(&self) -> Vec<&DeviceGet> {
         self.data
             .iter()
-            .filter(|r| matches!(r, Resource::Device(..)))
-            .map(|r| match r {
-                Resource::Device(device) => device,
-                _ => panic!("matched a resource that is not a device"),
+            .filter_map(|r| match r {
+                Resource::Device(device) => Some(device),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn rooms(&self) -> Vec<&RoomGet> {
+        self.data
+            .iter()
+            .filter_map(|r| match r {
+                Resource::Room(room) => Some(room),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn scenes(&self) -> Vec<&SceneGet> {
+        self.data
+            .iter()
+            .filter_map(|r| match r {
+                Resource::Scene(scene) => Some(scene),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn entertainment_configurations(&self) -> Vec<&EntertainmentConfigurationGet> {
+        self.data
+            .iter()
+            .filter_map(|r| match r {
+                Resource::EntertainmentConfiguration(configuration) => Some(configuration),
+                _ => None,
             })
             .collect()
     }
@@ -44,6 +73,20 @@ impl DevicesResponse {
                 Resource::Device(device) => (device.id.clone(), resource),
                 Resource::Light(light) => (light.id.clone(), resource),
                 Resource::Button(button) => (button.id.clone(), resource),
+                Resource::Room(room) => (room.id.clone(), resource),
+                Resource::Scene(scene) => (scene.id.clone(), resource),
+                Resource::Motion(motion) => (motion.id.clone(), resource),
+                Resource::LightLevel(light_level) => (light_level.id.clone(), resource),
+                Resource::Temperature(temperature) => (temperature.id.clone(), resource),
+                Resource::ZigbeeConnectivity(connectivity)
+                | Resource::ZigbeeBridgeConnectivity(connectivity) => {
+                    (connectivity.id.clone(), resource)
+                }
+                Resource::DeviceSoftwareUpdate(update) => (update.id.clone(), resource),
+                Resource::DevicePower(power) => (power.id.clone(), resource),
+                Resource::EntertainmentConfiguration(configuration) => {
+                    (configuration.id.clone(), resource)
+                }
                 Resource::Unknown => ("".to_string(), &Resource::Unknown),
             })
             .collect()
@@ -51,17 +94,39 @@ impl DevicesResponse {
 }
 
 #[derive(Deserialize, Debug)]
-pub(crate) struct HueError {
+pub struct HueError {
     description: String,
 }
 
+impl HueError {
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "type")]
 pub(crate) enum Resource {
     Device(DeviceGet),
-    Light(LightGet),
+    Light(Box<LightGet>),
     Button(ButtonGet),
+    Room(RoomGet),
+    Scene(SceneGet),
+    Motion(MotionGet),
+    #[serde(rename = "light_level")]
+    LightLevel(LightLevelGet),
+    Temperature(TemperatureGet),
+    #[serde(rename = "zigbee_connectivity")]
+    ZigbeeConnectivity(ZigbeeConnectivityGet),
+    #[serde(rename = "zigbee_bridge_connectivity")]
+    ZigbeeBridgeConnectivity(ZigbeeConnectivityGet),
+    #[serde(rename = "device_software_update")]
+    DeviceSoftwareUpdate(DeviceSoftwareUpdateGet),
+    #[serde(rename = "device_power")]
+    DevicePower(DevicePowerGet),
+    #[serde(rename = "entertainment_configuration")]
+    EntertainmentConfiguration(EntertainmentConfigurationGet),
     #[serde(other)]
     Unknown,
 }
@@ -150,9 +215,15 @@ pub(crate) struct LightGet {
     color: Option<Color>,
     dynamics: Option<Dynamics>,
     alert: Option<Alert>,
+    effects: Option<Effects>,
+    timed_effects: Option<TimedEffects>,
 }
 
 impl LightGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
     pub fn on(&self) -> bool {
         self.on.on
     }
@@ -176,6 +247,14 @@ impl LightGet {
     pub fn alert(&self) -> Option<&Alert> {
         self.alert.as_ref()
     }
+
+    pub fn effects(&self) -> Option<&Effects> {
+        self.effects.as_ref()
+    }
+
+    pub fn timed_effects(&self) -> Option<&TimedEffects> {
+        self.timed_effects.as_ref()
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -183,10 +262,26 @@ pub(crate) struct On {
     on: bool,
 }
 
+impl On {
+    pub fn on(&self) -> bool {
+        self.on
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct Diming {
-    brightness: f32,            // >= 0 && <= 100
-    min_dim_level: Option<f32>, // >= 0 && <= 100
+    brightness: f64,            // >= 0 && <= 100
+    min_dim_level: Option<f64>, // >= 0 && <= 100
+}
+
+impl Diming {
+    pub fn brightness(&self) -> f64 {
+        self.brightness
+    }
+
+    pub fn min_dim_level(&self) -> Option<f64> {
+        self.min_dim_level
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -200,6 +295,10 @@ impl ColorTemperature {
     pub fn mirek(&self) -> Option<&usize> {
         self.mirek.as_ref()
     }
+
+    pub fn mirek_schema(&self) -> &MirekSchema {
+        &self.mirek_schema
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -208,17 +307,51 @@ pub(crate) struct MirekSchema {
     mirek_maximum: usize, // >= 153 && <= 500
 }
 
+impl MirekSchema {
+    pub fn mirek_minimum(&self) -> usize {
+        self.mirek_minimum
+    }
+
+    pub fn mirek_maximum(&self) -> usize {
+        self.mirek_maximum
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct Color {
     xy: Xy,
-    gamut: Gamut,
-    gamut_type: char, // A, B or C
+    gamut: Option<Gamut>,
+    gamut_type: GamutType,
+}
+
+impl Color {
+    pub fn xy(&self) -> &Xy {
+        &self.xy
+    }
+
+    pub fn gamut(&self) -> Option<&Gamut> {
+        self.gamut.as_ref()
+    }
+
+    pub fn gamut_type(&self) -> GamutType {
+        self.gamut_type
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub(crate) struct Xy {
-    x: f32, // >= 0.0 && <= 1.0
-    y: f32, // >= 0.0 && <= 1.0
+    x: f64, // >= 0.0 && <= 1.0
+    y: f64, // >= 0.0 && <= 1.0
+}
+
+impl Xy {
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -228,6 +361,29 @@ pub(crate) struct Gamut {
     blue: Xy,
 }
 
+impl Gamut {
+    pub fn red(&self) -> &Xy {
+        &self.red
+    }
+
+    pub fn green(&self) -> &Xy {
+        &self.green
+    }
+
+    pub fn blue(&self) -> &Xy {
+        &self.blue
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+pub(crate) enum GamutType {
+    A,
+    B,
+    C,
+    #[serde(other)]
+    Other,
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct Dynamics {
     status: String,             // dynamic_pallette or none
@@ -236,11 +392,69 @@ pub(crate) struct Dynamics {
     speed_valid: bool,
 }
 
+impl Dynamics {
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn status_values(&self) -> &Vec<String> {
+        &self.status_values
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn speed_valid(&self) -> bool {
+        self.speed_valid
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct Alert {
     action_values: Vec<String>, // AlertEffectType
 }
 
+impl Alert {
+    pub fn action_values(&self) -> &Vec<String> {
+        &self.action_values
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct Effects {
+    status: String, // no_effect, candle, fire, prism, sparkle, opal, glisten, ...
+    status_values: Vec<String>, // Every status the light can report
+    effect_values: Vec<String>, // Every effect that can be set
+}
+
+impl Effects {
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn effect_values(&self) -> &Vec<String> {
+        &self.effect_values
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct TimedEffects {
+    status: String,             // no_effect, sunrise or sunset
+    status_values: Vec<String>, // Every status the light can report
+    effect_values: Vec<String>, // Every effect that can be set
+}
+
+impl TimedEffects {
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn effect_values(&self) -> &Vec<String> {
+        &self.effect_values
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct ButtonGet {
     id: String,
@@ -249,15 +463,35 @@ pub(crate) struct ButtonGet {
     button: Button,
 }
 
+impl ButtonGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn metadata(&self) -> &ButtonMetadata {
+        &self.metadata
+    }
+
+    pub fn button(&self) -> &Button {
+        &self.button
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct ButtonMetadata {
     control_id: u8, // >= 0 && <= 8
 }
 
+impl ButtonMetadata {
+    pub fn control_id(&self) -> u8 {
+        self.control_id
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct Button {
     button_report: Option<ButtonReport>,
-    repeat_interval: usize,
+    repeat_interval: usize, // In milliseconds
     event_values: Vec<ButtonEvent>,
 }
 
@@ -266,6 +500,10 @@ impl Button {
         self.button_report.as_ref()
     }
 
+    pub fn repeat_interval(&self) -> usize {
+        self.repeat_interval
+    }
+
     pub fn event_values(&self) -> Vec<ButtonEvent> {
         self.event_values.clone()
     }
@@ -277,6 +515,16 @@ pub(crate) struct ButtonReport {
     event: ButtonEvent,
 }
 
+impl ButtonReport {
+    pub fn updated(&self) -> DateTime<Utc> {
+        self.updated
+    }
+
+    pub fn event(&self) -> ButtonEvent {
+        self.event
+    }
+}
+
 #[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum ButtonEvent {
@@ -288,6 +536,382 @@ pub(crate) enum ButtonEvent {
     LongPress,
 }
 
+#[derive(Deserialize, Debug)]
+pub(crate) struct RoomGet {
+    id: String,
+    metadata: RoomMetadata,
+    children: Vec<ResourceIdentifierGet>,
+    services: Vec<ResourceIdentifierGet>,
+}
+
+impl RoomGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn metadata(&self) -> &RoomMetadata {
+        &self.metadata
+    }
+
+    pub fn children(&self) -> Vec<&ResourceIdentifierGet> {
+        self.children.iter().collect()
+    }
+
+    pub fn services(&self) -> Vec<&ResourceIdentifierGet> {
+        self.services.iter().collect()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct RoomMetadata {
+    name: String,
+}
+
+impl RoomMetadata {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct MotionGet {
+    id: String,
+    owner: ResourceIdentifierGet,
+    enabled: bool,
+    motion: Motion,
+}
+
+impl MotionGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn motion(&self) -> &Motion {
+        &self.motion
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct Motion {
+    motion: bool,
+    motion_valid: bool,
+}
+
+impl Motion {
+    pub fn motion(&self) -> bool {
+        self.motion
+    }
+
+    pub fn motion_valid(&self) -> bool {
+        self.motion_valid
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct LightLevelGet {
+    id: String,
+    owner: ResourceIdentifierGet,
+    enabled: bool,
+    light: LightLevel,
+}
+
+impl LightLevelGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn light(&self) -> &LightLevel {
+        &self.light
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct LightLevel {
+    light_level: usize, // 10000 * log10(lux) + 1
+    light_level_valid: bool,
+}
+
+impl LightLevel {
+    pub fn light_level(&self) -> usize {
+        self.light_level
+    }
+
+    pub fn light_level_valid(&self) -> bool {
+        self.light_level_valid
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct TemperatureGet {
+    id: String,
+    owner: ResourceIdentifierGet,
+    enabled: bool,
+    temperature: Temperature,
+}
+
+impl TemperatureGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn temperature(&self) -> &Temperature {
+        &self.temperature
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct Temperature {
+    temperature: f64, // °C
+    temperature_valid: bool,
+}
+
+impl Temperature {
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    pub fn temperature_valid(&self) -> bool {
+        self.temperature_valid
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ZigbeeConnectivityGet {
+    id: String,
+    owner: ResourceIdentifierGet,
+    status: ZigbeeStatus,
+}
+
+impl ZigbeeConnectivityGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn status(&self) -> ZigbeeStatus {
+        self.status
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ZigbeeStatus {
+    Connected,
+    Disconnected,
+    ConnectivityIssue,
+    /// Messages from the device arrive, but those to it do not.
+    UnidirectionalIncoming,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct DevicePowerGet {
+    id: String,
+    owner: ResourceIdentifierGet,
+    power_state: PowerState,
+}
+
+impl DevicePowerGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn power_state(&self) -> &PowerState {
+        &self.power_state
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct PowerState {
+    battery_state: Option<BatteryState>,
+    battery_level: Option<usize>, // >= 0 && <= 100
+}
+
+impl PowerState {
+    pub fn battery_state(&self) -> Option<BatteryState> {
+        self.battery_state
+    }
+
+    pub fn battery_level(&self) -> Option<usize> {
+        self.battery_level
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BatteryState {
+    Normal,
+    Low,
+    Critical,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct DeviceSoftwareUpdateGet {
+    id: String,
+    owner: ResourceIdentifierGet,
+    state: SoftwareUpdateState,
+    problems: Vec<String>,
+}
+
+impl DeviceSoftwareUpdateGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn state(&self) -> SoftwareUpdateState {
+        self.state
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SoftwareUpdateState {
+    NoUpdate,
+    UpdatePending,
+    ReadyToInstall,
+    Installing,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct SceneGet {
+    id: String,
+    metadata: SceneMetadata,
+    group: ResourceIdentifierGet,
+}
+
+impl SceneGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn metadata(&self) -> &SceneMetadata {
+        &self.metadata
+    }
+
+    pub fn group(&self) -> &ResourceIdentifierGet {
+        &self.group
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct SceneMetadata {
+    name: String,
+}
+
+impl SceneMetadata {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// An entertainment area, the lights of a room placed in front of, behind and around the user for streaming.
+#[derive(Deserialize, Debug)]
+pub(crate) struct EntertainmentConfigurationGet {
+    id: String,
+    metadata: SceneMetadata,
+    configuration_type: EntertainmentConfigurationType,
+    status: EntertainmentStatus,
+    channels: Vec<EntertainmentChannelGet>,
+}
+
+impl EntertainmentConfigurationGet {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn metadata(&self) -> &SceneMetadata {
+        &self.metadata
+    }
+
+    pub fn configuration_type(&self) -> EntertainmentConfigurationType {
+        self.configuration_type
+    }
+
+    pub fn status(&self) -> EntertainmentStatus {
+        self.status
+    }
+
+    pub fn channels(&self) -> Vec<&EntertainmentChannelGet> {
+        self.channels.iter().collect()
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EntertainmentConfigurationType {
+    Screen,
+    Monitor,
+    Music,
+    #[serde(rename = "3dspace")]
+    Space3d,
+    Other,
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EntertainmentStatus {
+    Active,
+    Inactive,
+}
+
+/// What a frame addresses, one or more lights or segments of a light at a position in the area.
+#[derive(Deserialize, Debug)]
+pub(crate) struct EntertainmentChannelGet {
+    channel_id: u8,
+    position: Position,
+    members: Vec<EntertainmentChannelMember>,
+}
+
+impl EntertainmentChannelGet {
+    pub fn channel_id(&self) -> u8 {
+        self.channel_id
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn members(&self) -> Vec<&EntertainmentChannelMember> {
+        self.members.iter().collect()
+    }
+}
+
+/// From -1 to 1 on every axis: left to right, back to front and bottom to top, seen from the user.
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+pub(crate) struct Position {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Position {
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+
+    pub fn z(&self) -> f64 {
+        self.z
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct EntertainmentChannelMember {
+    service: ResourceIdentifierGet,
+    index: u32,
+}
+
+impl EntertainmentChannelMember {
+    pub fn service(&self) -> &ResourceIdentifierGet {
+        &self.service
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 pub(crate) struct ResourceIdentifierGet {
     rid: String,
@@ -299,11 +923,11 @@ impl ResourceIdentifierGet {
         &self.rid
     }
     pub fn rtype(&self) -> ResourceType {
-        self.rtype.clone()
+        self.rtype
     }
 }
 
-#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum ResourceType {
     AuthV1,
@@ -345,8 +969,19 @@ pub(crate) enum ResourceType {
     Zone,
 }
 
+impl ResourceType {
+    /// The name the bridge uses for the type in resource paths, e.g. `grouped_light`.
+    pub fn name(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum Archetype {
     Bollard,
     BridgeV2,
@@ -407,7 +1042,24 @@ mod tests {
         let errors = response.errors();
 
         assert_eq!(0, errors.len());
-        assert_eq!(2, data.len());
+        assert_eq!(3, data.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserializes_a_software_update() -> Result<(), Box<dyn Error>> {
+        let response = fs::read_to_string("tests/resources/devices_response_light.json")?;
+        let response = from_str::<DevicesResponse>(&response)?;
+
+        if let Resource::DeviceSoftwareUpdate(update) = response.data()[2] {
+            assert_eq!("64ac92d6-41b3-4f81-bd6d-315f01dc59c3", update.id);
+            assert_eq!("90bdce60-3704-470e-be4c-8264f2bc8151", update.owner.rid);
+            assert_eq!(SoftwareUpdateState::ReadyToInstall, update.state);
+            assert!(update.problems.is_empty());
+        } else {
+            panic!("data[2] is not a Resource::DeviceSoftwareUpdate");
+        }
 
         Ok(())
     }
@@ -455,7 +1107,7 @@ mod tests {
                 device.services
             );
         } else {
-            assert!(false, "data[0] is not a Resource::Device");
+            panic!("data[0] is not a Resource::Device");
         }
 
         Ok(())
@@ -479,7 +1131,7 @@ mod tests {
             assert_eq!(24.11, light.dimming().unwrap().brightness);
             assert_eq!(2.0, light.dimming().unwrap().min_dim_level.unwrap());
             assert!(light.color_temperature().unwrap().mirek.is_none());
-            assert_eq!(false, light.color_temperature().unwrap().mirek_valid);
+            assert!(!light.color_temperature().unwrap().mirek_valid);
             assert_eq!(
                 153,
                 light
@@ -498,19 +1150,150 @@ mod tests {
             );
             assert_eq!(0.669, light.color().unwrap().xy.x);
             assert_eq!(0.3251, light.color().unwrap().xy.y);
-            assert_eq!(0.675, light.color().unwrap().gamut.red.x);
-            assert_eq!(0.322, light.color().unwrap().gamut.red.y);
-            assert_eq!(0.409, light.color().unwrap().gamut.green.x);
-            assert_eq!(0.518, light.color().unwrap().gamut.green.y);
-            assert_eq!(0.167, light.color().unwrap().gamut.blue.x);
-            assert_eq!(0.04, light.color().unwrap().gamut.blue.y);
-            assert_eq!('B', light.color().unwrap().gamut_type);
+            let gamut = light.color().unwrap().gamut.as_ref().unwrap();
+            assert_eq!(0.675, gamut.red.x);
+            assert_eq!(0.322, gamut.red.y);
+            assert_eq!(0.409, gamut.green.x);
+            assert_eq!(0.518, gamut.green.y);
+            assert_eq!(0.167, gamut.blue.x);
+            assert_eq!(0.04, gamut.blue.y);
+            assert_eq!(GamutType::B, light.color().unwrap().gamut_type);
             assert_eq!("none", light.dynamics().unwrap().status);
             assert_eq!(vec!["none"], light.dynamics().unwrap().status_values);
             assert_eq!(0.0, light.dynamics().unwrap().speed);
-            assert_eq!(false, light.dynamics().unwrap().speed_valid);
+            assert!(!light.dynamics().unwrap().speed_valid);
+            assert_eq!(vec!["breathe"], light.alert().unwrap().action_values);
+            assert_eq!("candle", light.effects().unwrap().status);
+            assert_eq!(
+                vec!["no_effect", "candle"],
+                light.effects().unwrap().effect_values
+            );
+            assert_eq!(
+                vec!["no_effect", "sunrise", "sunset"],
+                light.timed_effects().unwrap().effect_values
+            );
+        } else {
+            panic!("data[1] is not a Resource::Light");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserializes_a_room() -> Result<(), Box<dyn Error>> {
+        let response = fs::read_to_string("tests/resources/devices_response_room.json")?;
+        let response = from_str::<DevicesResponse>(&response)?;
+
+        let rooms = response.rooms();
+        assert_eq!(1, rooms.len());
+        assert_eq!("0f9a3e4b-5c2d-4a8e-9b7f-6d1c2e3f4a5b", rooms[0].id);
+        assert_eq!("Living room", rooms[0].metadata.name);
+        assert_eq!(
+            vec![&ResourceIdentifierGet {
+                rid: "90bdce60-3704-470e-be4c-8264f2bc8151".to_string(),
+                rtype: ResourceType::Device
+            }],
+            rooms[0].children()
+        );
+        assert_eq!(
+            vec![&ResourceIdentifierGet {
+                rid: "b7c1d2e3-f4a5-4b6c-8d7e-9f0a1b2c3d4e".to_string(),
+                rtype: ResourceType::GroupedLight
+            }],
+            rooms[0].services()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserializes_a_scene() -> Result<(), Box<dyn Error>> {
+        let response = fs::read_to_string("tests/resources/devices_response_room.json")?;
+        let response = from_str::<DevicesResponse>(&response)?;
+
+        let scenes = response.scenes();
+        assert_eq!(1, scenes.len());
+        assert_eq!("5c8d2f1a-7b3e-4c9d-a1f2-3e4d5c6b7a89", scenes[0].id);
+        assert_eq!("Relax", scenes[0].metadata.name);
+        assert_eq!(
+            ResourceIdentifierGet {
+                rid: "0f9a3e4b-5c2d-4a8e-9b7f-6d1c2e3f4a5b".to_string(),
+                rtype: ResourceType::Room
+            },
+            scenes[0].group
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserializes_an_entertainment_configuration() -> Result<(), Box<dyn Error>> {
+        let response = fs::read_to_string("tests/resources/devices_response_entertainment.json")?;
+        let response = from_str::<DevicesResponse>(&response)?;
+
+        let configurations = response.entertainment_configurations();
+        assert_eq!(1, configurations.len());
+        assert_eq!("TV area", configurations[0].metadata.name);
+        assert_eq!(
+            EntertainmentConfigurationType::Screen,
+            configurations[0].configuration_type
+        );
+        assert_eq!(EntertainmentStatus::Inactive, configurations[0].status);
+        let channel = &configurations[0].channels[1];
+        assert_eq!(1, channel.channel_id);
+        assert_eq!(
+            Position {
+                x: 0.8,
+                y: 0.8,
+                z: 0.0
+            },
+            channel.position
+        );
+        assert_eq!(
+            ResourceType::Entertainment,
+            channel.members[0].service.rtype
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserializes_a_motion_sensor() -> Result<(), Box<dyn Error>> {
+        let response = fs::read_to_string("tests/resources/devices_response_motion.json")?;
+        let response = from_str::<DevicesResponse>(&response)?;
+
+        let data = response.data();
+        assert_eq!(6, data.len());
+
+        if let Resource::Motion(motion) = data[1] {
+            assert_eq!("6a7b8c9d-0e1f-4a2b-8c3d-4e5f6a7b8c9d", motion.id);
+            assert!(motion.enabled);
+            assert!(motion.motion.motion);
+            assert!(motion.motion.motion_valid);
+        } else {
+            panic!("data[1] is not a Resource::Motion");
+        }
+
+        if let Resource::LightLevel(light_level) = data[2] {
+            assert_eq!("9d0e1f2a-3b4c-4d5e-bf6a-7b8c9d0e1f2a", light_level.id);
+            assert_eq!(14320, light_level.light.light_level);
+            assert!(light_level.light.light_level_valid);
+        } else {
+            panic!("data[2] is not a Resource::LightLevel");
+        }
+
+        if let Resource::ZigbeeConnectivity(connectivity) = data[3] {
+            assert_eq!(ZigbeeStatus::ConnectivityIssue, connectivity.status());
+        } else {
+            panic!("data[3] is not a Resource::ZigbeeConnectivity");
+        }
+
+        if let Resource::Temperature(temperature) = data[5] {
+            assert_eq!("ae1f2a3b-4c5d-4e6f-8a7b-8c9d0e1f2a3b", temperature.id);
+            assert_eq!(21.37, temperature.temperature.temperature);
+            assert!(temperature.temperature.temperature_valid);
         } else {
-            assert!(false, "data[1] is not a Resource::Light");
+            panic!("data[5] is not a Resource::Temperature");
         }
 
         Ok(())
@@ -550,7 +1333,7 @@ mod tests {
                 button.button.event_values()
             );
         } else {
-            assert!(false, "data[1] is not a Resource::Button");
+            panic!("data[1] is not a Resource::Button");
         }
 
         if let Resource::Button(button) = data[2] {
@@ -564,7 +1347,7 @@ mod tests {
                 button.button.button_report().unwrap().event
             );
         } else {
-            assert!(false, "data[2] is not a Resource::Button");
+            panic!("data[2] is not a Resource::Button");
         }
 
         Ok(())
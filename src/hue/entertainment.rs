@@ -0,0 +1,719 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use openssl::error::ErrorStack;
+use openssl::ssl::{HandshakeError, Ssl, SslContext, SslMethod, SslStream, SslVersion};
+use serde::Serialize;
+use thiserror::Error;
+use tokio::task;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+use tracing::{info, warn};
+
+use crate::hue::client::{describe, HueClient, HueClientError};
+use crate::hue::devices_response::{
+    DevicesResponse, EntertainmentConfigurationGet, EntertainmentConfigurationType,
+    EntertainmentStatus, HueError, ResourceType,
+};
+use crate::hue::entertainment_request::EntertainmentConfigurationPut;
+use crate::model::{Rgb, Xy};
+
+/// The port the bridge accepts streams on.
+const STREAM_PORT: u16 = 2100;
+/// The only cipher suite the bridge offers, `TLS_PSK_WITH_AES_128_GCM_SHA256`.
+pub(in crate::hue) const CIPHER: &str = "PSK-AES128-GCM-SHA256";
+/// How long the bridge gets to complete the whole handshake. A handshake with the wrong client key ends this way, the
+/// bridge silently drops what it cannot decrypt.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(4);
+/// How long a read waits for the bridge during the handshake before the handshake is resumed. Resuming resends the
+/// last flight once OpenSSL's retransmission timer has run out, after one second and then twice as long each time, so
+/// a lost datagram does not fail the handshake.
+const HANDSHAKE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// The bridge forwards at most 25 frames a second to the lights, streams are sent faster so a lost datagram is made up
+/// by the next one.
+const MIN_RATE: u32 = 25;
+const MAX_RATE: u32 = 50;
+/// The most channels a single frame may hold.
+const MAX_CHANNELS: usize = 20;
+
+const PROTOCOL_NAME: &[u8] = b"HueStream";
+const PROTOCOL_VERSION: [u8; 2] = [2, 0];
+const HEADER_LENGTH: usize = 16;
+
+/// Streams colours to the lights of entertainment areas over the bridge's DTLS streaming protocol, for effects that
+/// change faster than the bridge accepts requests, like music- or screen-synced lighting.
+pub struct HueEntertainment {
+    client: HueClient,
+    application_key: String,
+    client_key: Vec<u8>,
+    port: u16,
+}
+
+/// An entertainment area as configured in the Hue app.
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct EntertainmentArea {
+    id: String,
+    name: String,
+    kind: String,
+    active: bool,
+    channels: Vec<EntertainmentChannel>,
+}
+
+/// A light, or a segment of a gradient light, at a position from -1 to 1 on every axis: left to right, back to front
+/// and bottom to top, seen from the user.
+#[derive(Serialize, Copy, Clone, PartialEq, Debug)]
+pub struct EntertainmentChannel {
+    id: u8,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+/// The colours of some channels of an area at one moment. Channels that are left out keep their colour.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Frame {
+    color_space: ColorSpace,
+    channels: Vec<(u8, [u16; 3])>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum ColorSpace {
+    Rgb = 0,
+    XyBrightness = 1,
+}
+
+/// Produces the frames of an effect, e.g. from the beat of music or the colours at the edges of a screen.
+pub trait FrameProducer {
+    /// The frame to show `elapsed` after the effect started, `None` ends the effect.
+    fn frame(&mut self, elapsed: Duration, channels: &[EntertainmentChannel]) -> Option<Frame>;
+}
+
+impl<F> FrameProducer for F
+where
+    F: FnMut(Duration, &[EntertainmentChannel]) -> Option<Frame>,
+{
+    fn frame(&mut self, elapsed: Duration, channels: &[EntertainmentChannel]) -> Option<Frame> {
+        self(elapsed, channels)
+    }
+}
+
+/// A started entertainment area. The bridge ignores requests for its lights while it streams, and ends the stream
+/// when it receives no frame for 10 seconds. Stop it with [`EntertainmentStream::stop`] to hand the lights back
+/// right away.
+pub struct EntertainmentStream {
+    client: HueClient,
+    area: EntertainmentArea,
+    stream: SslStream<UdpStream>,
+    sequence: u8,
+}
+
+/// A UDP socket connected to a single peer, as a stream for OpenSSL's DTLS. Every write is sent as one datagram.
+#[derive(Debug)]
+pub(in crate::hue) struct UdpStream(pub(in crate::hue) UdpSocket);
+
+impl HueEntertainment {
+    /// Streams with the keys printed by `chambrier pair`, `HUE_APP_KEY` and `HUE_CLIENT_KEY`.
+    pub fn new() -> Result<HueEntertainment, HueEntertainmentError> {
+        HueEntertainment::with_keys(
+            HueClient::new()?,
+            &HueClient::env_var("HUE_APP_KEY".to_string())?,
+            &HueClient::env_var("HUE_CLIENT_KEY".to_string())?,
+        )
+    }
+
+    /// Streams with the keys of a pairing, the client key being the 32 hexadecimal digits the bridge responded with.
+    pub fn with_keys(
+        client: HueClient,
+        application_key: &str,
+        client_key: &str,
+    ) -> Result<HueEntertainment, HueEntertainmentError> {
+        Ok(HueEntertainment {
+            client,
+            application_key: application_key.to_string(),
+            client_key: decode_client_key(client_key)?,
+            port: STREAM_PORT,
+        })
+    }
+
+    /// Streams to another port than the bridge's, e.g. that of a stand-in.
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub async fn areas(&self) -> Result<Vec<EntertainmentArea>, HueEntertainmentError> {
+        let body = self
+            .client
+            .fetch_resources_of_type(ResourceType::EntertainmentConfiguration)
+            .await?;
+        let response: DevicesResponse =
+            serde_json::from_str(&body).map_err(HueEntertainmentError::InvalidResources)?;
+        if !response.errors().is_empty() {
+            return Err(HueEntertainmentError::FetchAreasResponse(
+                response.take_errors(),
+            ));
+        }
+        Ok(response
+            .entertainment_configurations()
+            .into_iter()
+            .map(map_area)
+            .collect())
+    }
+
+    /// Claims the area and connects to the bridge to stream to it. The area is handed back if connecting fails.
+    pub async fn start(
+        &self,
+        area: &EntertainmentArea,
+    ) -> Result<EntertainmentStream, HueEntertainmentError> {
+        self.client
+            .update_entertainment_configuration(&area.id, &EntertainmentConfigurationPut::start())
+            .await?;
+
+        let address = stream_address(self.client.endpoint(), self.port);
+        let application_key = self.application_key.clone();
+        let client_key = self.client_key.clone();
+        let connected =
+            task::spawn_blocking(move || connect(&address, application_key, client_key))
+                .await
+                .map_err(|e| HueEntertainmentError::HandshakeFailed(e.to_string()))
+                .and_then(|connected| connected);
+        match connected {
+            Ok(stream) => {
+                info!(area = area.name, "streaming to the entertainment area");
+                Ok(EntertainmentStream {
+                    client: self.client.clone(),
+                    area: area.clone(),
+                    stream,
+                    sequence: 0,
+                })
+            }
+            Err(e) => {
+                let stop = EntertainmentConfigurationPut::stop();
+                if let Err(e) = self
+                    .client
+                    .update_entertainment_configuration(&area.id, &stop)
+                    .await
+                {
+                    warn!(area = area.name, error = %e, "handing back the entertainment area failed");
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl EntertainmentArea {
+    pub fn id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    /// What the area is set up for: `screen`, `monitor`, `music`, `3dspace` or `other`.
+    pub fn kind(&self) -> &String {
+        &self.kind
+    }
+
+    /// Whether something is streaming to the area.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    pub fn channels(&self) -> &Vec<EntertainmentChannel> {
+        &self.channels
+    }
+}
+
+impl EntertainmentChannel {
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+
+    pub fn z(&self) -> f64 {
+        self.z
+    }
+}
+
+impl Frame {
+    pub fn rgb(colors: impl IntoIterator<Item = (u8, Rgb)>) -> Frame {
+        Frame {
+            color_space: ColorSpace::Rgb,
+            channels: colors
+                .into_iter()
+                .map(|(channel, rgb)| {
+                    let color = [rgb.red(), rgb.green(), rgb.blue()].map(|c| c as u16 * 257);
+                    (channel, color)
+                })
+                .collect(),
+        }
+    }
+
+    /// Colours as chromaticities with a brightness from 0 to 1, the bridge fits them into each light's gamut.
+    pub fn xy(colors: impl IntoIterator<Item = (u8, Xy, f64)>) -> Frame {
+        Frame {
+            color_space: ColorSpace::XyBrightness,
+            channels: colors
+                .into_iter()
+                .map(|(channel, xy, brightness)| {
+                    let color = [xy.x(), xy.y(), brightness]
+                        .map(|c| (c.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16);
+                    (channel, color)
+                })
+                .collect(),
+        }
+    }
+
+    /// The ids of the channels the frame sets.
+    pub fn channels(&self) -> Vec<u8> {
+        self.channels.iter().map(|(channel, _)| *channel).collect()
+    }
+
+    /// The message of the streaming protocol: a header with the area's id, then each channel with its colour as three
+    /// big-endian 16 bit numbers.
+    pub(in crate::hue) fn encode(&self, area_id: &str, sequence: u8) -> Vec<u8> {
+        let mut message =
+            Vec::with_capacity(HEADER_LENGTH + area_id.len() + self.channels.len() * 7);
+        message.extend_from_slice(PROTOCOL_NAME);
+        message.extend_from_slice(&PROTOCOL_VERSION);
+        message.push(sequence);
+        message.extend_from_slice(&[0, 0]);
+        message.push(self.color_space as u8);
+        message.push(0);
+        message.extend_from_slice(area_id.as_bytes());
+        for (channel, color) in &self.channels {
+            message.push(*channel);
+            for component in color {
+                message.extend_from_slice(&component.to_be_bytes());
+            }
+        }
+        message
+    }
+}
+
+impl EntertainmentStream {
+    pub fn area(&self) -> &EntertainmentArea {
+        &self.area
+    }
+
+    /// Writes the frame to the socket right away. Sending a datagram only blocks while the socket's send buffer is
+    /// full, which a few dozen small frames a second never fill, so [`EntertainmentStream::run`] calls it from async
+    /// code rather than from a blocking task.
+    pub fn send(&mut self, frame: &Frame) -> Result<(), HueEntertainmentError> {
+        if frame.channels.len() > MAX_CHANNELS {
+            return Err(HueEntertainmentError::TooManyChannels(frame.channels.len()));
+        }
+        if let Some(channel) = frame
+            .channels()
+            .into_iter()
+            .find(|channel| !self.area.channels.iter().any(|c| c.id == *channel))
+        {
+            return Err(HueEntertainmentError::UnknownChannel(channel));
+        }
+        self.stream
+            .write_all(&frame.encode(&self.area.id, self.sequence))?;
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Sends the frames of `producer` at `rate` frames a second, limited to 25 to 50, until it ends the effect. A
+    /// frame that is late is skipped rather than sent in a burst with the next.
+    pub async fn run(
+        &mut self,
+        producer: &mut impl FrameProducer,
+        rate: u32,
+    ) -> Result<(), HueEntertainmentError> {
+        let mut ticks = interval(Duration::from_secs(1) / rate.clamp(MIN_RATE, MAX_RATE));
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let started = Instant::now();
+        loop {
+            ticks.tick().await;
+            let channels = self.area.channels.clone();
+            match producer.frame(started.elapsed(), &channels) {
+                Some(frame) => self.send(&frame)?,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Ends the stream and hands the area's lights back to the bridge.
+    pub async fn stop(mut self) -> Result<(), HueEntertainmentError> {
+        // The bridge notices a stream that ended without a close notify as well, just later
+        if let Err(e) = self.stream.shutdown() {
+            warn!(error = %e, "closing the stream failed");
+        }
+        self.client
+            .update_entertainment_configuration(
+                &self.area.id,
+                &EntertainmentConfigurationPut::stop(),
+            )
+            .await?;
+        info!(
+            area = self.area.name,
+            "stopped streaming to the entertainment area"
+        );
+        Ok(())
+    }
+}
+
+impl Read for UdpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.recv(buf)
+    }
+}
+
+impl Write for UdpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn map_area(configuration: &EntertainmentConfigurationGet) -> EntertainmentArea {
+    let kind = match configuration.configuration_type() {
+        EntertainmentConfigurationType::Screen => "screen",
+        EntertainmentConfigurationType::Monitor => "monitor",
+        EntertainmentConfigurationType::Music => "music",
+        EntertainmentConfigurationType::Space3d => "3dspace",
+        EntertainmentConfigurationType::Other => "other",
+    };
+    EntertainmentArea {
+        id: configuration.id().to_string(),
+        name: configuration.metadata().name().to_string(),
+        kind: kind.to_string(),
+        active: configuration.status() == EntertainmentStatus::Active,
+        channels: configuration
+            .channels()
+            .into_iter()
+            .map(|channel| EntertainmentChannel {
+                id: channel.channel_id(),
+                x: channel.position().x(),
+                y: channel.position().y(),
+                z: channel.position().z(),
+            })
+            .collect(),
+    }
+}
+
+pub(in crate::hue) fn decode_client_key(
+    client_key: &str,
+) -> Result<Vec<u8>, HueEntertainmentError> {
+    if client_key.len() != 32 || !client_key.is_ascii() {
+        return Err(HueEntertainmentError::InvalidClientKey);
+    }
+    (0..client_key.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&client_key[i..i + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| HueEntertainmentError::InvalidClientKey)
+}
+
+/// The bridge's host, without the port of its HTTPS endpoint if it has one, with the streaming port.
+fn stream_address(endpoint: &str, port: u16) -> (String, u16) {
+    let host = match endpoint.parse::<SocketAddr>() {
+        Ok(address) => address.ip().to_string(),
+        Err(_) => match endpoint.rsplit_once(':') {
+            Some((host, _)) if !host.contains(':') => host.to_string(),
+            _ => endpoint.to_string(),
+        },
+    };
+    (host, port)
+}
+
+/// Performs the DTLS handshake, identified by the application key and authenticated by the client key.
+fn connect(
+    address: &(String, u16),
+    application_key: String,
+    client_key: Vec<u8>,
+) -> Result<SslStream<UdpStream>, HueEntertainmentError> {
+    let address = (address.0.as_str(), address.1)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| HueEntertainmentError::HandshakeFailed("unknown host".to_string()))?;
+    let socket = UdpSocket::bind(if address.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    })?;
+    socket.connect(address)?;
+    socket.set_read_timeout(Some(HANDSHAKE_POLL_INTERVAL))?;
+
+    let mut context = SslContext::builder(SslMethod::dtls())?;
+    context.set_min_proto_version(Some(SslVersion::DTLS1_2))?;
+    context.set_cipher_list(CIPHER)?;
+    context.set_psk_client_callback(move |_, _, identity, psk| {
+        // The identity is NUL-terminated
+        if application_key.len() >= identity.len() || client_key.len() > psk.len() {
+            return Ok(0);
+        }
+        identity[..application_key.len()].copy_from_slice(application_key.as_bytes());
+        identity[application_key.len()] = 0;
+        psk[..client_key.len()].copy_from_slice(&client_key);
+        Ok(client_key.len())
+    });
+    let ssl = Ssl::new(&context.build())?;
+    let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+    let mut handshake = ssl.connect(UdpStream(socket));
+    loop {
+        match handshake {
+            Ok(stream) => return Ok(stream),
+            Err(HandshakeError::WouldBlock(stream)) if Instant::now() < deadline => {
+                handshake = stream.handshake();
+            }
+            Err(HandshakeError::WouldBlock(_)) => {
+                return Err(HueEntertainmentError::HandshakeFailed(
+                    "the bridge did not respond in time".to_string(),
+                ))
+            }
+            Err(e) => return Err(HueEntertainmentError::HandshakeFailed(e.to_string())),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum HueEntertainmentError {
+    #[error(transparent)]
+    ClientError(#[from] HueClientError),
+    #[error(
+        "the client key must be the 32 hexadecimal digits the bridge responded with when pairing"
+    )]
+    InvalidClientKey,
+    #[error("invalid entertainment areas received from the bridge")]
+    InvalidResources(serde_json::Error),
+    #[error("fetch entertainment areas response contains errors{}", describe(.0))]
+    FetchAreasResponse(Vec<HueError>),
+    #[error("DTLS handshake with the bridge failed: {0}")]
+    HandshakeFailed(String),
+    #[error(transparent)]
+    TlsError(#[from] ErrorStack),
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error("a frame holds at most {MAX_CHANNELS} channels, not {0}")]
+    TooManyChannels(usize),
+    #[error("the entertainment area has no channel {0}")]
+    UnknownChannel(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tokio::time::{sleep, timeout};
+
+    use super::*;
+    use crate::hue::FakeBridge;
+
+    const AREA_ID: &str = "1a8d99cc-967b-44f2-9202-43f976c0fa6b";
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    async fn bridge() -> FakeBridge {
+        let fixture = fs::read_to_string("tests/resources/devices_response_entertainment.json")
+            .expect("the fixture is readable");
+        FakeBridge::start(&fixture)
+            .await
+            .expect("the bridge starts")
+    }
+
+    fn entertainment(bridge: &FakeBridge) -> HueEntertainment {
+        let client = HueClient::with_endpoint(bridge.endpoint(), bridge.application_key())
+            .expect("a valid client");
+        HueEntertainment::with_keys(client, bridge.application_key(), bridge.client_key())
+            .expect("a valid client key")
+            .with_port(bridge.entertainment_port())
+    }
+
+    #[test]
+    fn encodes_an_rgb_frame() {
+        let frame = Frame::rgb([(1, Rgb::new(255, 128, 0))]);
+
+        let message = frame.encode(AREA_ID, 7);
+
+        assert_eq!(b"HueStream", &message[..9]);
+        assert_eq!([2, 0, 7, 0, 0, 0, 0], message[9..16]);
+        assert_eq!(AREA_ID.as_bytes(), &message[16..52]);
+        assert_eq!([1, 0xff, 0xff, 0x80, 0x80, 0, 0], message[52..]);
+    }
+
+    #[test]
+    fn encodes_an_xy_frame_with_brightness() {
+        let frame = Frame::xy([(0, Xy::new(0.5, 0.25), 1.0)]);
+
+        let message = frame.encode(AREA_ID, 0);
+
+        assert_eq!(1, message[14]);
+        assert_eq!([0, 0x80, 0x00, 0x40, 0x00, 0xff, 0xff], message[52..]);
+    }
+
+    #[test]
+    fn rejects_an_invalid_client_key() {
+        assert_eq!(
+            vec![0xab; 16],
+            decode_client_key(&"AB".repeat(16)).unwrap_or_default()
+        );
+        assert!(matches!(
+            decode_client_key("not a key"),
+            Err(HueEntertainmentError::InvalidClientKey)
+        ));
+        assert!(matches!(
+            decode_client_key("0011223344556677889900112233445G"),
+            Err(HueEntertainmentError::InvalidClientKey)
+        ));
+    }
+
+    #[test]
+    fn streams_to_the_host_of_the_endpoint() {
+        assert_eq!(
+            ("192.168.2.23".to_string(), 2100),
+            stream_address("192.168.2.23", 2100)
+        );
+        assert_eq!(
+            ("127.0.0.1".to_string(), 2100),
+            stream_address("127.0.0.1:8443", 2100)
+        );
+        assert_eq!(
+            ("hue-bridge.local".to_string(), 2100),
+            stream_address("hue-bridge.local:443", 2100)
+        );
+        assert_eq!(("::1".to_string(), 2100), stream_address("[::1]:443", 2100));
+    }
+
+    #[tokio::test]
+    async fn lists_areas_with_their_channels() -> Result<(), HueEntertainmentError> {
+        let bridge = bridge().await;
+
+        let areas = entertainment(&bridge).areas().await?;
+
+        assert_eq!(1, areas.len());
+        assert_eq!("TV area", areas[0].name());
+        assert_eq!("screen", areas[0].kind());
+        assert!(!areas[0].active());
+        assert_eq!(
+            vec![
+                EntertainmentChannel {
+                    id: 0,
+                    x: -0.8,
+                    y: 0.8,
+                    z: 0.0
+                },
+                EntertainmentChannel {
+                    id: 1,
+                    x: 0.8,
+                    y: 0.8,
+                    z: 0.0
+                },
+            ],
+            *areas[0].channels()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn streams_the_frames_of_a_producer() -> Result<(), HueEntertainmentError> {
+        let bridge = bridge().await;
+        let entertainment = entertainment(&bridge);
+        let area = entertainment.areas().await?.remove(0);
+
+        let mut stream = entertainment.start(&area).await?;
+        assert_eq!("active", bridge.resource(AREA_ID).unwrap()["status"]);
+        let mut frames = 0;
+        let mut producer = |_: Duration, channels: &[EntertainmentChannel]| {
+            frames += 1;
+            (frames <= 5).then(|| {
+                Frame::rgb(
+                    channels
+                        .iter()
+                        .map(|channel| (channel.id(), Rgb::new(frames, 0, 0))),
+                )
+            })
+        };
+        stream.run(&mut producer, 50).await?;
+        stream.stop().await?;
+
+        assert_eq!("inactive", bridge.resource(AREA_ID).unwrap()["status"]);
+        timeout(TIMEOUT, async {
+            while bridge.frames().len() < 5 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the frames arrive");
+        let received = bridge.frames();
+        assert_eq!(
+            vec![0, 1, 2, 3, 4],
+            received.iter().map(|frame| frame[11]).collect::<Vec<_>>()
+        );
+        assert_eq!(52 + 2 * 7, received[0].len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resends_a_lost_client_hello() -> Result<(), HueEntertainmentError> {
+        let bridge = bridge().await;
+        bridge.drop_stream_datagrams(1);
+        let entertainment = entertainment(&bridge);
+        let area = entertainment.areas().await?.remove(0);
+
+        let mut stream = entertainment.start(&area).await?;
+        stream.send(&Frame::rgb([(0, Rgb::new(255, 0, 0))]))?;
+
+        timeout(TIMEOUT, async {
+            while bridge.frames().is_empty() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the frame arrives");
+        stream.stop().await
+    }
+
+    #[tokio::test]
+    async fn rejects_frames_for_channels_the_area_does_not_have(
+    ) -> Result<(), HueEntertainmentError> {
+        let bridge = bridge().await;
+        let entertainment = entertainment(&bridge);
+        let area = entertainment.areas().await?.remove(0);
+        let mut stream = entertainment.start(&area).await?;
+
+        let result = stream.send(&Frame::rgb([(9, Rgb::new(0, 0, 255))]));
+
+        assert!(matches!(
+            result,
+            Err(HueEntertainmentError::UnknownChannel(9))
+        ));
+        stream.stop().await
+    }
+
+    #[tokio::test]
+    async fn hands_back_the_area_when_the_handshake_fails() -> Result<(), HueEntertainmentError> {
+        let bridge = bridge().await;
+        let client = HueClient::with_endpoint(bridge.endpoint(), bridge.application_key())?;
+        let entertainment = HueEntertainment::with_keys(
+            client,
+            bridge.application_key(),
+            "FFEEDDCCBBAA99887766554433221100",
+        )?
+        .with_port(bridge.entertainment_port());
+        let area = entertainment.areas().await?.remove(0);
+
+        let result = entertainment.start(&area).await;
+
+        assert!(matches!(
+            result,
+            Err(HueEntertainmentError::HandshakeFailed(_))
+        ));
+        assert_eq!("inactive", bridge.resource(AREA_ID).unwrap()["status"]);
+        Ok(())
+    }
+}
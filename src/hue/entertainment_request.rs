@@ -0,0 +1,30 @@
+use serde::Serialize;
+
+// Request bodies for `PUT /clip/v2/resource/entertainment_configuration/{id}`.
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct EntertainmentConfigurationPut {
+    action: EntertainmentAction,
+}
+
+impl EntertainmentConfigurationPut {
+    /// Claims the area for streaming, the bridge then accepts a DTLS connection for it.
+    pub fn start() -> Self {
+        EntertainmentConfigurationPut {
+            action: EntertainmentAction::Start,
+        }
+    }
+
+    pub fn stop() -> Self {
+        EntertainmentConfigurationPut {
+            action: EntertainmentAction::Stop,
+        }
+    }
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EntertainmentAction {
+    Start,
+    Stop,
+}
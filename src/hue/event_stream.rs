@@ -0,0 +1,426 @@
+use serde::Deserialize;
+
+use crate::hue::devices_response::{
+    ButtonEvent, ButtonReport, Diming, LightLevel, Motion, On, PowerState, ResourceIdentifierGet,
+    SoftwareUpdateState, Temperature, Xy, ZigbeeStatus,
+};
+
+// Messages sent over the `/eventstream/clip/v2` server-sent events endpoint. Updates only contain the changed fields.
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct EventStreamMessage {
+    #[serde(rename = "type")]
+    message_type: EventStreamMessageType,
+    data: Vec<ResourceUpdate>,
+}
+
+impl EventStreamMessage {
+    pub fn message_type(&self) -> EventStreamMessageType {
+        self.message_type
+    }
+
+    pub fn data(&self) -> Vec<&ResourceUpdate> {
+        self.data
+            .iter()
+            .filter(|r| !matches!(r, ResourceUpdate::Unknown))
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EventStreamMessageType {
+    Add,
+    Delete,
+    Error,
+    Update,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub(crate) enum ResourceUpdate {
+    Light(LightUpdate),
+    Button(ButtonUpdate),
+    Motion(MotionUpdate),
+    LightLevel(LightLevelUpdate),
+    Temperature(TemperatureUpdate),
+    ZigbeeConnectivity(ConnectivityUpdate),
+    ZigbeeBridgeConnectivity(ConnectivityUpdate),
+    DeviceSoftwareUpdate(SoftwareUpdateUpdate),
+    DevicePower(DevicePowerUpdate),
+    #[serde(other)]
+    Unknown,
+}
+
+impl ResourceUpdate {
+    /// The resource the updated service belongs to, usually a device.
+    pub fn owner(&self) -> Option<&ResourceIdentifierGet> {
+        match self {
+            ResourceUpdate::Light(light) => Some(light.owner()),
+            ResourceUpdate::Button(button) => Some(button.owner()),
+            ResourceUpdate::Motion(motion) => Some(motion.owner()),
+            ResourceUpdate::LightLevel(light_level) => Some(light_level.owner()),
+            ResourceUpdate::Temperature(temperature) => Some(temperature.owner()),
+            ResourceUpdate::ZigbeeConnectivity(connectivity)
+            | ResourceUpdate::ZigbeeBridgeConnectivity(connectivity) => Some(connectivity.owner()),
+            ResourceUpdate::DeviceSoftwareUpdate(update) => Some(update.owner()),
+            ResourceUpdate::DevicePower(power) => Some(power.owner()),
+            ResourceUpdate::Unknown => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct LightUpdate {
+    owner: ResourceIdentifierGet,
+    on: Option<On>,
+    dimming: Option<Diming>,
+    color_temperature: Option<ColorTemperatureUpdate>,
+    color: Option<ColorUpdate>,
+    dynamics: Option<DynamicsUpdate>,
+    effects: Option<EffectsUpdate>,
+    timed_effects: Option<EffectsUpdate>,
+}
+
+impl LightUpdate {
+    pub fn owner(&self) -> &ResourceIdentifierGet {
+        &self.owner
+    }
+
+    pub fn on(&self) -> Option<bool> {
+        self.on.as_ref().map(|on| on.on())
+    }
+
+    pub fn dimming(&self) -> Option<&Diming> {
+        self.dimming.as_ref()
+    }
+
+    pub fn color_temperature(&self) -> Option<&ColorTemperatureUpdate> {
+        self.color_temperature.as_ref()
+    }
+
+    pub fn color(&self) -> Option<&ColorUpdate> {
+        self.color.as_ref()
+    }
+
+    pub fn dynamics(&self) -> Option<&DynamicsUpdate> {
+        self.dynamics.as_ref()
+    }
+
+    pub fn effects(&self) -> Option<&EffectsUpdate> {
+        self.effects.as_ref()
+    }
+
+    pub fn timed_effects(&self) -> Option<&EffectsUpdate> {
+        self.timed_effects.as_ref()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ColorUpdate {
+    xy: Xy,
+}
+
+impl ColorUpdate {
+    pub fn xy(&self) -> &Xy {
+        &self.xy
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct DynamicsUpdate {
+    status: Option<String>,
+    speed: Option<f32>,
+    speed_valid: Option<bool>,
+}
+
+impl DynamicsUpdate {
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    /// The speed, `None` if it was not updated or is not valid for the current status.
+    pub fn speed(&self) -> Option<f32> {
+        self.speed
+            .filter(|_| self.speed_valid.unwrap_or(self.speed.is_some()))
+    }
+}
+
+/// An update of the `effects` or `timed_effects` of a light.
+#[derive(Deserialize, Debug)]
+pub(crate) struct EffectsUpdate {
+    status: Option<String>,
+}
+
+impl EffectsUpdate {
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ColorTemperatureUpdate {
+    mirek: Option<usize>,
+    mirek_valid: Option<bool>,
+}
+
+impl ColorTemperatureUpdate {
+    pub fn mirek(&self) -> Option<usize> {
+        self.mirek
+    }
+
+    pub fn mirek_valid(&self) -> bool {
+        self.mirek_valid.unwrap_or(self.mirek.is_some())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ButtonUpdate {
+    id: String,
+    owner: ResourceIdentifierGet,
+    button: ButtonUpdateState,
+}
+
+impl ButtonUpdate {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn owner(&self) -> &ResourceIdentifierGet {
+        &self.owner
+    }
+
+    /// Older firmware only reports `last_event`, newer firmware adds `button_report`.
+    pub fn event(&self) -> Option<ButtonEvent> {
+        self.button
+            .button_report
+            .as_ref()
+            .map(|report| report.event())
+            .or(self.button.last_event)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ButtonUpdateState {
+    button_report: Option<ButtonReport>,
+    last_event: Option<ButtonEvent>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct MotionUpdate {
+    owner: ResourceIdentifierGet,
+    motion: Option<Motion>,
+}
+
+impl MotionUpdate {
+    pub fn owner(&self) -> &ResourceIdentifierGet {
+        &self.owner
+    }
+
+    pub fn motion(&self) -> Option<&Motion> {
+        self.motion.as_ref()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct LightLevelUpdate {
+    owner: ResourceIdentifierGet,
+    light: Option<LightLevel>,
+}
+
+impl LightLevelUpdate {
+    pub fn owner(&self) -> &ResourceIdentifierGet {
+        &self.owner
+    }
+
+    pub fn light(&self) -> Option<&LightLevel> {
+        self.light.as_ref()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct TemperatureUpdate {
+    owner: ResourceIdentifierGet,
+    temperature: Option<Temperature>,
+}
+
+impl TemperatureUpdate {
+    pub fn owner(&self) -> &ResourceIdentifierGet {
+        &self.owner
+    }
+
+    pub fn temperature(&self) -> Option<&Temperature> {
+        self.temperature.as_ref()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ConnectivityUpdate {
+    owner: ResourceIdentifierGet,
+    status: Option<ZigbeeStatus>,
+}
+
+impl ConnectivityUpdate {
+    pub fn owner(&self) -> &ResourceIdentifierGet {
+        &self.owner
+    }
+
+    pub fn status(&self) -> Option<ZigbeeStatus> {
+        self.status
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct DevicePowerUpdate {
+    owner: ResourceIdentifierGet,
+    power_state: Option<PowerState>,
+}
+
+impl DevicePowerUpdate {
+    pub fn owner(&self) -> &ResourceIdentifierGet {
+        &self.owner
+    }
+
+    pub fn power_state(&self) -> Option<&PowerState> {
+        self.power_state.as_ref()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct SoftwareUpdateUpdate {
+    owner: ResourceIdentifierGet,
+    state: Option<SoftwareUpdateState>,
+}
+
+impl SoftwareUpdateUpdate {
+    pub fn owner(&self) -> &ResourceIdentifierGet {
+        &self.owner
+    }
+
+    pub fn state(&self) -> Option<SoftwareUpdateState> {
+        self.state
+    }
+}
+
+/// Splits the raw server-sent events byte stream into messages. Chunks do not necessarily end on an event boundary,
+/// so anything after the last complete event is kept until the next chunk arrives.
+#[derive(Default)]
+pub(crate) struct EventStreamParser {
+    buffer: Vec<u8>,
+}
+
+impl EventStreamParser {
+    /// Buffers the chunk and returns the data of every event that is complete, without parsing it.
+    pub fn push_data(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut data = vec![];
+        while let Some((end, separator_length)) = find_event_boundary(&self.buffer) {
+            let event: Vec<u8> = self
+                .buffer
+                .drain(..end + separator_length)
+                .take(end)
+                .collect();
+            let event_data = String::from_utf8_lossy(&event)
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.trim_start())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if !event_data.is_empty() {
+                data.push(event_data);
+            }
+        }
+        data
+    }
+
+    pub fn parse(data: &str) -> Result<Vec<EventStreamMessage>, serde_json::Error> {
+        serde_json::from_str(data)
+    }
+}
+
+fn find_event_boundary(buffer: &[u8]) -> Option<(usize, usize)> {
+    let lf = buffer.windows(2).position(|w| w == b"\n\n").map(|p| (p, 2));
+    let crlf = buffer
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|p| (p, 4));
+    match (lf, crlf) {
+        (Some(lf), Some(crlf)) => Some(if lf.0 < crlf.0 { lf } else { crlf }),
+        (lf, crlf) => lf.or(crlf),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hue::devices_response::ResourceType;
+
+    impl EventStreamParser {
+        pub(crate) fn push(
+            &mut self,
+            chunk: &[u8],
+        ) -> Result<Vec<EventStreamMessage>, serde_json::Error> {
+            let mut messages = vec![];
+            for data in self.push_data(chunk) {
+                messages.extend(Self::parse(&data)?);
+            }
+            Ok(messages)
+        }
+    }
+
+    const LIGHT_EVENT: &str = r#"id: 1700000000:0
+data: [{"creationtime":"2023-11-14T22:13:20Z","data":[{"id":"4e5ad66f-633e-4300-84cd-634129fdb451","id_v1":"/lights/25","on":{"on":false},"owner":{"rid":"90bdce60-3704-470e-be4c-8264f2bc8151","rtype":"device"},"type":"light"}],"id":"a1b2c3d4-e5f6-4a7b-8c9d-0e1f2a3b4c5d","type":"update"}]
+
+"#;
+
+    #[test]
+    fn parses_a_light_update() -> Result<(), serde_json::Error> {
+        let mut parser = EventStreamParser::default();
+        let messages = parser.push(LIGHT_EVENT.as_bytes())?;
+
+        assert_eq!(1, messages.len());
+        assert_eq!(EventStreamMessageType::Update, messages[0].message_type());
+        if let ResourceUpdate::Light(light) = messages[0].data()[0] {
+            assert_eq!("90bdce60-3704-470e-be4c-8264f2bc8151", light.owner().rid());
+            assert_eq!(ResourceType::Device, light.owner().rtype());
+            assert_eq!(Some(false), light.on());
+            assert!(light.dimming().is_none());
+        } else {
+            panic!("data[0] is not a ResourceUpdate::Light");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_a_button_update() -> Result<(), serde_json::Error> {
+        let event = r#"data: [{"creationtime":"2023-11-14T22:13:20Z","data":[{"button":{"button_report":{"event":"initial_press","updated":"2023-11-14T22:13:20.015Z"},"last_event":"initial_press"},"id":"9ea998a8-c996-4a8b-a652-cb7baa9d26e5","id_v1":"/sensors/12","owner":{"rid":"e84075f8-023f-43e7-80ea-c0246fdf2835","rtype":"device"},"type":"button"}],"id":"b2c3d4e5-f6a7-4b8c-9d0e-1f2a3b4c5d6e","type":"update"}]
+
+"#;
+        let messages = EventStreamParser::default().push(event.as_bytes())?;
+
+        if let ResourceUpdate::Button(button) = messages[0].data()[0] {
+            assert_eq!("9ea998a8-c996-4a8b-a652-cb7baa9d26e5", button.id());
+            assert_eq!(Some(ButtonEvent::InitialPress), button.event());
+        } else {
+            panic!("data[0] is not a ResourceUpdate::Button");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_incomplete_events_until_the_next_chunk() -> Result<(), serde_json::Error> {
+        let mut parser = EventStreamParser::default();
+        let (first, second) = LIGHT_EVENT.split_at(80);
+
+        assert_eq!(0, parser.push(b": hi\n\n")?.len());
+        assert_eq!(0, parser.push(first.as_bytes())?.len());
+        assert_eq!(1, parser.push(second.as_bytes())?.len());
+
+        Ok(())
+    }
+}
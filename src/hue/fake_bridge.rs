@@ -0,0 +1,913 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::ssl::{ErrorCode, HandshakeError, Ssl, SslContext, SslMethod, SslStream};
+use openssl::x509::{X509NameBuilder, X509};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tokio_native_tls::native_tls::{self, Identity};
+use tokio_native_tls::TlsAcceptor;
+
+use crate::hue::entertainment::{decode_client_key, UdpStream, CIPHER};
+use crate::hue::HueEntertainmentError;
+
+/// The stand-in for the streaming port wakes up this often to notice the bridge was dropped.
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Like the bridge, a stream that sends nothing for this long is ended.
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An in-process stand-in for a Hue bridge. It serves the resources of a fixture over HTTPS with a self-signed
+/// certificate, applies PUTs to its state and reports them on its event stream, like the bridge does. Failures,
+/// latency and dropped event streams can be injected to test how chambrier copes with them. Entertainment streams are
+/// accepted over DTLS on a port of their own.
+pub struct FakeBridge {
+    endpoint: String,
+    entertainment_port: u16,
+    shared: Arc<Shared>,
+    server: JoinHandle<()>,
+}
+
+struct Shared {
+    application_key: String,
+    client_key: String,
+    state: Mutex<BridgeState>,
+    stream: broadcast::Sender<StreamMessage>,
+    stopped: AtomicBool,
+}
+
+#[derive(Default)]
+struct BridgeState {
+    resources: Vec<Value>,
+    failures: VecDeque<StatusCode>,
+    latency: Duration,
+    messages_sent: u64,
+    link_button_pressed: bool,
+    frames: Vec<Vec<u8>>,
+    datagrams_to_drop: usize,
+}
+
+#[derive(Clone, Debug)]
+enum StreamMessage {
+    Event(String),
+    Disconnect,
+}
+
+impl FakeBridge {
+    /// Starts a bridge on a free local port serving the resources of `fixture`, a response of `/clip/v2/resource`.
+    pub async fn start(fixture: &str) -> Result<FakeBridge, FakeBridgeError> {
+        let fixture: Value = serde_json::from_str(fixture)?;
+        let resources = match fixture.get("data") {
+            Some(Value::Array(resources)) => resources.clone(),
+            _ => return Err(FakeBridgeError::InvalidFixture),
+        };
+
+        let acceptor = TlsAcceptor::from(native_tls::TlsAcceptor::new(self_signed_identity()?)?);
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let endpoint = listener.local_addr()?.to_string();
+        let (stream, _) = broadcast::channel(100);
+        let shared = Arc::new(Shared {
+            application_key: "fake-application-key".to_string(),
+            client_key: "00112233445566778899AABBCCDDEEFF".to_string(),
+            state: Mutex::new(BridgeState {
+                resources,
+                ..BridgeState::default()
+            }),
+            stream,
+            stopped: AtomicBool::new(false),
+        });
+
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        let entertainment_port = socket.local_addr()?.port();
+        let context = stream_context(&shared)?;
+        let stream_shared = shared.clone();
+        thread::spawn(move || serve_streams(socket, context, stream_shared));
+
+        let server_shared = shared.clone();
+        let server = tokio::spawn(async move {
+            while let Ok((connection, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                let shared = server_shared.clone();
+                tokio::spawn(async move {
+                    let Ok(connection) = acceptor.accept(connection).await else {
+                        return;
+                    };
+                    let service = service_fn(move |request| handle(request, shared.clone()));
+                    let _ = Http::new().serve_connection(connection, service).await;
+                });
+            }
+        });
+
+        Ok(FakeBridge {
+            endpoint,
+            entertainment_port,
+            shared,
+            server,
+        })
+    }
+
+    /// The address to use as `HUE_ENDPOINT`.
+    pub fn endpoint(&self) -> &String {
+        &self.endpoint
+    }
+
+    /// The key every request must send in the `hue-application-key` header.
+    pub fn application_key(&self) -> &String {
+        &self.shared.application_key
+    }
+
+    /// The key to stream to entertainment areas with, as returned when pairing.
+    pub fn client_key(&self) -> &String {
+        &self.shared.client_key
+    }
+
+    /// The port entertainment streams are accepted on, instead of the bridge's 2100.
+    pub fn entertainment_port(&self) -> u16 {
+        self.entertainment_port
+    }
+
+    /// The decrypted messages received on entertainment streams, only those for areas that were started.
+    pub fn frames(&self) -> Vec<Vec<u8>> {
+        self.shared.state().frames.clone()
+    }
+
+    /// Drops the next `count` datagrams received on the streaming port, as a lossy network would, e.g. the first
+    /// flight of a handshake.
+    pub fn drop_stream_datagrams(&self, count: usize) {
+        self.shared.state().datagrams_to_drop += count;
+    }
+
+    /// The current state of a resource, including the changes made by PUTs and emitted updates.
+    pub fn resource(&self, id: &str) -> Option<Value> {
+        self.shared.state().find(id).cloned()
+    }
+
+    /// The number of clients following the event stream.
+    pub fn event_streams(&self) -> usize {
+        self.shared.stream.receiver_count()
+    }
+
+    /// Answers the next request with `status` instead of handling it. Calls queue up, one failure per request.
+    pub fn fail_next(&self, status: StatusCode) {
+        self.shared.state().failures.push_back(status);
+    }
+
+    /// Delays every response by `latency`.
+    pub fn set_latency(&self, latency: Duration) {
+        self.shared.state().latency = latency;
+    }
+
+    /// Lets the next request for an application key succeed, as pressing the bridge's link button does.
+    pub fn press_link_button(&self) {
+        self.shared.state().link_button_pressed = true;
+    }
+
+    /// Drops every event stream mid-response, as happens when the bridge restarts or the network hiccups.
+    pub fn disconnect_event_streams(&self) {
+        let _ = self.shared.stream.send(StreamMessage::Disconnect);
+    }
+
+    /// Applies updates to the state and reports them on the event stream as a single message, e.g. to simulate a
+    /// button press or motion. Every update needs the `id` and `type` of the resource it updates.
+    pub fn emit(&self, updates: Vec<Value>) {
+        let mut state = self.shared.state();
+        for update in &updates {
+            if let Some(resource) = update["id"].as_str().and_then(|id| state.find_mut(id)) {
+                merge(resource, update);
+            }
+        }
+        self.shared.send_message(&mut state, "update", updates);
+    }
+
+    /// Adds resources and reports them on the event stream as a single message, as when a light is paired.
+    pub fn add(&self, resources: Vec<Value>) {
+        let mut state = self.shared.state();
+        state.resources.extend(resources.iter().cloned());
+        self.shared.send_message(&mut state, "add", resources);
+    }
+}
+
+impl Drop for FakeBridge {
+    fn drop(&mut self) {
+        self.server.abort();
+        self.shared.stopped.store(true, Ordering::Relaxed);
+        self.disconnect_event_streams();
+    }
+}
+
+impl Shared {
+    fn state(&self) -> std::sync::MutexGuard<'_, BridgeState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn send_message(&self, state: &mut BridgeState, message_type: &str, data: Vec<Value>) {
+        state.messages_sent += 1;
+        let message = json!([{
+            "creationtime": Utc::now().to_rfc3339(),
+            "data": data,
+            "id": format!("00000000-0000-4000-8000-{:012x}", state.messages_sent),
+            "type": message_type,
+        }]);
+        let event = format!("id: {}:0\ndata: {}\n\n", Utc::now().timestamp(), message);
+        let _ = self.stream.send(StreamMessage::Event(event));
+    }
+}
+
+impl BridgeState {
+    fn find(&self, id: &str) -> Option<&Value> {
+        self.resources.iter().find(|r| r["id"] == id)
+    }
+
+    fn find_mut(&mut self, id: &str) -> Option<&mut Value> {
+        self.resources.iter_mut().find(|r| r["id"] == id)
+    }
+
+    fn take_dropped_datagram(&mut self) -> bool {
+        let drop = self.datagrams_to_drop > 0;
+        self.datagrams_to_drop = self.datagrams_to_drop.saturating_sub(1);
+        drop
+    }
+}
+
+async fn handle(request: Request<Body>, shared: Arc<Shared>) -> Result<Response<Body>, Infallible> {
+    let latency = shared.state().latency;
+    if !latency.is_zero() {
+        sleep(latency).await;
+    }
+
+    // Creating an application key is the only request that needs none
+    if request.method() == Method::POST && request.uri().path() == "/api" {
+        return Ok(create_application_key(&shared));
+    }
+
+    let authorized = request
+        .headers()
+        .get("hue-application-key")
+        .is_some_and(|key| key.as_bytes() == shared.application_key.as_bytes());
+    if !authorized {
+        return Ok(error(StatusCode::FORBIDDEN, "unauthorized user"));
+    }
+    let failure = shared.state().failures.pop_front();
+    if let Some(status) = failure {
+        return Ok(error(status, status.canonical_reason().unwrap_or("error")));
+    }
+
+    let path = request.uri().path().to_string();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let response = match (request.method(), segments.as_slice()) {
+        (&Method::GET, ["clip", "v2", "resource"]) => data(shared.state().resources.to_vec()),
+        (&Method::GET, ["clip", "v2", "resource", resource_type]) => data(
+            shared
+                .state()
+                .resources
+                .iter()
+                .filter(|r| r["type"] == *resource_type)
+                .cloned()
+                .collect(),
+        ),
+        (&Method::GET, ["clip", "v2", "resource", resource_type, id]) => {
+            match shared.state().find(id) {
+                Some(resource) if resource["type"] == *resource_type => {
+                    data(vec![resource.clone()])
+                }
+                _ => error(StatusCode::NOT_FOUND, "Not Found"),
+            }
+        }
+        (&Method::PUT, ["clip", "v2", "resource", resource_type, id]) => {
+            let (resource_type, id) = (resource_type.to_string(), id.to_string());
+            let body = hyper::body::to_bytes(request.into_body())
+                .await
+                .ok()
+                .and_then(|body| serde_json::from_slice(&body).ok());
+            match body {
+                Some(body) => update(&shared, &resource_type, &id, body),
+                None => error(StatusCode::BAD_REQUEST, "invalid body"),
+            }
+        }
+        (&Method::GET, ["eventstream", "clip", "v2"]) => event_stream(&shared),
+        _ => error(StatusCode::NOT_FOUND, "Not Found"),
+    };
+    Ok(response)
+}
+
+fn update(shared: &Shared, resource_type: &str, id: &str, mut body: Value) -> Response<Body> {
+    strip_actions(&mut body);
+    let mut state = shared.state();
+    let Some(resource) = state.find_mut(id).filter(|r| r["type"] == resource_type) else {
+        return error(StatusCode::NOT_FOUND, "Not Found");
+    };
+    merge(resource, &body);
+
+    // Updates on the event stream carry the changed fields along with the resource's id, type and owner
+    let mut update = body;
+    if let Some(update) = update.as_object_mut() {
+        update.insert("id".to_string(), json!(id));
+        update.insert("type".to_string(), json!(resource_type));
+        if let Some(owner) = resource.get("owner") {
+            update.insert("owner".to_string(), owner.clone());
+        }
+    }
+    shared.send_message(&mut state, "update", vec![update]);
+
+    data(vec![json!({ "rid": id, "rtype": resource_type })])
+}
+
+fn create_application_key(shared: &Shared) -> Response<Body> {
+    let mut state = shared.state();
+    let body = if state.link_button_pressed {
+        state.link_button_pressed = false;
+        json!([{ "success": {
+            "username": shared.application_key,
+            "clientkey": shared.client_key,
+        }}])
+    } else {
+        json!([{ "error": { "type": 101, "address": "", "description": "link button not pressed" } }])
+    };
+    json_response(StatusCode::OK, body)
+}
+
+/// Entertainment streams authenticate with the application key as identity and the client key as pre-shared key.
+fn stream_context(shared: &Shared) -> Result<SslContext, FakeBridgeError> {
+    let application_key = shared.application_key.clone();
+    let client_key = decode_client_key(&shared.client_key)?;
+    let mut context = SslContext::builder(SslMethod::dtls())?;
+    context.set_cipher_list(CIPHER)?;
+    context.set_psk_server_callback(move |_, identity, psk| {
+        if identity != Some(application_key.as_bytes()) || client_key.len() > psk.len() {
+            return Ok(0);
+        }
+        psk[..client_key.len()].copy_from_slice(&client_key);
+        Ok(client_key.len())
+    });
+    Ok(context.build())
+}
+
+/// Accepts one entertainment stream at a time, until the bridge is dropped.
+fn serve_streams(socket: UdpSocket, context: SslContext, shared: Arc<Shared>) {
+    if socket.set_read_timeout(Some(STREAM_POLL_INTERVAL)).is_err() {
+        return;
+    }
+    while !shared.stopped.load(Ordering::Relaxed) {
+        // The first datagram of a handshake tells who to accept the stream from
+        let Ok((_, peer)) = socket.peek_from(&mut [0; 1]) else {
+            continue;
+        };
+        if shared.state().take_dropped_datagram() {
+            let _ = socket.recv_from(&mut [0; 2048]);
+            continue;
+        }
+        let Ok(stream) = socket.connect(peer).and_then(|_| socket.try_clone()) else {
+            continue;
+        };
+        if let Some(stream) = accept_stream(&context, UdpStream(stream), &shared) {
+            receive_frames(stream, &shared);
+        }
+    }
+}
+
+fn accept_stream(
+    context: &SslContext,
+    stream: UdpStream,
+    shared: &Shared,
+) -> Option<SslStream<UdpStream>> {
+    let mut handshake = Ssl::new(context).ok()?.accept(stream);
+    loop {
+        match handshake {
+            Ok(stream) => return Some(stream),
+            Err(HandshakeError::WouldBlock(stream)) if !shared.stopped.load(Ordering::Relaxed) => {
+                handshake = stream.handshake();
+            }
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Records the frames for started areas until the stream is closed or idle for too long.
+fn receive_frames(mut stream: SslStream<UdpStream>, shared: &Shared) {
+    let mut buffer = [0; 1024];
+    let mut last_frame = Instant::now();
+    while !shared.stopped.load(Ordering::Relaxed) && last_frame.elapsed() < STREAM_IDLE_TIMEOUT {
+        match stream.ssl_read(&mut buffer) {
+            Ok(length) => {
+                last_frame = Instant::now();
+                let frame = buffer[..length].to_vec();
+                let mut state = shared.state();
+                let started = frame
+                    .get(16..52)
+                    .and_then(|id| std::str::from_utf8(id).ok())
+                    .and_then(|id| state.find(id))
+                    .is_some_and(|area| area["status"] == "active");
+                if started {
+                    state.frames.push(frame);
+                }
+            }
+            Err(e) if e.code() == ErrorCode::WANT_READ => {}
+            Err(_) => return,
+        }
+    }
+}
+
+fn event_stream(shared: &Shared) -> Response<Body> {
+    let mut messages = shared.stream.subscribe();
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        if sender.send_data(": hi\n\n".into()).await.is_err() {
+            return;
+        }
+        loop {
+            match messages.recv().await {
+                Ok(StreamMessage::Event(event)) => {
+                    if sender.send_data(event.into()).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(StreamMessage::Disconnect) | Err(broadcast::error::RecvError::Closed) => {
+                    sender.abort();
+                    return;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+            }
+        }
+    });
+
+    Response::builder()
+        .header("content-type", "text/event-stream")
+        .body(body)
+        .expect("a valid response")
+}
+
+fn data(data: Vec<Value>) -> Response<Body> {
+    json_response(StatusCode::OK, json!({ "errors": [], "data": data }))
+}
+
+fn error(status: StatusCode, description: &str) -> Response<Body> {
+    json_response(
+        status,
+        json!({ "errors": [{ "description": description }], "data": [] }),
+    )
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(body.to_string().into())
+        .expect("a valid response")
+}
+
+/// Removes the parts of a PUT body that trigger something on the bridge rather than describe the resource's state,
+/// an update to install is replaced by the state it puts the resource in and a started effect by its status.
+fn strip_actions(body: &mut Value) {
+    let Some(body) = body.as_object_mut() else {
+        return;
+    };
+    body.remove("recall");
+    body.remove("alert");
+    for effects in ["effects", "timed_effects"] {
+        if let Some(Value::Object(effects)) = body.get_mut(effects) {
+            if let Some(effect) = effects.remove("effect") {
+                effects.insert("status".to_string(), effect);
+            }
+        }
+    }
+    match body.remove("action").as_ref().and_then(Value::as_str) {
+        Some("start") => {
+            body.insert("status".to_string(), json!("active"));
+        }
+        Some("stop") => {
+            body.insert("status".to_string(), json!("inactive"));
+        }
+        _ => {}
+    }
+    if body.remove("install") == Some(json!(true)) {
+        body.insert("state".to_string(), json!("installing"));
+    }
+    if let Some(Value::Object(dynamics)) = body.get_mut("dynamics") {
+        dynamics.remove("duration");
+        if dynamics.is_empty() {
+            body.remove("dynamics");
+        }
+    }
+}
+
+fn merge(target: &mut Value, patch: &Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+fn self_signed_identity() -> Result<Identity, FakeBridgeError> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_text("CN", "localhost")?;
+    let name = name.build();
+    let serial_number = BigNum::from_u32(1)?.to_asn1_integer()?;
+    let not_before = Asn1Time::days_from_now(0)?;
+    let not_after = Asn1Time::days_from_now(365)?;
+
+    let mut certificate = X509::builder()?;
+    certificate.set_version(2)?;
+    certificate.set_serial_number(&serial_number)?;
+    certificate.set_subject_name(&name)?;
+    certificate.set_issuer_name(&name)?;
+    certificate.set_pubkey(&key)?;
+    certificate.set_not_before(&not_before)?;
+    certificate.set_not_after(&not_after)?;
+    certificate.sign(&key, MessageDigest::sha256())?;
+    let certificate = certificate.build();
+
+    Ok(Identity::from_pkcs8(
+        &certificate.to_pem()?,
+        &key.private_key_to_pem_pkcs8()?,
+    )?)
+}
+
+#[derive(Error, Debug)]
+pub enum FakeBridgeError {
+    #[error("the fixture has no data array")]
+    InvalidFixture,
+    #[error(transparent)]
+    FixtureError(#[from] serde_json::Error),
+    #[error(transparent)]
+    EntertainmentError(#[from] HueEntertainmentError),
+    #[error(transparent)]
+    CertificateError(#[from] ErrorStack),
+    #[error(transparent)]
+    TlsError(#[from] native_tls::Error),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+
+    use tokio::sync::mpsc;
+    use tokio::time::{timeout, Instant};
+
+    use super::*;
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::hue::devices_response::ResourceType;
+    use crate::hue::{HueClient, HueClientError, HueController, HueObserver};
+    use crate::model::PropertyValue;
+
+    const LIGHT_ID: &str = "4e5ad66f-633e-4300-84cd-634129fdb451";
+    const DEVICE_ID: &str = "90bdce60-3704-470e-be4c-8264f2bc8151";
+    const SOFTWARE_UPDATE_ID: &str = "64ac92d6-41b3-4f81-bd6d-315f01dc59c3";
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    async fn bridge() -> FakeBridge {
+        let fixture = fs::read_to_string("tests/resources/devices_response_light.json")
+            .expect("the fixture is readable");
+        FakeBridge::start(&fixture)
+            .await
+            .expect("the bridge starts")
+    }
+
+    fn client(bridge: &FakeBridge) -> HueClient {
+        HueClient::with_endpoint(bridge.endpoint(), bridge.application_key())
+            .expect("a valid client")
+    }
+
+    fn turn_off() -> Command {
+        Command::ControlDevice {
+            device_id: DEVICE_ID.to_string(),
+            properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(false))]),
+            transition: Some(Duration::from_millis(400)),
+        }
+    }
+
+    async fn wait_for_event_stream(bridge: &FakeBridge) {
+        timeout(TIMEOUT, async {
+            while bridge.event_streams() == 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("a client follows the event stream");
+    }
+
+    #[tokio::test]
+    async fn discovers_devices_over_https() {
+        let bridge = bridge().await;
+
+        let events = HueObserver::new(client(&bridge))
+            .discover()
+            .await
+            .expect("discovery succeeds");
+
+        match &events[0] {
+            Event::DiscoveredDevices(devices) => {
+                assert_eq!(1, devices.len());
+                assert_eq!(DEVICE_ID, devices[0].id());
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_resources_by_type_and_id() {
+        let bridge = bridge().await;
+        let client = client(&bridge);
+
+        let lights: Value = serde_json::from_str(
+            &client
+                .fetch_resources_of_type(ResourceType::Light)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let device: Value = serde_json::from_str(
+            &client
+                .fetch_resource(ResourceType::Device, DEVICE_ID)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(1, lights["data"].as_array().unwrap().len());
+        assert_eq!(LIGHT_ID, lights["data"][0]["id"]);
+        assert_eq!(DEVICE_ID, device["data"][0]["id"]);
+        assert!(matches!(
+            client.fetch_resource(ResourceType::Light, DEVICE_ID).await,
+            Err(HueClientError::NotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn discovers_devices_added_later() {
+        let bridge = bridge().await;
+        let observer = HueObserver::new(client(&bridge));
+        let (sender, mut receiver) = mpsc::channel(100);
+        tokio::spawn(async move { observer.observe(sender).await });
+        for _ in 0..3 {
+            timeout(TIMEOUT, receiver.recv()).await.unwrap().unwrap();
+        }
+        wait_for_event_stream(&bridge).await;
+
+        let device_id = "0d3ae8b4-6a0c-4bfb-8cab-3ef1c7a3f4a2";
+        let light_id = "b1d3f0a4-1f6e-4e83-9d68-0c2f1e5a7b90";
+        let mut device = bridge.resource(DEVICE_ID).unwrap();
+        device["id"] = json!(device_id);
+        device["services"] = json!([{ "rid": light_id, "rtype": "light" }]);
+        let mut light = bridge.resource(LIGHT_ID).unwrap();
+        light["id"] = json!(light_id);
+        light["owner"]["rid"] = json!(device_id);
+        bridge.add(vec![device, light]);
+
+        match timeout(TIMEOUT, receiver.recv()).await.unwrap() {
+            Some(Event::DiscoveredDevices(devices)) => {
+                assert_eq!(1, devices.len());
+                assert_eq!(device_id, devices[0].id());
+                assert!(devices[0].properties().contains_key("on"));
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_an_unknown_device_once_even_if_that_fails() {
+        let bridge = bridge().await;
+        let observer = HueObserver::new(client(&bridge));
+        let (sender, mut receiver) = mpsc::channel(100);
+        tokio::spawn(async move { observer.observe(sender).await });
+        for _ in 0..3 {
+            timeout(TIMEOUT, receiver.recv()).await.unwrap().unwrap();
+        }
+        wait_for_event_stream(&bridge).await;
+        bridge.fail_next(StatusCode::NOT_FOUND);
+        bridge.fail_next(StatusCode::NOT_FOUND);
+
+        let press = json!({
+            "id": "8f1b4a52-2c1d-4a8e-9a57-3e2b1f0c6d71",
+            "type": "button",
+            "owner": { "rid": "0d3ae8b4-6a0c-4bfb-8cab-3ef1c7a3f4a2", "rtype": "device" },
+            "button": { "last_event": "short_release" },
+        });
+        bridge.emit(vec![press.clone()]);
+        bridge.emit(vec![press]);
+        bridge.emit(vec![json!({
+            "id": LIGHT_ID,
+            "type": "light",
+            "owner": { "rid": DEVICE_ID, "rtype": "device" },
+            "on": { "on": false },
+        })]);
+        // The light's update is handled after both presses
+        timeout(TIMEOUT, receiver.recv()).await.unwrap().unwrap();
+
+        assert!(matches!(
+            client(&bridge)
+                .fetch_resource(ResourceType::Device, DEVICE_ID)
+                .await,
+            Err(HueClientError::NotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_without_the_application_key() {
+        let bridge = bridge().await;
+        let client = HueClient::with_endpoint(bridge.endpoint(), "wrong").unwrap();
+
+        let result = HueObserver::new(client).discover().await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn controls_a_light_and_reports_it_on_the_event_stream() {
+        let bridge = bridge().await;
+        let observer = HueObserver::new(client(&bridge));
+        let mut controller = HueController::new(client(&bridge));
+        let (sender, mut receiver) = mpsc::channel(100);
+        tokio::spawn(async move { observer.observe(sender).await });
+        for _ in 0..3 {
+            let event = timeout(TIMEOUT, receiver.recv()).await.unwrap().unwrap();
+            controller.handle_event(&event);
+        }
+        wait_for_event_stream(&bridge).await;
+
+        controller.execute(&turn_off()).await.unwrap();
+
+        assert_eq!(false, bridge.resource(LIGHT_ID).unwrap()["on"]["on"]);
+        assert!(bridge.resource(LIGHT_ID).unwrap()["dynamics"]
+            .get("duration")
+            .is_none());
+        match timeout(TIMEOUT, receiver.recv()).await.unwrap() {
+            Some(Event::PropertiesChanged {
+                device_id,
+                properties,
+            }) => {
+                assert_eq!(DEVICE_ID, device_id);
+                assert_eq!(Some(&PropertyValue::Boolean(false)), properties.get("on"));
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn installs_a_ready_update() {
+        let bridge = bridge().await;
+        let observer = HueObserver::new(client(&bridge));
+        let mut controller = HueController::new(client(&bridge));
+        for event in observer.discover().await.unwrap() {
+            controller.handle_event(&event);
+        }
+
+        controller
+            .execute(&Command::InstallUpdate {
+                device_id: DEVICE_ID.to_string(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            "installing",
+            bridge.resource(SOFTWARE_UPDATE_ID).unwrap()["state"]
+        );
+    }
+
+    #[tokio::test]
+    async fn injects_failures() {
+        let bridge = bridge().await;
+        let observer = HueObserver::new(client(&bridge));
+        let mut controller = HueController::new(client(&bridge));
+        for event in observer.discover().await.unwrap() {
+            controller.handle_event(&event);
+        }
+
+        bridge.fail_next(StatusCode::SERVICE_UNAVAILABLE);
+
+        assert!(controller.execute(&turn_off()).await.is_err());
+        assert!(controller.execute(&turn_off()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_a_wrong_application_key() {
+        let bridge = bridge().await;
+        let client = HueClient::with_endpoint(bridge.endpoint(), "wrong").unwrap();
+
+        let error = client.fetch_resources().await.unwrap_err();
+
+        assert!(matches!(error, HueClientError::Unauthorized(_)));
+        assert_eq!(
+            "the bridge rejected the application key, pair again to get a new one: unauthorized user",
+            error.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_reads_while_the_bridge_is_busy() {
+        let bridge = bridge().await;
+        bridge.fail_next(StatusCode::SERVICE_UNAVAILABLE);
+        bridge.fail_next(StatusCode::TOO_MANY_REQUESTS);
+
+        assert!(client(&bridge).fetch_resources().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reports_unexpected_statuses_with_the_bridges_description() {
+        let bridge = bridge().await;
+        bridge.fail_next(StatusCode::BAD_REQUEST);
+
+        let error = client(&bridge).fetch_resources().await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            HueClientError::UnexpectedStatus { status: 400, .. }
+        ));
+        assert_eq!(
+            "the bridge responded with status 400: Bad Request",
+            error.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_responses() {
+        let bridge = bridge().await;
+        bridge.set_latency(Duration::from_millis(500));
+        let client = client(&bridge)
+            .with_timeout(Duration::from_millis(100))
+            .with_retries(0);
+
+        let result = client.fetch_resources().await;
+
+        assert!(matches!(result, Err(HueClientError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn injects_latency() {
+        let bridge = bridge().await;
+        bridge.set_latency(Duration::from_millis(200));
+
+        let start = Instant::now();
+        HueObserver::new(client(&bridge)).discover().await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn drops_event_streams() {
+        let bridge = bridge().await;
+        let mut response = client(&bridge).event_stream().await.unwrap();
+        wait_for_event_stream(&bridge).await;
+
+        bridge.disconnect_event_streams();
+
+        let ended = timeout(TIMEOUT, async {
+            while let Ok(Some(_)) = response.chunk().await {}
+        })
+        .await;
+        assert!(ended.is_ok());
+    }
+
+    #[tokio::test]
+    async fn emits_updates() {
+        let bridge = bridge().await;
+        let mut response = client(&bridge).event_stream().await.unwrap();
+        wait_for_event_stream(&bridge).await;
+
+        bridge.emit(vec![json!({
+            "id": LIGHT_ID,
+            "type": "light",
+            "owner": { "rid": DEVICE_ID, "rtype": "device" },
+            "dimming": { "brightness": 50.0 },
+        })]);
+
+        assert_eq!(
+            50.0,
+            bridge.resource(LIGHT_ID).unwrap()["dimming"]["brightness"]
+        );
+        let mut received = String::new();
+        while !received.contains("\"brightness\":50.0") {
+            let chunk = timeout(TIMEOUT, response.chunk()).await.unwrap().unwrap();
+            received.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use serde::Serialize;
+
+// Request bodies for `PUT /clip/v2/resource/light/{id}`, absent fields are left untouched by the bridge.
+
+#[derive(Serialize, Default, PartialEq, Debug)]
+pub(crate) struct LightPut {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on: Option<OnPut>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimming: Option<DimingPut>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color_temperature: Option<ColorTemperaturePut>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<ColorPut>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dynamics: Option<DynamicsPut>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    effects: Option<EffectsPut>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timed_effects: Option<TimedEffectsPut>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alert: Option<AlertPut>,
+}
+
+impl LightPut {
+    pub fn on(mut self, on: bool) -> Self {
+        self.on = Some(OnPut { on });
+        self
+    }
+
+    pub fn brightness(mut self, brightness: f64) -> Self {
+        self.dimming = Some(DimingPut { brightness });
+        self
+    }
+
+    pub fn mirek(mut self, mirek: usize) -> Self {
+        self.color_temperature = Some(ColorTemperaturePut { mirek });
+        self
+    }
+
+    pub fn xy(mut self, x: f64, y: f64) -> Self {
+        self.color = Some(ColorPut { xy: XyPut { x, y } });
+        self
+    }
+
+    /// The duration of the transition, or of the timed effect if one is started.
+    pub fn duration(mut self, duration: u128) -> Self {
+        match &mut self.timed_effects {
+            Some(timed_effects) => timed_effects.duration = Some(duration),
+            None => {
+                self.dynamics
+                    .get_or_insert_with(DynamicsPut::default)
+                    .duration = Some(duration)
+            }
+        }
+        self
+    }
+
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.dynamics.get_or_insert_with(DynamicsPut::default).speed = Some(speed);
+        self
+    }
+
+    pub fn effect(mut self, effect: String) -> Self {
+        self.effects = Some(EffectsPut { effect });
+        self
+    }
+
+    pub fn timed_effect(mut self, effect: String) -> Self {
+        self.timed_effects = Some(TimedEffectsPut {
+            effect,
+            duration: None,
+        });
+        self
+    }
+
+    pub fn alert(mut self, action: String) -> Self {
+        self.alert = Some(AlertPut { action });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == LightPut::default()
+    }
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct OnPut {
+    on: bool,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct DimingPut {
+    brightness: f64, // >= 0 && <= 100
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct ColorTemperaturePut {
+    mirek: usize, // >= 153 && <= 500
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct ColorPut {
+    xy: XyPut,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct XyPut {
+    x: f64, // >= 0.0 && <= 1.0
+    y: f64, // >= 0.0 && <= 1.0
+}
+
+#[derive(Serialize, Default, PartialEq, Debug)]
+pub(crate) struct DynamicsPut {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<u128>, // In milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed: Option<f64>, // >= 0.0 && <= 1.0
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct EffectsPut {
+    effect: String, // One of the light's effect_values
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct TimedEffectsPut {
+    effect: String, // sunrise, sunset or no_effect
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<u128>, // In milliseconds
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct AlertPut {
+    action: String, // breathe
+}
@@ -1,8 +1,40 @@
 mod client;
+mod command_queue;
+mod controller;
+#[allow(dead_code)] // Mirrors the bridge's API, not every field is in use yet
 mod devices_response;
+mod entertainment;
+mod entertainment_request;
+mod event_stream;
+mod fake_bridge;
+mod light_request;
 mod observer;
+mod pairing;
+mod recording;
+mod scene_request;
+mod software_update_request;
 
 pub use client::HueClient;
 pub use client::HueClientError;
+pub use command_queue::HueCommandQueue;
+pub use controller::HueController;
+pub use controller::HueControllerError;
+pub use entertainment::EntertainmentArea;
+pub use entertainment::EntertainmentChannel;
+pub use entertainment::EntertainmentStream;
+pub use entertainment::Frame;
+pub use entertainment::FrameProducer;
+pub use entertainment::HueEntertainment;
+pub use entertainment::HueEntertainmentError;
+pub use fake_bridge::FakeBridge;
+pub use fake_bridge::FakeBridgeError;
 pub use observer::HueObserver;
 pub use observer::HueObserverError;
+pub use pairing::discover_bridges;
+pub use pairing::pair;
+pub use pairing::ApplicationKey;
+pub use pairing::DiscoveredBridge;
+pub use recording::Recorder;
+pub use recording::Recording;
+pub use recording::RecordingError;
+pub use recording::ReplayClock;
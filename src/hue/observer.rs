@@ -1,41 +1,390 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use thiserror::Error;
+use tokio::sync::mpsc::Sender;
+use tokio::time::sleep;
+use tracing::{error, info, instrument, warn};
 
 use crate::event::Event;
-use crate::hue::client::{HueClient, HueClientError};
+use crate::hue::client::{describe, HueClient, HueClientError};
 use crate::hue::devices_response::{
-    DeviceGet, HueError, LightGet, Resource, ResourceIdentifierGet, ResourceType,
+    BatteryState, ButtonEvent as HueButtonEvent, ButtonGet, Color as HueColor, DeviceGet,
+    DevicesResponse, GamutType as HueGamutType, HueError, LightGet, LightLevel, LightLevelGet,
+    Motion, MotionGet, PowerState, Resource, ResourceIdentifierGet, ResourceType, RoomGet,
+    SceneGet, SoftwareUpdateState, Temperature, TemperatureGet, Xy as HueXy, ZigbeeStatus,
 };
-use crate::model::{BooleanProperty, Device, DeviceType, Property, PropertyType};
+use crate::hue::event_stream::{
+    ButtonUpdate, EventStreamMessage, EventStreamMessageType, EventStreamParser, LightUpdate,
+    ResourceUpdate,
+};
+use crate::hue::recording::{Recorder, Traffic};
+use crate::model::{
+    Availability, BooleanProperty, ButtonEvent, ColorProperty, Device, DeviceType, EnumProperty,
+    Firmware, Gamut, GamutType, NumberProperty, Property, PropertyType, PropertyValue, Room, Scene,
+    Unit, UpdateState, Xy,
+};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// The resources devices are mapped from. Only these are fetched again when the event stream reconnects, to catch up
+/// on what was missed, the event stream does not update rooms and scenes either.
+const DEVICE_RESOURCES: [ResourceType; 10] = [
+    ResourceType::Device,
+    ResourceType::Light,
+    ResourceType::Button,
+    ResourceType::Motion,
+    ResourceType::LightLevel,
+    ResourceType::Temperature,
+    ResourceType::ZigbeeConnectivity,
+    ResourceType::ZigbeeBridgeConnectivity,
+    ResourceType::DevicePower,
+    ResourceType::DeviceSoftwareUpdate,
+];
+
+const ZIGBEE_STATUSES: [&str; 4] = [
+    "connected",
+    "disconnected",
+    "connectivity_issue",
+    "unidirectional_incoming",
+];
+const BATTERY_STATES: [&str; 3] = ["normal", "low", "critical"];
 
 pub struct HueObserver {
     client: HueClient,
+    recorder: Option<Recorder>,
+}
+
+/// Button updates on the event stream only carry the id of the button resource, the rest is known from discovery.
+pub(in crate::hue) struct ButtonService {
+    control_id: u8,
+    repeat_interval: Duration,
+}
+
+/// The resources discovered so far, to map the event stream's messages and notice the devices they reference that
+/// were not discovered, e.g. lights added since.
+#[derive(Default)]
+pub(in crate::hue) struct KnownResources {
+    devices: HashSet<String>,
+    buttons: HashMap<String, ButtonService>,
+    /// The devices that were fetched because the event stream referenced them, whether that succeeded or not.
+    fetched: HashSet<String>,
+}
+
+/// The envelope of every response of `/clip/v2/resource`, to combine several responses into one.
+#[derive(Serialize, Deserialize, Default)]
+struct RawResponse {
+    errors: Vec<Value>,
+    data: Vec<Value>,
 }
 
 impl HueObserver {
     pub fn new(client: HueClient) -> HueObserver {
-        HueObserver { client }
+        HueObserver {
+            client,
+            recorder: None,
+        }
+    }
+
+    /// Records every response and event stream message received from the bridge, so it can be replayed later.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Sends the discovered devices and rooms, then keeps following the bridge's event stream until the receiver is
+    /// dropped. The event stream is reconnected whenever it is interrupted.
+    #[instrument(name = "integration", fields(name = "hue"), skip_all)]
+    pub async fn observe(&self, sender: Sender<Event>) -> Result<(), HueObserverError> {
+        let response = self.fetch_devices().await?;
+        let mut known = KnownResources::default();
+        known.extend(&response);
+        info!(resources = response.data().len(), "discovered resources");
+        for event in map_discovered(&response)? {
+            send(&sender, event).await?;
+        }
+
+        let mut resync = false;
+        loop {
+            match self.follow_event_stream(&sender, &mut known, resync).await {
+                Err(HueObserverError::ChannelClosed) => {
+                    return Err(HueObserverError::ChannelClosed)
+                }
+                Err(e) => warn!(error = %e, "event stream interrupted"),
+                Ok(()) => warn!("event stream closed by the bridge"),
+            }
+            sleep(RECONNECT_DELAY).await;
+            if let Some(metrics) = self.client.metrics() {
+                metrics.event_stream_reconnected();
+            }
+            resync = true;
+        }
     }
 
-    pub async fn observe(&self) -> Result<Event, HueObserverError> {
-        let response = self.client.fetch_devices().await?;
-        if response.errors().len() > 0 {
-            return Err(HueObserverError::FetchDevicesResponse(
-                response.take_errors(),
-            ));
+    pub async fn discover(&self) -> Result<Vec<Event>, HueObserverError> {
+        map_discovered(&self.fetch_devices().await?)
+    }
+
+    async fn fetch_devices(&self) -> Result<DevicesResponse, HueObserverError> {
+        let body = self.client.fetch_resources().await?;
+        self.record(Traffic::Resources(body.clone()));
+        parse_response(&body)
+    }
+
+    /// Every device and the services they are mapped from, without the rest of the bridge's resources.
+    async fn fetch_device_resources(&self) -> Result<DevicesResponse, HueObserverError> {
+        let mut bodies = vec![];
+        for resource_type in DEVICE_RESOURCES {
+            bodies.push(self.client.fetch_resources_of_type(resource_type).await?);
         }
+        self.combine(bodies, Traffic::ResyncedResources)
+    }
 
-        let resource_map = response.devices_map();
-        let devices = response
+    /// A single device and the services it is mapped from.
+    async fn fetch_device(&self, device_id: &str) -> Result<DevicesResponse, HueObserverError> {
+        let body = self
+            .client
+            .fetch_resource(ResourceType::Device, device_id)
+            .await?;
+        let services: Vec<(ResourceType, String)> = parse_response(&body)?
             .devices()
             .iter()
-            .try_fold(vec![], |devices: Vec<Device>, device| {
-                fold_device(devices, device, &resource_map)
-            })?;
+            .flat_map(|device| device.services())
+            .filter(|service| DEVICE_RESOURCES.contains(&service.rtype()))
+            .map(|service| (service.rtype(), service.rid().to_string()))
+            .collect();
+        let mut bodies = vec![body];
+        for (resource_type, id) in services {
+            bodies.push(self.client.fetch_resource(resource_type, &id).await?);
+        }
+        self.combine(bodies, Traffic::DeviceResources)
+    }
+
+    /// Combines the responses of several requests as if their resources came with one, to map and record them.
+    fn combine(
+        &self,
+        bodies: Vec<String>,
+        traffic: fn(String) -> Traffic,
+    ) -> Result<DevicesResponse, HueObserverError> {
+        let mut combined = RawResponse::default();
+        for body in bodies {
+            let response: RawResponse =
+                serde_json::from_str(&body).map_err(HueObserverError::InvalidResources)?;
+            combined.errors.extend(response.errors);
+            combined.data.extend(response.data);
+        }
+        let body = serde_json::to_string(&combined).map_err(HueObserverError::InvalidResources)?;
+        self.record(traffic(body.clone()));
+        parse_response(&body)
+    }
+
+    async fn follow_event_stream(
+        &self,
+        sender: &Sender<Event>,
+        known: &mut KnownResources,
+        resync: bool,
+    ) -> Result<(), HueObserverError> {
+        let mut response = self.client.event_stream().await?;
+        let mut parser = EventStreamParser::default();
+        self.record(Traffic::EventStreamOpened);
+        info!("following the event stream");
+
+        // Anything may have changed while the event stream was interrupted
+        if resync {
+            match self.fetch_device_resources().await {
+                Ok(resources) => {
+                    info!(resources = resources.data().len(), "resynced devices");
+                    for event in map_resynced_devices(&resources, known)? {
+                        send(sender, event).await?;
+                    }
+                }
+                Err(e) => warn!(error = %e, "resyncing devices failed"),
+            }
+        }
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(HueClientError::RequestError)?
+        {
+            for data in parser.push_data(&chunk) {
+                self.record(Traffic::EventStream(data.clone()));
+                for message in EventStreamParser::parse(&data)? {
+                    for device_id in known.unknown_devices(&message) {
+                        self.discover_device(sender, known, &device_id).await?;
+                    }
+                    for event in map_event_stream_message(&message, &known.buttons) {
+                        send(sender, event).await?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
 
-        Ok(Event::DiscoveredDevices(devices))
+    /// Fetches a device the event stream referenced before it was discovered. It is not fetched again if that fails,
+    /// until the next resync.
+    async fn discover_device(
+        &self,
+        sender: &Sender<Event>,
+        known: &mut KnownResources,
+        device_id: &str,
+    ) -> Result<(), HueObserverError> {
+        known.fetched.insert(device_id.to_string());
+        info!(
+            device_id,
+            "discovering a device referenced by the event stream"
+        );
+        let resources = match self.fetch_device(device_id).await {
+            Ok(resources) => resources,
+            Err(e) => {
+                warn!(device_id, error = %e, "discovering the device failed");
+                return Ok(());
+            }
+        };
+        known.extend(&resources);
+        send(sender, map_discovered_devices(&resources)?).await
+    }
+
+    fn record(&self, traffic: Traffic) {
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.record(traffic) {
+                error!(error = %e, "recording bridge traffic failed");
+            }
+        }
+    }
+}
+
+impl KnownResources {
+    pub(in crate::hue) fn buttons(&self) -> &HashMap<String, ButtonService> {
+        &self.buttons
+    }
+
+    pub(in crate::hue) fn extend(&mut self, response: &DevicesResponse) {
+        self.devices.extend(
+            response
+                .devices()
+                .iter()
+                .map(|device| device.id().to_string()),
+        );
+        self.buttons.extend(map_button_services(response));
+    }
+
+    /// The devices the message's services belong to that were not discovered, including the owners of buttons that
+    /// were not. Devices that were fetched for an earlier message are left out.
+    fn unknown_devices(&self, message: &EventStreamMessage) -> Vec<String> {
+        if !matches!(
+            message.message_type(),
+            EventStreamMessageType::Add | EventStreamMessageType::Update
+        ) {
+            return vec![];
+        }
+        let mut unknown = vec![];
+        for update in message.data() {
+            let Some(owner) = update.owner() else {
+                continue;
+            };
+            let unknown_button = matches!(update, ResourceUpdate::Button(button) if !self.buttons.contains_key(button.id()));
+            if owner.rtype() == ResourceType::Device
+                && (!self.devices.contains(owner.rid()) || unknown_button)
+                && !self.fetched.contains(owner.rid())
+                && !unknown.iter().any(|id| id == owner.rid())
+            {
+                unknown.push(owner.rid().to_string());
+            }
+        }
+        unknown
+    }
+}
+
+async fn send(sender: &Sender<Event>, event: Event) -> Result<(), HueObserverError> {
+    sender
+        .send(event)
+        .await
+        .map_err(|_| HueObserverError::ChannelClosed)
+}
+
+fn parse_response(body: &str) -> Result<DevicesResponse, HueObserverError> {
+    let response: DevicesResponse =
+        serde_json::from_str(body).map_err(HueObserverError::InvalidResources)?;
+    if !response.errors().is_empty() {
+        return Err(HueObserverError::FetchDevicesResponse(
+            response.take_errors(),
+        ));
     }
+    Ok(response)
+}
+
+pub(in crate::hue) fn map_discovered(
+    response: &DevicesResponse,
+) -> Result<Vec<Event>, HueObserverError> {
+    let rooms = response.rooms().into_iter().map(map_room).collect();
+    let scenes = response.scenes().into_iter().map(map_scene).collect();
+
+    Ok(vec![
+        map_discovered_devices(response)?,
+        Event::DiscoveredRooms(rooms),
+        Event::DiscoveredScenes(scenes),
+    ])
+}
+
+/// The devices of a response with every device and its services, followed by the removal of the known devices that
+/// are missing from it. What is known is replaced by the response.
+pub(in crate::hue) fn map_resynced_devices(
+    response: &DevicesResponse,
+    known: &mut KnownResources,
+) -> Result<Vec<Event>, HueObserverError> {
+    let previous = std::mem::take(known);
+    known.extend(response);
+    let mut removed: Vec<String> = previous
+        .devices
+        .into_iter()
+        .filter(|id| !known.devices.contains(id))
+        .collect();
+    removed.sort();
+
+    let mut events = vec![map_discovered_devices(response)?];
+    if !removed.is_empty() {
+        events.push(Event::DevicesRemoved(removed));
+    }
+    Ok(events)
+}
+
+/// Only the devices, for responses without rooms and scenes that must not replace the ones discovered before.
+pub(in crate::hue) fn map_discovered_devices(
+    response: &DevicesResponse,
+) -> Result<Event, HueObserverError> {
+    let resource_map = response.devices_map();
+    let devices = response
+        .devices()
+        .iter()
+        .try_fold(vec![], |devices: Vec<Device>, device| {
+            fold_device(devices, device, &resource_map)
+        })?;
+    Ok(Event::DiscoveredDevices(devices))
+}
+
+pub(in crate::hue) fn map_button_services(
+    response: &DevicesResponse,
+) -> HashMap<String, ButtonService> {
+    response
+        .data()
+        .into_iter()
+        .filter_map(|resource| match resource {
+            Resource::Button(button) => Some((
+                button.id().to_string(),
+                ButtonService {
+                    control_id: button.metadata().control_id(),
+                    repeat_interval: Duration::from_millis(
+                        button.button().repeat_interval() as u64
+                    ),
+                },
+            )),
+            _ => None,
+        })
+        .collect()
 }
 
 fn fold_device(
@@ -43,21 +392,126 @@ fn fold_device(
     device: &&DeviceGet,
     resource_map: &HashMap<String, &Resource>,
 ) -> Result<Vec<Device>, HueObserverError> {
-    let properties: HashMap<String, Property> = device
+    let mut properties: HashMap<String, Property> = device
         .services()
         .iter()
         .try_fold(HashMap::new(), |properties, service| {
             fold_services(properties, service, resource_map)
         })?;
 
-    if properties.len() > 0 {
-        let device = map_device(device, properties, None);
+    if !properties.is_empty() {
+        properties.extend(map_status_services(device, resource_map));
+        let availability = device
+            .services()
+            .iter()
+            .filter(|service| {
+                matches!(
+                    service.rtype(),
+                    ResourceType::ZigbeeConnectivity | ResourceType::ZigbeeBridgeConnectivity
+                )
+            })
+            .find_map(|service| match resource_map.get(service.rid()) {
+                Some(Resource::ZigbeeConnectivity(connectivity))
+                | Some(Resource::ZigbeeBridgeConnectivity(connectivity)) => {
+                    Some(map_zigbee_status(connectivity.status()))
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+        let firmware = map_firmware(device, resource_map);
+        let device = map_device(device, properties, None)
+            .with_availability(availability)
+            .with_firmware(firmware);
         devices.push(device);
     }
 
     Ok(devices)
 }
 
+/// The version comes with the device, whether there is an update with its `device_software_update` service, which
+/// not every device has.
+fn map_firmware(device: &DeviceGet, resource_map: &HashMap<String, &Resource>) -> Firmware {
+    let version = device.product_data().software_version().to_string();
+    let update = device
+        .services()
+        .iter()
+        .filter(|service| service.rtype() == ResourceType::DeviceSoftwareUpdate)
+        .find_map(|service| match resource_map.get(service.rid()) {
+            Some(Resource::DeviceSoftwareUpdate(update)) => Some(update),
+            _ => None,
+        });
+    match update {
+        Some(update) => Firmware::new(
+            version,
+            map_update_state(update.state()),
+            Some(update.id().to_string()),
+        ),
+        None => Firmware::new(version, UpdateState::NoUpdate, None),
+    }
+}
+
+/// The connectivity and battery of a device. Every device has them, including the bridge, so they are only added to
+/// devices that have other properties.
+fn map_status_services(
+    device: &DeviceGet,
+    resource_map: &HashMap<String, &Resource>,
+) -> HashMap<String, Property> {
+    let mut properties = HashMap::new();
+    for service in device.services() {
+        match resource_map.get(service.rid()) {
+            Some(Resource::ZigbeeConnectivity(connectivity))
+            | Some(Resource::ZigbeeBridgeConnectivity(connectivity)) => {
+                let connectivity_property = Property::Enum(EnumProperty::new(
+                    "connectivity".to_string(),
+                    true,
+                    PropertyType::Connectivity,
+                    Some(connectivity.id().to_string()),
+                    zigbee_status_name(connectivity.status()).to_string(),
+                    ZIGBEE_STATUSES.map(String::from).to_vec(),
+                ));
+                properties.insert("connectivity".to_string(), connectivity_property);
+            }
+            Some(Resource::DevicePower(power)) => {
+                properties.extend(map_power(power.id(), power.power_state()));
+            }
+            _ => {}
+        }
+    }
+    properties
+}
+
+fn map_power(id: &str, power_state: &PowerState) -> HashMap<String, Property> {
+    let mut properties = HashMap::new();
+    if let Some(battery_level) = power_state.battery_level() {
+        let battery_level_property = Property::Number(
+            NumberProperty::new(
+                "battery_level".to_string(),
+                true,
+                PropertyType::BatteryLevel,
+                Some(id.to_string()),
+                Unit::Percentage,
+                Some(battery_level as f64),
+                Some(0.0),
+                Some(100.0),
+            )
+            .with_step(1.0),
+        );
+        properties.insert("battery_level".to_string(), battery_level_property);
+    }
+    if let Some(battery_state) = power_state.battery_state() {
+        let battery_state_property = Property::Enum(EnumProperty::new(
+            "battery_state".to_string(),
+            true,
+            PropertyType::BatteryState,
+            Some(id.to_string()),
+            battery_state_name(battery_state).to_string(),
+            BATTERY_STATES.map(String::from).to_vec(),
+        ));
+        properties.insert("battery_state".to_string(), battery_state_property);
+    }
+    properties
+}
+
 fn fold_services(
     mut properties: HashMap<String, Property>,
     service: &&ResourceIdentifierGet,
@@ -65,12 +519,40 @@ fn fold_services(
 ) -> Result<HashMap<String, Property>, HueObserverError> {
     match service.rtype() {
         ResourceType::Light => {
-            if let Resource::Light(light) = resource_map[service.rid()] {
+            if let Some(Resource::Light(light)) = resource_map.get(service.rid()) {
                 properties.extend(&mut map_lights(light).drain());
             } else {
                 return Err(HueObserverError::InvalidData);
             }
         }
+        ResourceType::Button => {
+            if let Some(Resource::Button(button)) = resource_map.get(service.rid()) {
+                properties.extend(&mut map_buttons(button).drain());
+            } else {
+                return Err(HueObserverError::InvalidData);
+            }
+        }
+        ResourceType::Motion => {
+            if let Some(Resource::Motion(motion)) = resource_map.get(service.rid()) {
+                properties.extend(&mut map_motion(motion).drain());
+            } else {
+                return Err(HueObserverError::InvalidData);
+            }
+        }
+        ResourceType::LightLevel => {
+            if let Some(Resource::LightLevel(light_level)) = resource_map.get(service.rid()) {
+                properties.extend(&mut map_light_level(light_level).drain());
+            } else {
+                return Err(HueObserverError::InvalidData);
+            }
+        }
+        ResourceType::Temperature => {
+            if let Some(Resource::Temperature(temperature)) = resource_map.get(service.rid()) {
+                properties.extend(&mut map_temperature(temperature).drain());
+            } else {
+                return Err(HueObserverError::InvalidData);
+            }
+        }
         _ => {}
     }
 
@@ -82,9 +564,19 @@ fn map_device(
     properties: HashMap<String, Property>,
     external_id: Option<String>,
 ) -> Device {
+    let device_type = if properties.contains_key("on") {
+        DeviceType::Light
+    } else if properties
+        .values()
+        .any(|p| *p.property_type() == PropertyType::Button)
+    {
+        DeviceType::Switch
+    } else {
+        DeviceType::Sensor
+    };
     Device::new(
         device.id().to_string(),
-        DeviceType::Light,
+        device_type,
         device.product_data().manufacturer_name().to_string(),
         device.product_data().model_id().to_string(),
         device.product_data().product_name().to_string(),
@@ -100,21 +592,521 @@ fn map_lights(light: &LightGet) -> HashMap<String, Property> {
         "on".to_string(),
         false,
         PropertyType::On,
-        None,
+        Some(light.id().to_string()),
         light.on(),
     ));
     properties.insert("on".to_string(), on_property);
+
+    if let Some(dimming) = light.dimming() {
+        let brightness_property = Property::Number(NumberProperty::new(
+            "brightness".to_string(),
+            false,
+            PropertyType::Brightness,
+            Some(light.id().to_string()),
+            Unit::Percentage,
+            Some(dimming.brightness()),
+            Some(dimming.min_dim_level().unwrap_or(0.0)),
+            Some(100.0),
+        ));
+        properties.insert("brightness".to_string(), brightness_property);
+    }
+
+    if let Some(color_temperature) = light.color_temperature() {
+        let color_temperature_property = Property::Number(
+            NumberProperty::new(
+                "color_temperature".to_string(),
+                false,
+                PropertyType::ColorTemperature,
+                Some(light.id().to_string()),
+                Unit::Mirek,
+                color_temperature.mirek().map(|mirek| *mirek as f64),
+                Some(color_temperature.mirek_schema().mirek_minimum() as f64),
+                Some(color_temperature.mirek_schema().mirek_maximum() as f64),
+            )
+            .with_step(1.0),
+        );
+        properties.insert("color_temperature".to_string(), color_temperature_property);
+    }
+
+    if let Some(color) = light.color() {
+        properties.insert("color".to_string(), map_color(light.id(), color));
+    }
+
+    if let Some(dynamics) = light.dynamics() {
+        let dynamics_property = Property::Enum(EnumProperty::new(
+            "dynamics".to_string(),
+            true,
+            PropertyType::Dynamics,
+            Some(light.id().to_string()),
+            dynamics.status().to_string(),
+            dynamics.status_values().clone(),
+        ));
+        properties.insert("dynamics".to_string(), dynamics_property);
+
+        // Only lights that can play a dynamic palette have a speed to set
+        if dynamics
+            .status_values()
+            .iter()
+            .any(|status| status != "none")
+        {
+            let speed_property = Property::Number(
+                NumberProperty::new(
+                    "dynamics_speed".to_string(),
+                    false,
+                    PropertyType::DynamicsSpeed,
+                    Some(light.id().to_string()),
+                    Unit::Percentage,
+                    Some(speed_percentage(dynamics.speed())).filter(|_| dynamics.speed_valid()),
+                    Some(0.0),
+                    Some(100.0),
+                )
+                .with_step(1.0),
+            );
+            properties.insert("dynamics_speed".to_string(), speed_property);
+        }
+    }
+
+    if let Some(effects) = light.effects() {
+        let effect_property = Property::Enum(EnumProperty::new(
+            "effect".to_string(),
+            false,
+            PropertyType::Effect,
+            Some(light.id().to_string()),
+            effects.status().to_string(),
+            effects.effect_values().clone(),
+        ));
+        properties.insert("effect".to_string(), effect_property);
+    }
+
+    if let Some(timed_effects) = light.timed_effects() {
+        let timed_effect_property = Property::Enum(EnumProperty::new(
+            "timed_effect".to_string(),
+            false,
+            PropertyType::TimedEffect,
+            Some(light.id().to_string()),
+            timed_effects.status().to_string(),
+            timed_effects.effect_values().clone(),
+        ));
+        properties.insert("timed_effect".to_string(), timed_effect_property);
+    }
+
     properties
 }
 
+/// The bridge reports the speed of dynamics from 0 to 1, as a percentage rounded to whole percents.
+fn speed_percentage(speed: f32) -> f64 {
+    (f64::from(speed) * 100.0).round()
+}
+
+fn map_color(light_id: &str, color: &HueColor) -> Property {
+    let gamut = color.gamut().map(|gamut| {
+        Gamut::new(
+            map_xy(gamut.red()),
+            map_xy(gamut.green()),
+            map_xy(gamut.blue()),
+        )
+    });
+    let gamut_type = match color.gamut_type() {
+        HueGamutType::A => GamutType::A,
+        HueGamutType::B => GamutType::B,
+        HueGamutType::C => GamutType::C,
+        HueGamutType::Other => GamutType::Other,
+    };
+    Property::Color(
+        ColorProperty::new(
+            "color".to_string(),
+            false,
+            PropertyType::Color,
+            Some(light_id.to_string()),
+            map_xy(color.xy()),
+        )
+        .with_gamut(gamut, gamut_type),
+    )
+}
+
+fn map_xy(xy: &HueXy) -> Xy {
+    Xy::new(xy.x(), xy.y())
+}
+
+fn map_buttons(button: &ButtonGet) -> HashMap<String, Property> {
+    let name = button_property_name(button.metadata().control_id());
+    let held = button
+        .button()
+        .button_report()
+        .is_some_and(|report| is_held(report.event()));
+    let button_property = Property::Boolean(BooleanProperty::new(
+        name.clone(),
+        true,
+        PropertyType::Button,
+        Some(button.id().to_string()),
+        held,
+    ));
+    HashMap::from([(name, button_property)])
+}
+
+fn map_motion(motion: &MotionGet) -> HashMap<String, Property> {
+    let motion_property = Property::Boolean(BooleanProperty::new(
+        "motion".to_string(),
+        true,
+        PropertyType::Motion,
+        Some(motion.id().to_string()),
+        valid_motion(motion.motion()),
+    ));
+    HashMap::from([("motion".to_string(), motion_property)])
+}
+
+fn map_light_level(light_level: &LightLevelGet) -> HashMap<String, Property> {
+    let light_level_property = Property::Number(
+        NumberProperty::new(
+            "light_level".to_string(),
+            true,
+            PropertyType::LightLevel,
+            Some(light_level.id().to_string()),
+            Unit::LightLevel,
+            valid_light_level(light_level.light()),
+            None,
+            None,
+        )
+        .with_step(1.0),
+    );
+    HashMap::from([("light_level".to_string(), light_level_property)])
+}
+
+fn valid_light_level(light: &LightLevel) -> Option<f64> {
+    Some(light.light_level() as f64).filter(|_| light.light_level_valid())
+}
+
+fn map_temperature(temperature: &TemperatureGet) -> HashMap<String, Property> {
+    let temperature_property = Property::Number(NumberProperty::new(
+        "temperature".to_string(),
+        true,
+        PropertyType::Temperature,
+        Some(temperature.id().to_string()),
+        Unit::Celcius,
+        valid_temperature(temperature.temperature()),
+        None,
+        None,
+    ));
+    HashMap::from([("temperature".to_string(), temperature_property)])
+}
+
+fn valid_temperature(temperature: &Temperature) -> Option<f64> {
+    Some(temperature.temperature()).filter(|_| temperature.temperature_valid())
+}
+
+fn valid_motion(motion: &Motion) -> bool {
+    motion.motion() && motion.motion_valid()
+}
+
+fn map_zigbee_status(status: ZigbeeStatus) -> Availability {
+    match status {
+        ZigbeeStatus::Connected => Availability::Available,
+        ZigbeeStatus::ConnectivityIssue | ZigbeeStatus::UnidirectionalIncoming => {
+            Availability::Degraded
+        }
+        ZigbeeStatus::Disconnected => Availability::Unavailable,
+    }
+}
+
+fn zigbee_status_name(status: ZigbeeStatus) -> &'static str {
+    match status {
+        ZigbeeStatus::Connected => "connected",
+        ZigbeeStatus::Disconnected => "disconnected",
+        ZigbeeStatus::ConnectivityIssue => "connectivity_issue",
+        ZigbeeStatus::UnidirectionalIncoming => "unidirectional_incoming",
+    }
+}
+
+fn battery_state_name(state: BatteryState) -> &'static str {
+    match state {
+        BatteryState::Normal => "normal",
+        BatteryState::Low => "low",
+        BatteryState::Critical => "critical",
+    }
+}
+
+fn map_update_state(state: SoftwareUpdateState) -> UpdateState {
+    match state {
+        SoftwareUpdateState::NoUpdate => UpdateState::NoUpdate,
+        SoftwareUpdateState::UpdatePending => UpdateState::UpdatePending,
+        SoftwareUpdateState::ReadyToInstall => UpdateState::ReadyToInstall,
+        SoftwareUpdateState::Installing => UpdateState::Installing,
+    }
+}
+
+fn button_property_name(control_id: u8) -> String {
+    format!("button_{}", control_id)
+}
+
+fn is_held(event: HueButtonEvent) -> bool {
+    matches!(
+        event,
+        HueButtonEvent::InitialPress | HueButtonEvent::Repeat | HueButtonEvent::LongPress
+    )
+}
+
+fn map_button_event(event: HueButtonEvent) -> ButtonEvent {
+    match event {
+        HueButtonEvent::InitialPress => ButtonEvent::InitialPress,
+        HueButtonEvent::Repeat => ButtonEvent::Repeat,
+        HueButtonEvent::ShortRelease => ButtonEvent::ShortRelease,
+        HueButtonEvent::LongRelease => ButtonEvent::LongRelease,
+        HueButtonEvent::DoubleShortRelease => ButtonEvent::DoubleShortRelease,
+        HueButtonEvent::LongPress => ButtonEvent::LongPress,
+    }
+}
+
+fn map_room(room: &RoomGet) -> Room {
+    let mapped = Room::new(
+        room.id().to_string(),
+        room.metadata().name().to_string(),
+        room.children()
+            .iter()
+            .filter(|child| child.rtype() == ResourceType::Device)
+            .map(|child| child.rid().to_string())
+            .collect(),
+    );
+    match room
+        .services()
+        .iter()
+        .find(|service| service.rtype() == ResourceType::GroupedLight)
+    {
+        Some(grouped_light) => mapped.with_external_id(grouped_light.rid().to_string()),
+        None => mapped,
+    }
+}
+
+fn map_scene(scene: &SceneGet) -> Scene {
+    let room_id = Some(scene.group())
+        .filter(|group| group.rtype() == ResourceType::Room)
+        .map(|group| group.rid().to_string());
+    Scene::new(
+        scene.id().to_string(),
+        scene.metadata().name().to_string(),
+        room_id,
+    )
+}
+
+pub(in crate::hue) fn map_event_stream_message(
+    message: &EventStreamMessage,
+    buttons: &HashMap<String, ButtonService>,
+) -> Vec<Event> {
+    if message.message_type() != EventStreamMessageType::Update {
+        return vec![];
+    }
+
+    message
+        .data()
+        .iter()
+        .flat_map(|update| match update {
+            ResourceUpdate::Light(light) => map_light_update(light).into_iter().collect(),
+            ResourceUpdate::Button(button) => map_button_update(button, buttons),
+            ResourceUpdate::Motion(motion) => motion
+                .motion()
+                .map(|m| {
+                    property_changed(
+                        motion.owner(),
+                        "motion",
+                        PropertyValue::Boolean(valid_motion(m)),
+                    )
+                })
+                .into_iter()
+                .collect(),
+            ResourceUpdate::LightLevel(light_level) => light_level
+                .light()
+                .map(|l| {
+                    property_changed(
+                        light_level.owner(),
+                        "light_level",
+                        PropertyValue::Number(valid_light_level(l)),
+                    )
+                })
+                .into_iter()
+                .collect(),
+            ResourceUpdate::Temperature(temperature) => temperature
+                .temperature()
+                .map(|t| {
+                    property_changed(
+                        temperature.owner(),
+                        "temperature",
+                        PropertyValue::Number(valid_temperature(t)),
+                    )
+                })
+                .into_iter()
+                .collect(),
+            ResourceUpdate::ZigbeeConnectivity(connectivity)
+            | ResourceUpdate::ZigbeeBridgeConnectivity(connectivity) => connectivity
+                .status()
+                .map(|status| {
+                    vec![
+                        Event::AvailabilityChanged {
+                            device_id: connectivity.owner().rid().to_string(),
+                            availability: map_zigbee_status(status),
+                        },
+                        property_changed(
+                            connectivity.owner(),
+                            "connectivity",
+                            PropertyValue::Text(zigbee_status_name(status).to_string()),
+                        ),
+                    ]
+                })
+                .unwrap_or_default(),
+            ResourceUpdate::DevicePower(power) => power
+                .power_state()
+                .and_then(|power_state| map_power_update(power.owner(), power_state))
+                .into_iter()
+                .collect(),
+            ResourceUpdate::DeviceSoftwareUpdate(update) => update
+                .state()
+                .map(|state| Event::UpdateStateChanged {
+                    device_id: update.owner().rid().to_string(),
+                    update: map_update_state(state),
+                })
+                .into_iter()
+                .collect(),
+            ResourceUpdate::Unknown => vec![],
+        })
+        .collect()
+}
+
+fn map_power_update(owner: &ResourceIdentifierGet, power_state: &PowerState) -> Option<Event> {
+    let mut properties = HashMap::new();
+    if let Some(battery_level) = power_state.battery_level() {
+        properties.insert(
+            "battery_level".to_string(),
+            PropertyValue::Number(Some(battery_level as f64)),
+        );
+    }
+    if let Some(battery_state) = power_state.battery_state() {
+        properties.insert(
+            "battery_state".to_string(),
+            PropertyValue::Text(battery_state_name(battery_state).to_string()),
+        );
+    }
+    if properties.is_empty() {
+        return None;
+    }
+
+    Some(Event::PropertiesChanged {
+        device_id: owner.rid().to_string(),
+        properties,
+    })
+}
+
+fn property_changed(owner: &ResourceIdentifierGet, name: &str, value: PropertyValue) -> Event {
+    Event::PropertiesChanged {
+        device_id: owner.rid().to_string(),
+        properties: HashMap::from([(name.to_string(), value)]),
+    }
+}
+
+fn map_button_update(
+    button: &ButtonUpdate,
+    buttons: &HashMap<String, ButtonService>,
+) -> Vec<Event> {
+    let (Some(service), Some(event)) = (buttons.get(button.id()), button.event()) else {
+        return vec![];
+    };
+
+    let device_id = button.owner().rid().to_string();
+    vec![
+        Event::PropertiesChanged {
+            device_id: device_id.clone(),
+            properties: HashMap::from([(
+                button_property_name(service.control_id),
+                PropertyValue::Boolean(is_held(event)),
+            )]),
+        },
+        Event::ButtonPressed {
+            device_id,
+            control_id: service.control_id,
+            event: map_button_event(event),
+            repeat_interval: service.repeat_interval,
+        },
+    ]
+}
+
+fn map_light_update(light: &LightUpdate) -> Option<Event> {
+    let mut properties = HashMap::new();
+    if let Some(on) = light.on() {
+        properties.insert("on".to_string(), PropertyValue::Boolean(on));
+    }
+    if let Some(dimming) = light.dimming() {
+        properties.insert(
+            "brightness".to_string(),
+            PropertyValue::Number(Some(dimming.brightness())),
+        );
+    }
+    if let Some(color_temperature) = light.color_temperature() {
+        let mirek = color_temperature
+            .mirek()
+            .filter(|_| color_temperature.mirek_valid())
+            .map(|mirek| mirek as f64);
+        properties.insert(
+            "color_temperature".to_string(),
+            PropertyValue::Number(mirek),
+        );
+    }
+    if let Some(color) = light.color() {
+        properties.insert(
+            "color".to_string(),
+            PropertyValue::Color(map_xy(color.xy())),
+        );
+    }
+    if let Some(dynamics) = light.dynamics() {
+        if let Some(status) = dynamics.status() {
+            properties.insert(
+                "dynamics".to_string(),
+                PropertyValue::Text(status.to_string()),
+            );
+        }
+        if let Some(speed) = dynamics.speed() {
+            properties.insert(
+                "dynamics_speed".to_string(),
+                PropertyValue::Number(Some(speed_percentage(speed))),
+            );
+        }
+    }
+    if let Some(status) = light.effects().and_then(|effects| effects.status()) {
+        properties.insert(
+            "effect".to_string(),
+            PropertyValue::Text(status.to_string()),
+        );
+    }
+    if let Some(status) = light
+        .timed_effects()
+        .and_then(|timed_effects| timed_effects.status())
+    {
+        properties.insert(
+            "timed_effect".to_string(),
+            PropertyValue::Text(status.to_string()),
+        );
+    }
+
+    if properties.is_empty() {
+        return None;
+    }
+
+    Some(Event::PropertiesChanged {
+        device_id: light.owner().rid().to_string(),
+        properties,
+    })
+}
+
 #[derive(Error, Debug)]
 pub enum HueObserverError {
     #[error(transparent)]
     ClientError(#[from] HueClientError),
-    #[error("fetch devices response contains errors")]
+    #[error("fetch devices response contains errors{}", describe(.0))]
     FetchDevicesResponse(Vec<HueError>),
+    #[error("invalid resources received from the bridge")]
+    InvalidResources(serde_json::Error),
     #[error("invalid data received from the bridge, a service is not pointing to a valid device")]
     InvalidData,
+    #[error("invalid event stream data received from the bridge")]
+    InvalidEventStreamData(#[from] serde_json::Error),
+    #[error("event receiver has been dropped")]
+    ChannelClosed,
 }
 
 #[cfg(test)]
@@ -137,21 +1129,62 @@ mod tests {
         if let Resource::Device(device) = response.data()[0] {
             let devices = fold_device(vec![], &device, &response.devices_map())?;
             assert_eq!(1, devices.len());
-            assert_eq!(1, devices[0].properties().len());
+            assert_eq!(7, devices[0].properties().len());
 
             if let Property::Boolean(on_property) = &devices[0].properties()["on"] {
                 assert_eq!("on", on_property.name());
-                assert_eq!(false, on_property.readonly());
+                assert!(!on_property.readonly());
                 assert_eq!(PropertyType::On, *on_property.property_type());
-                assert_eq!(None, on_property.external_id());
-                assert_eq!(true, on_property.value());
+                assert_eq!(
+                    Some(&"4e5ad66f-633e-4300-84cd-634129fdb451".to_string()),
+                    on_property.external_id()
+                );
+                assert!(on_property.value());
             } else {
-                assert!(false, r#"property["on"] is not a Resource::Device"#);
+                panic!(r#"property["on"] is not a Resource::Device"#);
             }
             let property = &devices[0].properties()["on"];
-            assert!(matches!(property, Property::Boolean(_)))
+            assert!(matches!(property, Property::Boolean(_)));
+
+            if let Property::Number(color_temperature) =
+                &devices[0].properties()["color_temperature"]
+            {
+                assert_eq!(Unit::Mirek, *color_temperature.unit());
+                assert_eq!(None, color_temperature.value());
+                assert_eq!(Some(153.0), color_temperature.minimum());
+                assert_eq!(Some(500.0), color_temperature.maximum());
+            } else {
+                panic!(r#"property["color_temperature"] is not a Property::Number"#);
+            }
+
+            if let Property::Color(color) = &devices[0].properties()["color"] {
+                assert_eq!(Xy::new(0.669, 0.3251), color.value());
+                assert_eq!(Some(GamutType::B), color.gamut_type());
+                assert_eq!(Some(Xy::new(0.675, 0.322)), color.gamut().map(|g| g.red()));
+            } else {
+                panic!(r#"property["color"] is not a Property::Color"#);
+            }
+
+            if let Property::Enum(effect) = &devices[0].properties()["effect"] {
+                assert!(!effect.readonly());
+                assert_eq!("candle", effect.value());
+                assert_eq!(&vec!["no_effect", "candle"], effect.values());
+            } else {
+                panic!(r#"property["effect"] is not a Property::Enum"#);
+            }
+            assert!(!devices[0].properties().contains_key("dynamics_speed"));
+
+            let firmware = devices[0]
+                .firmware()
+                .expect("the light reports its firmware");
+            assert_eq!("1.104.2", firmware.version());
+            assert_eq!(UpdateState::ReadyToInstall, firmware.update());
+            assert_eq!(
+                Some(&"64ac92d6-41b3-4f81-bd6d-315f01dc59c3".to_string()),
+                firmware.external_id()
+            );
         } else {
-            assert!(false, "data[0] is not a Resource::Device");
+            panic!("data[0] is not a Resource::Device");
         }
 
         Ok(())
@@ -166,9 +1199,264 @@ mod tests {
             let devices = fold_device(vec![], &device, &response.devices_map())?;
             assert_eq!(0, devices.len());
         } else {
-            assert!(false, "data[0] is not a Resource::Device");
+            panic!("data[0] is not a Resource::Device");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn maps_a_light_update_to_changed_properties() -> Result<(), Box<dyn Error>> {
+        let message = r#"data: [{"data":[{"id":"4e5ad66f-633e-4300-84cd-634129fdb451","dimming":{"brightness":41.5},"color_temperature":{"mirek":366,"mirek_valid":true},"color":{"xy":{"x":0.4599,"y":0.4106}},"owner":{"rid":"90bdce60-3704-470e-be4c-8264f2bc8151","rtype":"device"},"type":"light"}],"type":"update"}]
+
+"#;
+        let messages = EventStreamParser::default().push(message.as_bytes())?;
+
+        let events = map_event_stream_message(&messages[0], &HashMap::new());
+
+        assert_eq!(1, events.len());
+        if let Event::PropertiesChanged {
+            device_id,
+            properties,
+        } = &events[0]
+        {
+            assert_eq!("90bdce60-3704-470e-be4c-8264f2bc8151", device_id);
+            assert_eq!(3, properties.len());
+            assert_eq!(PropertyValue::Number(Some(41.5)), properties["brightness"]);
+            assert_eq!(
+                PropertyValue::Number(Some(366.0)),
+                properties["color_temperature"]
+            );
+            assert_eq!(
+                PropertyValue::Color(Xy::new(0.4599, 0.4106)),
+                properties["color"]
+            );
+        } else {
+            panic!("events[0] is not an Event::PropertiesChanged");
         }
 
         Ok(())
     }
+
+    #[test]
+    fn maps_effect_and_dynamics_updates_to_changed_properties() -> Result<(), Box<dyn Error>> {
+        let message = r#"data: [{"data":[{"id":"4e5ad66f-633e-4300-84cd-634129fdb451","dynamics":{"status":"dynamic_palette","speed":0.7,"speed_valid":true},"effects":{"status":"fire"},"timed_effects":{"status":"sunrise"},"owner":{"rid":"90bdce60-3704-470e-be4c-8264f2bc8151","rtype":"device"},"type":"light"}],"type":"update"}]
+
+"#;
+        let messages = EventStreamParser::default().push(message.as_bytes())?;
+
+        let events = map_event_stream_message(&messages[0], &HashMap::new());
+
+        if let Event::PropertiesChanged { properties, .. } = &events[0] {
+            assert_eq!(
+                PropertyValue::Text("dynamic_palette".to_string()),
+                properties["dynamics"]
+            );
+            assert_eq!(
+                PropertyValue::Number(Some(70.0)),
+                properties["dynamics_speed"]
+            );
+            assert_eq!(
+                PropertyValue::Text("fire".to_string()),
+                properties["effect"]
+            );
+            assert_eq!(
+                PropertyValue::Text("sunrise".to_string()),
+                properties["timed_effect"]
+            );
+        } else {
+            panic!("events[0] is not an Event::PropertiesChanged");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn maps_a_room_with_its_grouped_light() -> Result<(), Box<dyn Error>> {
+        let response = fs::read_to_string("tests/resources/devices_response_room.json")?;
+        let response = from_str::<DevicesResponse>(&response)?;
+
+        let room = map_room(response.rooms()[0]);
+
+        assert_eq!("Living room", room.name());
+        assert_eq!(
+            Some(&"b7c1d2e3-f4a5-4b6c-8d7e-9f0a1b2c3d4e".to_string()),
+            room.external_id()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn maps_a_button_update_to_a_button_press() -> Result<(), Box<dyn Error>> {
+        let response = fs::read_to_string("tests/resources/devices_response_button.json")?;
+        let response = from_str::<DevicesResponse>(&response)?;
+        let message = r#"data: [{"data":[{"button":{"last_event":"repeat"},"id":"9ea998a8-c996-4a8b-a652-cb7baa9d26e5","owner":{"rid":"e84075f8-023f-43e7-80ea-c0246fdf2835","rtype":"device"},"type":"button"}],"type":"update"}]
+
+"#;
+        let messages = EventStreamParser::default().push(message.as_bytes())?;
+
+        let events = map_event_stream_message(&messages[0], &map_button_services(&response));
+
+        assert_eq!(2, events.len());
+        if let Event::ButtonPressed {
+            device_id,
+            control_id,
+            event,
+            repeat_interval,
+        } = &events[1]
+        {
+            assert_eq!("e84075f8-023f-43e7-80ea-c0246fdf2835", device_id);
+            assert_eq!(1, *control_id);
+            assert_eq!(ButtonEvent::Repeat, *event);
+            assert_eq!(Duration::from_millis(800), *repeat_interval);
+        } else {
+            panic!("events[1] is not an Event::ButtonPressed");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn folds_a_switch_with_buttons() -> Result<(), Box<dyn Error>> {
+        let response = fs::read_to_string("tests/resources/devices_response_button.json")?;
+        let response = from_str::<DevicesResponse>(&response)?;
+
+        if let Resource::Device(device) = response.data()[0] {
+            let devices = fold_device(vec![], &device, &response.devices_map())?;
+            assert_eq!(1, devices.len());
+            assert!(matches!(devices[0].device_type(), DeviceType::Switch));
+            assert_eq!(4, devices[0].properties().len());
+            assert_eq!(
+                PropertyValue::Boolean(false),
+                devices[0].properties()["button_1"].value()
+            );
+        } else {
+            panic!("data[0] is not a Resource::Device");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn folds_a_motion_sensor() -> Result<(), Box<dyn Error>> {
+        let response = fs::read_to_string("tests/resources/devices_response_motion.json")?;
+        let response = from_str::<DevicesResponse>(&response)?;
+
+        if let Resource::Device(device) = response.data()[0] {
+            let devices = fold_device(vec![], &device, &response.devices_map())?;
+            assert!(matches!(devices[0].device_type(), DeviceType::Sensor));
+            assert_eq!(
+                PropertyValue::Boolean(true),
+                devices[0].properties()["motion"].value()
+            );
+            assert_eq!(
+                PropertyValue::Number(Some(14320.0)),
+                devices[0].properties()["light_level"].value()
+            );
+            if let Property::Number(temperature) = &devices[0].properties()["temperature"] {
+                assert_eq!(Unit::Celcius, *temperature.unit());
+                assert_eq!(Some(21.37), temperature.value());
+            } else {
+                panic!(r#"property["temperature"] is not a Property::Number"#);
+            }
+            assert_eq!(Availability::Degraded, devices[0].availability());
+            assert_eq!(
+                PropertyValue::Text("connectivity_issue".to_string()),
+                devices[0].properties()["connectivity"].value()
+            );
+            assert_eq!(
+                PropertyValue::Number(Some(12.0)),
+                devices[0].properties()["battery_level"].value()
+            );
+            if let Property::Enum(battery_state) = &devices[0].properties()["battery_state"] {
+                assert_eq!("low", battery_state.value());
+                assert_eq!(
+                    &BATTERY_STATES.map(String::from).to_vec(),
+                    battery_state.values()
+                );
+            } else {
+                panic!(r#"property["battery_state"] is not a Property::Enum"#);
+            }
+        } else {
+            panic!("data[0] is not a Resource::Device");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn maps_a_temperature_update_to_a_changed_property() -> Result<(), Box<dyn Error>> {
+        let message = r#"data: [{"data":[{"id":"ae1f2a3b-4c5d-4e6f-8a7b-8c9d0e1f2a3b","owner":{"rid":"3f6b1c2d-8e9a-4b7c-a5d4-e3f2a1b0c9d8","rtype":"device"},"temperature":{"temperature":19.84,"temperature_valid":true},"type":"temperature"}],"type":"update"}]
+
+"#;
+        let messages = EventStreamParser::default().push(message.as_bytes())?;
+
+        let events = map_event_stream_message(&messages[0], &HashMap::new());
+
+        assert!(matches!(
+            &events[..],
+            [Event::PropertiesChanged { device_id, properties }]
+                if device_id == "3f6b1c2d-8e9a-4b7c-a5d4-e3f2a1b0c9d8"
+                    && properties["temperature"] == PropertyValue::Number(Some(19.84))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn maps_a_connectivity_update_to_an_availability_change() -> Result<(), Box<dyn Error>> {
+        let message = r#"data: [{"data":[{"id":"7a0ece11-0e2d-4bbf-b290-1d575b541533","owner":{"rid":"90bdce60-3704-470e-be4c-8264f2bc8151","rtype":"device"},"status":"disconnected","type":"zigbee_connectivity"}],"type":"update"}]
+
+"#;
+        let messages = EventStreamParser::default().push(message.as_bytes())?;
+
+        let events = map_event_stream_message(&messages[0], &HashMap::new());
+
+        assert!(matches!(
+            &events[..],
+            [Event::AvailabilityChanged { device_id, availability: Availability::Unavailable }, _]
+                if device_id == "90bdce60-3704-470e-be4c-8264f2bc8151"
+        ));
+        assert!(matches!(
+            &events[1],
+            Event::PropertiesChanged { properties, .. }
+                if properties["connectivity"] == PropertyValue::Text("disconnected".to_string())
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn maps_a_power_update_to_changed_battery_properties() -> Result<(), Box<dyn Error>> {
+        let message = r#"data: [{"data":[{"id":"7b8c9d0e-1f2a-4b3c-9d4e-5f6a7b8c9d0e","owner":{"rid":"3f6b1c2d-8e9a-4b7c-a5d4-e3f2a1b0c9d8","rtype":"device"},"power_state":{"battery_level":4,"battery_state":"critical"},"type":"device_power"}],"type":"update"}]
+
+"#;
+        let messages = EventStreamParser::default().push(message.as_bytes())?;
+
+        let events = map_event_stream_message(&messages[0], &HashMap::new());
+
+        assert!(matches!(
+            &events[..],
+            [Event::PropertiesChanged { device_id, properties }]
+                if device_id == "3f6b1c2d-8e9a-4b7c-a5d4-e3f2a1b0c9d8"
+                    && properties["battery_level"] == PropertyValue::Number(Some(4.0))
+                    && properties["battery_state"] == PropertyValue::Text("critical".to_string())
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn maps_a_software_update_to_an_update_state_change() -> Result<(), Box<dyn Error>> {
+        let message = r#"data: [{"data":[{"id":"64ac92d6-41b3-4f81-bd6d-315f01dc59c3","owner":{"rid":"90bdce60-3704-470e-be4c-8264f2bc8151","rtype":"device"},"state":"installing","type":"device_software_update"}],"type":"update"}]
+
+"#;
+        let messages = EventStreamParser::default().push(message.as_bytes())?;
+
+        let events = map_event_stream_message(&messages[0], &HashMap::new());
+
+        assert!(matches!(
+            &events[..],
+            [Event::UpdateStateChanged { device_id, update: UpdateState::Installing }]
+                if device_id == "90bdce60-3704-470e-be4c-8264f2bc8151"
+        ));
+        Ok(())
+    }
 }
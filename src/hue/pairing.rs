@@ -0,0 +1,151 @@
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::hue::client::HueClientError;
+
+const DISCOVERY_ENDPOINT: &str = "https://discovery.meethue.com/";
+
+/// The bridge answers with this error type until its link button has been pressed.
+const LINK_BUTTON_NOT_PRESSED: u16 = 101;
+
+/// A bridge on the local network, as reported by the Philips Hue discovery service.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DiscoveredBridge {
+    id: String,
+    #[serde(rename = "internalipaddress")]
+    address: String,
+    port: Option<u16>,
+}
+
+impl DiscoveredBridge {
+    pub fn id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn address(&self) -> &String {
+        &self.address
+    }
+
+    /// The endpoint to use for `HUE_ENDPOINT`, the port is left out when it is the default.
+    pub fn endpoint(&self) -> String {
+        match self.port {
+            Some(port) if port != 443 => format!("{}:{}", self.address, port),
+            _ => self.address.clone(),
+        }
+    }
+}
+
+/// The keys the bridge creates for an application when its link button has been pressed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApplicationKey {
+    #[serde(rename = "username")]
+    application_key: String,
+    #[serde(rename = "clientkey")]
+    client_key: Option<String>,
+}
+
+impl ApplicationKey {
+    /// The value of the `hue-application-key` header, i.e. `HUE_APP_KEY`.
+    pub fn application_key(&self) -> &String {
+        &self.application_key
+    }
+
+    /// The pre-shared key for entertainment streaming.
+    pub fn client_key(&self) -> Option<&String> {
+        self.client_key.as_ref()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct PairResponse {
+    success: Option<ApplicationKey>,
+    error: Option<PairError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PairError {
+    #[serde(rename = "type")]
+    error_type: u16,
+    description: String,
+}
+
+/// Finds the bridges on the local network through the Philips Hue discovery service.
+pub async fn discover_bridges() -> Result<Vec<DiscoveredBridge>, HueClientError> {
+    Ok(Client::new()
+        .get(DISCOVERY_ENDPOINT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// Asks the bridge at `endpoint` for an application key. The bridge only hands one out shortly after its link button
+/// has been pressed, until then this fails with [`HueClientError::LinkButtonNotPressed`].
+pub async fn pair(endpoint: &str, device_type: &str) -> Result<ApplicationKey, HueClientError> {
+    let responses = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()?
+        .post(format!("https://{}/api", endpoint))
+        .json(&serde_json::json!({ "devicetype": device_type, "generateclientkey": true }))
+        .send()
+        .await?
+        .json::<Vec<PairResponse>>()
+        .await?;
+
+    match responses.into_iter().next() {
+        Some(PairResponse {
+            success: Some(key), ..
+        }) => Ok(key),
+        Some(PairResponse {
+            error: Some(error), ..
+        }) if error.error_type == LINK_BUTTON_NOT_PRESSED => {
+            Err(HueClientError::LinkButtonNotPressed)
+        }
+        Some(PairResponse {
+            error: Some(error), ..
+        }) => Err(HueClientError::PairingFailed(error.description)),
+        None
+        | Some(PairResponse {
+            success: None,
+            error: None,
+        }) => Err(HueClientError::PairingFailed("empty response".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::hue::FakeBridge;
+
+    #[tokio::test]
+    async fn pairs_once_the_link_button_is_pressed() -> Result<(), Box<dyn std::error::Error>> {
+        let fixture = fs::read_to_string("tests/resources/devices_response_light.json")?;
+        let bridge = FakeBridge::start(&fixture).await?;
+
+        assert!(matches!(
+            pair(bridge.endpoint(), "chambrier#test").await,
+            Err(HueClientError::LinkButtonNotPressed)
+        ));
+        bridge.press_link_button();
+        let key = pair(bridge.endpoint(), "chambrier#test").await?;
+
+        assert_eq!(bridge.application_key(), key.application_key());
+        assert!(key.client_key().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_out_the_default_port() -> Result<(), serde_json::Error> {
+        let bridges: Vec<DiscoveredBridge> = serde_json::from_str(
+            r#"[{"id":"001788fffe100491","internalipaddress":"192.168.2.23","port":443},
+                {"id":"001788fffe100492","internalipaddress":"192.168.2.24","port":8443}]"#,
+        )?;
+
+        assert_eq!("192.168.2.23", bridges[0].endpoint());
+        assert_eq!("192.168.2.24:8443", bridges[1].endpoint());
+        Ok(())
+    }
+}
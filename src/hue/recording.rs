@@ -0,0 +1,431 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::mpsc::Sender;
+use tokio::time::{sleep, Instant};
+
+use crate::event::Event;
+use crate::hue::devices_response::DevicesResponse;
+use crate::hue::event_stream::EventStreamParser;
+use crate::hue::observer::{
+    map_discovered, map_discovered_devices, map_event_stream_message, map_resynced_devices,
+    HueObserverError, KnownResources,
+};
+
+/// What was received from the bridge, unparsed, so a recording still replays after the mapping has changed.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "kind", content = "body", rename_all = "snake_case")]
+pub(in crate::hue) enum Traffic {
+    /// The response of `/clip/v2/resource`.
+    Resources(String),
+    /// The responses of the requests for some devices and their services, combined into one.
+    DeviceResources(String),
+    /// The responses of the requests for every device and its services after the event stream reconnected, combined
+    /// into one. The devices that are missing from it were removed in the meantime.
+    ResyncedResources(String),
+    /// The event stream was (re)connected, anything buffered from the previous connection is void.
+    EventStreamOpened,
+    /// The data of a single message of the event stream.
+    EventStream(String),
+    /// A request that changed a resource, with the response of the bridge or why there was none. It is not replayed,
+    /// the changes it caused arrive through the event stream.
+    Update {
+        path: String,
+        request: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        response: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct RecordedTraffic {
+    time: DateTime<Utc>,
+    #[serde(flatten)]
+    traffic: Traffic,
+}
+
+/// Writes bridge traffic as JSON lines, each with the time it was received. Clones write to the same file, so the
+/// observer and the client that sends the commands can share one.
+#[derive(Clone)]
+pub struct Recorder {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Recorder {
+    /// Records to the file at `path`, replacing it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Recorder> {
+        Ok(Recorder::new(File::create(path)?))
+    }
+
+    pub fn new(writer: impl Write + Send + 'static) -> Recorder {
+        Recorder {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+
+    pub(in crate::hue) fn record(&self, traffic: Traffic) -> io::Result<()> {
+        let line = serde_json::to_string(&RecordedTraffic {
+            time: Utc::now(),
+            traffic,
+        })?;
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(writer, "{}", line)?;
+        writer.flush()
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder").finish_non_exhaustive()
+    }
+}
+
+/// Recorded bridge traffic, turned back into the events the observer sent when it was received.
+pub struct Recording {
+    traffic: Vec<RecordedTraffic>,
+}
+
+impl Recording {
+    pub fn load(path: impl AsRef<Path>) -> Result<Recording, RecordingError> {
+        Recording::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(recording: &str) -> Result<Recording, RecordingError> {
+        let traffic = recording
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(Recording { traffic })
+    }
+
+    /// Every event with the time its traffic was received, e.g. to test automations against a real day's events.
+    pub fn events(&self) -> Result<Vec<(DateTime<Utc>, Event)>, RecordingError> {
+        let mut events = vec![];
+        let mut known = KnownResources::default();
+        for recorded in &self.traffic {
+            match &recorded.traffic {
+                Traffic::Resources(body) => {
+                    let response: DevicesResponse = serde_json::from_str(body)?;
+                    known = KnownResources::default();
+                    known.extend(&response);
+                    events.extend(
+                        map_discovered(&response)?
+                            .into_iter()
+                            .map(|event| (recorded.time, event)),
+                    );
+                }
+                Traffic::DeviceResources(body) => {
+                    let response: DevicesResponse = serde_json::from_str(body)?;
+                    known.extend(&response);
+                    events.push((recorded.time, map_discovered_devices(&response)?));
+                }
+                Traffic::ResyncedResources(body) => {
+                    let response: DevicesResponse = serde_json::from_str(body)?;
+                    events.extend(
+                        map_resynced_devices(&response, &mut known)?
+                            .into_iter()
+                            .map(|event| (recorded.time, event)),
+                    );
+                }
+                Traffic::EventStreamOpened | Traffic::Update { .. } => {}
+                Traffic::EventStream(data) => {
+                    for message in EventStreamParser::parse(data)? {
+                        events.extend(
+                            map_event_stream_message(&message, known.buttons())
+                                .into_iter()
+                                .map(|event| (recorded.time, event)),
+                        );
+                    }
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// A clock for replaying at `speed`, that starts at the time of the first recorded traffic.
+    pub fn clock(&self, speed: f64) -> ReplayClock {
+        let start = self.traffic.first().map_or_else(Utc::now, |t| t.time);
+        ReplayClock {
+            speed,
+            time: Arc::new(Mutex::new((start, Instant::now()))),
+        }
+    }
+
+    /// Sends the events keeping the time between them, divided by the speed of the clock: 1 replays in real time, 60
+    /// replays an hour in a minute. Zero or less sends them without delay. The clock is set to the time each event was
+    /// recorded at as it is sent, so automations see the time of the recording rather than the time of the replay.
+    pub async fn replay(
+        &self,
+        sender: Sender<Event>,
+        clock: &ReplayClock,
+    ) -> Result<(), RecordingError> {
+        let speed = clock.speed;
+        let mut previous: Option<DateTime<Utc>> = None;
+        for (time, event) in self.events()? {
+            if let Some(previous) = previous.filter(|_| speed > 0.0) {
+                let delay = (time - previous).to_std().unwrap_or_default();
+                sleep(delay.div_f64(speed)).await;
+            }
+            previous = Some(time);
+            clock.set(time);
+            sender
+                .send(event)
+                .await
+                .map_err(|_| RecordingError::ChannelClosed)?;
+        }
+        Ok(())
+    }
+}
+
+/// The time in a replayed recording: the time of the event sent last, moved on by the real time since then multiplied
+/// by the speed of the replay. Clones share the time.
+#[derive(Clone, Debug)]
+pub struct ReplayClock {
+    speed: f64,
+    time: Arc<Mutex<(DateTime<Utc>, Instant)>>,
+}
+
+impl ReplayClock {
+    pub fn now(&self) -> DateTime<Utc> {
+        let (time, set_at) = *self.time.lock().unwrap_or_else(|e| e.into_inner());
+        if self.speed <= 0.0 {
+            return time;
+        }
+        let elapsed = set_at.elapsed().mul_f64(self.speed);
+        time + chrono::Duration::from_std(elapsed).unwrap_or_else(|_| chrono::Duration::zero())
+    }
+
+    fn set(&self, time: DateTime<Utc>) {
+        *self.time.lock().unwrap_or_else(|e| e.into_inner()) = (time, Instant::now());
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error("invalid recording")]
+    InvalidRecording(#[from] serde_json::Error),
+    #[error(transparent)]
+    ObserverError(#[from] HueObserverError),
+    #[error("event receiver has been dropped")]
+    ChannelClosed,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::time::Duration;
+
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use tokio::time::{timeout, Instant};
+
+    use super::*;
+    use crate::hue::light_request::LightPut;
+    use crate::hue::{FakeBridge, HueClient, HueObserver};
+    use crate::model::PropertyValue;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn recording() -> String {
+        let resources = fs::read_to_string("tests/resources/devices_response_light.json").unwrap();
+        let update = json!([{
+            "creationtime": "2024-03-15T12:00:01Z",
+            "data": [{
+                "id": "4e5ad66f-633e-4300-84cd-634129fdb451",
+                "type": "light",
+                "owner": { "rid": "90bdce60-3704-470e-be4c-8264f2bc8151", "rtype": "device" },
+                "on": { "on": false }
+            }],
+            "id": "00000000-0000-4000-8000-000000000001",
+            "type": "update"
+        }]);
+        [
+            json!({ "time": "2024-03-15T12:00:00Z", "kind": "resources", "body": resources }),
+            json!({ "time": "2024-03-15T12:00:00Z", "kind": "event_stream_opened" }),
+            json!({ "time": "2024-03-15T12:00:01Z", "kind": "event_stream", "body": update.to_string() }),
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+
+    #[test]
+    fn maps_a_recording_to_events() -> Result<(), RecordingError> {
+        let events = Recording::parse(&recording())?.events()?;
+
+        assert_eq!(4, events.len());
+        assert!(matches!(events[0].1, Event::DiscoveredDevices(_)));
+        assert_eq!("2024-03-15T12:00:01+00:00", events[3].0.to_rfc3339());
+        match &events[3].1 {
+            Event::PropertiesChanged { properties, .. } => {
+                assert_eq!(Some(&PropertyValue::Boolean(false)), properties.get("on"))
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn maps_resynced_devices_without_replacing_rooms_and_scenes() -> Result<(), RecordingError> {
+        let resources = fs::read_to_string("tests/resources/devices_response_light.json")?;
+        let line = json!({ "time": "2024-03-15T12:00:00Z", "kind": "device_resources", "body": resources });
+
+        let events = Recording::parse(&line.to_string())?.events()?;
+
+        assert_eq!(1, events.len());
+        assert!(matches!(events[0].1, Event::DiscoveredDevices(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn removes_devices_missing_from_a_resync() -> Result<(), RecordingError> {
+        let resync = json!({
+            "time": "2024-03-15T12:05:00Z",
+            "kind": "resynced_resources",
+            "body": json!({ "errors": [], "data": [] }).to_string(),
+        });
+        let recording = format!("{}\n{}", recording(), resync);
+
+        let events = Recording::parse(&recording)?.events()?;
+
+        assert_eq!(6, events.len());
+        assert!(matches!(&events[4].1, Event::DiscoveredDevices(devices) if devices.is_empty()));
+        match &events[5].1 {
+            Event::DevicesRemoved(device_ids) => assert_eq!(
+                &vec!["90bdce60-3704-470e-be4c-8264f2bc8151".to_string()],
+                device_ids
+            ),
+            event => panic!("Unexpected event {:?}", event),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replays_at_accelerated_speed() -> Result<(), RecordingError> {
+        let recording = Recording::parse(&recording())?;
+        let (sender, mut receiver) = mpsc::channel(100);
+
+        let start = Instant::now();
+        recording.replay(sender, &recording.clock(10.0)).await?;
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_secs(1));
+        let mut count = 0;
+        while receiver.recv().await.is_some() {
+            count += 1;
+        }
+        assert_eq!(4, count);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keeps_the_time_of_the_recording() -> Result<(), RecordingError> {
+        let recording = Recording::parse(&recording())?;
+        let clock = recording.clock(0.0);
+        let (sender, _receiver) = mpsc::channel(100);
+
+        assert_eq!("2024-03-15T12:00:00+00:00", clock.now().to_rfc3339());
+        recording.replay(sender, &clock).await?;
+
+        assert_eq!("2024-03-15T12:00:01+00:00", clock.now().to_rfc3339());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn records_the_responses_to_updates() -> Result<(), Box<dyn std::error::Error>> {
+        let fixture = fs::read_to_string("tests/resources/devices_response_light.json")?;
+        let bridge = FakeBridge::start(&fixture).await?;
+        let path = env::temp_dir().join(format!(
+            "chambrier-update-recording-{}.jsonl",
+            std::process::id()
+        ));
+        let client = HueClient::with_endpoint(bridge.endpoint(), bridge.application_key())?
+            .with_recorder(Recorder::create(&path)?);
+
+        client
+            .update_light(
+                "4e5ad66f-633e-4300-84cd-634129fdb451",
+                &LightPut::default().on(false),
+            )
+            .await?;
+
+        let recording = Recording::load(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(0, recording.events()?.len());
+        match &recording.traffic[0].traffic {
+            Traffic::Update {
+                path,
+                request,
+                response,
+                error,
+            } => {
+                assert_eq!(
+                    "clip/v2/resource/light/4e5ad66f-633e-4300-84cd-634129fdb451",
+                    path
+                );
+                assert_eq!(r#"{"on":{"on":false}}"#, request);
+                assert!(response.as_ref().is_some_and(|r| r.contains("errors")));
+                assert_eq!(&None, error);
+            }
+            traffic => panic!("Unexpected traffic {:?}", traffic),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn records_the_traffic_of_a_bridge() -> Result<(), Box<dyn std::error::Error>> {
+        let fixture = fs::read_to_string("tests/resources/devices_response_light.json")?;
+        let bridge = FakeBridge::start(&fixture).await?;
+        let path =
+            env::temp_dir().join(format!("chambrier-recording-{}.jsonl", std::process::id()));
+        let client = HueClient::with_endpoint(bridge.endpoint(), bridge.application_key())?;
+        let observer = HueObserver::new(client).with_recorder(Recorder::create(&path)?);
+        let (sender, mut receiver) = mpsc::channel(100);
+        tokio::spawn(async move { observer.observe(sender).await });
+
+        timeout(TIMEOUT, async {
+            while bridge.event_streams() == 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        bridge.emit(vec![json!({
+            "id": "4e5ad66f-633e-4300-84cd-634129fdb451",
+            "type": "light",
+            "owner": { "rid": "90bdce60-3704-470e-be4c-8264f2bc8151", "rtype": "device" },
+            "on": { "on": false },
+        })]);
+        let mut received = vec![];
+        for _ in 0..4 {
+            received.push(timeout(TIMEOUT, receiver.recv()).await?.unwrap());
+        }
+
+        let replayed = Recording::load(&path)?.events()?;
+        fs::remove_file(&path)?;
+        assert_eq!(received.len(), replayed.len());
+        match (&replayed[3].1, &received[3]) {
+            (
+                Event::PropertiesChanged { properties, .. },
+                Event::PropertiesChanged {
+                    properties: expected,
+                    ..
+                },
+            ) => assert_eq!(expected, properties),
+            events => panic!("Unexpected events {:?}", events),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use serde::Serialize;
+
+// Request bodies for `PUT /clip/v2/resource/scene/{id}`.
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct ScenePut {
+    recall: SceneRecall,
+}
+
+impl ScenePut {
+    pub fn recall() -> Self {
+        ScenePut {
+            recall: SceneRecall {
+                action: SceneRecallAction::Active,
+                duration: None,
+            },
+        }
+    }
+
+    /// The duration of the transition to the scene.
+    pub fn duration(mut self, duration: u128) -> Self {
+        self.recall.duration = Some(duration);
+        self
+    }
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct SceneRecall {
+    action: SceneRecallAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<u128>, // In milliseconds
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SceneRecallAction {
+    Active,
+}
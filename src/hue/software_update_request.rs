@@ -0,0 +1,14 @@
+use serde::Serialize;
+
+// Request bodies for `PUT /clip/v2/resource/device_software_update/{id}`.
+
+#[derive(Serialize, PartialEq, Debug)]
+pub(crate) struct SoftwareUpdatePut {
+    install: bool,
+}
+
+impl SoftwareUpdatePut {
+    pub fn install() -> Self {
+        SoftwareUpdatePut { install: true }
+    }
+}
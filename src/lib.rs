@@ -0,0 +1,14 @@
+pub mod automation;
+pub mod command;
+pub mod control_server;
+pub mod event;
+pub mod health;
+pub mod hue;
+pub mod metrics;
+pub mod model;
+pub mod registry;
+pub mod simulator;
+pub mod status_server;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod virtual_devices;
@@ -0,0 +1,25 @@
+use std::env;
+use std::error::Error;
+
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
+
+/// Logs to `writer` at the levels in `RUST_LOG`, e.g. `chambrier::hue=debug,info`, or at `default_level` if it is not
+/// set. `LOG_FORMAT=json` logs a JSON object per line, including the spans the event happened in.
+pub fn init<W>(default_level: &str, writer: W, ansi: bool) -> Result<(), Box<dyn Error>>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let filter =
+        EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(default_level))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(ansi);
+    if env::var("LOG_FORMAT").is_ok_and(|format| format == "json") {
+        builder.json().with_span_list(true).try_init()
+    } else {
+        builder.try_init()
+    }
+    .map_err(|e| e as Box<dyn Error>)
+}
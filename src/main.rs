@@ -1,17 +1,207 @@
 use std::error::Error;
+use std::io;
+use std::time::Duration;
 
-use crate::hue::{HueClient, HueObserver};
+use clap::{Parser, Subcommand};
 
-mod event;
-mod hue;
-mod model;
+mod cli;
+mod dashboard;
+mod logging;
+mod serve;
+
+/// Home automation for Philips Hue. Without a command it runs the automations, like `serve`.
+#[derive(Parser)]
+#[command(name = "chambrier", version)]
+struct Cli {
+    /// Print JSON instead of text, for scripts
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// List or inspect devices
+    Devices {
+        #[command(subcommand)]
+        command: DevicesCommand,
+    },
+    /// Show a device and its properties
+    Device {
+        #[command(subcommand)]
+        command: DeviceCommand,
+    },
+    /// Set a property of a device, e.g. `set "kitchen" brightness 40`, `set "kitchen" color "#ff8800"` or
+    /// `set "kitchen" effect candle`
+    Set {
+        /// Id or (part of the) name of the device
+        device: String,
+        property: String,
+        value: String,
+        /// How long the light takes to change, e.g. `2s`
+        #[arg(long, value_parser = cli::parse_duration)]
+        transition: Option<Duration>,
+    },
+    /// Slowly change a number property of a device, e.g. `fade "bedroom" brightness 100 --over 30m`. Stops when
+    /// someone else changes the device
+    Fade {
+        /// Id or (part of the) name of the device
+        device: String,
+        property: String,
+        value: String,
+        /// How long the fade takes
+        #[arg(long, value_parser = cli::parse_duration)]
+        over: Duration,
+    },
+    /// Recall scenes
+    Scene {
+        #[command(subcommand)]
+        command: SceneCommand,
+    },
+    /// Stream colours to entertainment areas, with the client key printed by `pair` as `HUE_CLIENT_KEY`
+    Entertainment {
+        #[command(subcommand)]
+        command: EntertainmentCommand,
+    },
+    /// Print events as they happen
+    Watch,
+    /// Show and control the house in an interactive dashboard
+    Dashboard,
+    /// Create an application key, after pressing the bridge's link button
+    Pair {
+        /// Address of the bridge, found through discovery if not given
+        #[arg(long)]
+        bridge: Option<String>,
+    },
+    /// Find bridges on the local network
+    Discover,
+    /// Print a snapshot of every device, room and scene, in the format of `schema`
+    Export,
+    /// Print the JSON Schema of snapshots
+    Schema,
+    /// Run the automations
+    Serve,
+}
+
+#[derive(Subcommand)]
+enum DevicesCommand {
+    /// List every device with its type, room and properties
+    List,
+    /// List the firmware version and update state of every device
+    Inventory,
+}
+
+#[derive(Subcommand)]
+enum DeviceCommand {
+    /// Show every detail of a device
+    Show {
+        /// Id or (part of the) name of the device
+        device: String,
+    },
+    /// Install a firmware update that the device has ready to install
+    Update {
+        /// Id or (part of the) name of the device
+        device: String,
+    },
+    /// Make a light breathe for a few seconds, to find out which one it is
+    Identify {
+        /// Id or (part of the) name of the light
+        device: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SceneCommand {
+    /// Recall a scene
+    Recall {
+        /// Id or (part of the) name of the scene
+        scene: String,
+        /// Only look for the scene in this room
+        #[arg(long)]
+        room: Option<String>,
+        /// How long the lights take to change, e.g. `2s`
+        #[arg(long, value_parser = cli::parse_duration)]
+        transition: Option<Duration>,
+    },
+}
+
+#[derive(Subcommand)]
+enum EntertainmentCommand {
+    /// List the entertainment areas and the positions of their channels
+    List,
+    /// Sweep a rainbow across an area from left to right, to check that streaming works
+    Sweep {
+        /// Id or (part of the) name of the area
+        area: String,
+        /// How long the sweep runs
+        #[arg(long, value_parser = cli::parse_duration, default_value = "10s")]
+        over: Duration,
+        /// Frames sent per second, from 25 to 50
+        #[arg(long, default_value_t = 50)]
+        rate: u32,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    println!("Retrieving devices from Philips Hue...");
-    let client = HueClient::new()?;
-    let observer = HueObserver::new(client);
-    let response = observer.observe().await?;
-    eprintln!("response = {:#?}", response);
-    Ok(())
+    let cli = Cli::parse();
+    let json = cli.json;
+    let command = cli.command.unwrap_or(CliCommand::Serve);
+    // The dashboard shows the logs itself, the other commands keep stdout for their output
+    match command {
+        CliCommand::Dashboard => {}
+        CliCommand::Serve => logging::init("info", io::stderr, true)?,
+        _ => logging::init("warn", io::stderr, true)?,
+    }
+    match command {
+        CliCommand::Devices {
+            command: DevicesCommand::List,
+        } => cli::list_devices(json).await,
+        CliCommand::Devices {
+            command: DevicesCommand::Inventory,
+        } => cli::inventory(json).await,
+        CliCommand::Device {
+            command: DeviceCommand::Show { device },
+        } => cli::show_device(&device, json).await,
+        CliCommand::Device {
+            command: DeviceCommand::Update { device },
+        } => cli::install_update(&device, json).await,
+        CliCommand::Device {
+            command: DeviceCommand::Identify { device },
+        } => cli::identify(&device, json).await,
+        CliCommand::Set {
+            device,
+            property,
+            value,
+            transition,
+        } => cli::set_property(&device, &property, &value, transition, json).await,
+        CliCommand::Fade {
+            device,
+            property,
+            value,
+            over,
+        } => cli::fade(&device, &property, &value, over, json).await,
+        CliCommand::Scene {
+            command:
+                SceneCommand::Recall {
+                    scene,
+                    room,
+                    transition,
+                },
+        } => cli::recall_scene(&scene, room.as_deref(), transition, json).await,
+        CliCommand::Entertainment {
+            command: EntertainmentCommand::List,
+        } => cli::entertainment_areas(json).await,
+        CliCommand::Entertainment {
+            command: EntertainmentCommand::Sweep { area, over, rate },
+        } => cli::sweep(&area, over, rate, json).await,
+        CliCommand::Watch => cli::watch(json).await,
+        CliCommand::Dashboard => dashboard::run().await,
+        CliCommand::Pair { bridge } => cli::pair(bridge, json).await,
+        CliCommand::Discover => cli::discover(json).await,
+        CliCommand::Export => cli::export().await,
+        CliCommand::Schema => cli::schema(),
+        CliCommand::Serve => serve::serve().await,
+    }
 }
@@ -0,0 +1,400 @@
+use std::fmt;
+use std::time::Duration;
+
+use prometheus::{
+    GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, TextEncoder,
+};
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::model::{ButtonEvent, Device, Property, PropertyType};
+use crate::registry::Registry;
+
+/// Counters, gauges and histograms of the house and of chambrier itself, in the Prometheus text format. Clones share
+/// the same metrics, so one can be handed to every part that reports to them.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: prometheus::Registry,
+    device_values: GaugeVec,
+    button_events: IntCounterVec,
+    commands: IntCounterVec,
+    bridge_requests: HistogramVec,
+    event_stream_reconnects: IntCounter,
+    command_queue_depth: IntGauge,
+    automation_runs: IntCounterVec,
+    automation_commands: IntCounterVec,
+    automation_durations: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let registry = prometheus::Registry::new_custom(Some("chambrier".to_string()), None)
+            .expect("a valid prefix");
+        let metrics = Metrics {
+            device_values: GaugeVec::new(
+                Opts::new(
+                    "device_value",
+                    "Current value of a device property, booleans are 0 or 1",
+                ),
+                &["device_id", "device", "room", "property", "property_type"],
+            )
+            .expect("a valid gauge"),
+            button_events: IntCounterVec::new(
+                Opts::new("button_events_total", "Button events by device and event"),
+                &["device_id", "device", "room", "event"],
+            )
+            .expect("a valid counter"),
+            commands: IntCounterVec::new(
+                Opts::new("commands_total", "Executed commands by kind and result"),
+                &["kind", "result"],
+            )
+            .expect("a valid counter"),
+            bridge_requests: HistogramVec::new(
+                HistogramOpts::new(
+                    "bridge_request_duration_seconds",
+                    "Time until the bridge responded, by method and status",
+                ),
+                &["method", "status"],
+            )
+            .expect("a valid histogram"),
+            event_stream_reconnects: IntCounter::new(
+                "event_stream_reconnects_total",
+                "Times the bridge's event stream was reconnected",
+            )
+            .expect("a valid counter"),
+            command_queue_depth: IntGauge::new(
+                "command_queue_depth",
+                "Commands waiting to be sent to the bridge",
+            )
+            .expect("a valid gauge"),
+            automation_runs: IntCounterVec::new(
+                Opts::new("automation_runs_total", "Runs of an automation"),
+                &["automation"],
+            )
+            .expect("a valid counter"),
+            automation_commands: IntCounterVec::new(
+                Opts::new(
+                    "automation_commands_total",
+                    "Commands issued by an automation",
+                ),
+                &["automation"],
+            )
+            .expect("a valid counter"),
+            automation_durations: HistogramVec::new(
+                HistogramOpts::new(
+                    "automation_duration_seconds",
+                    "Time an automation took to handle an event or tick",
+                )
+                .buckets(vec![0.000_01, 0.000_1, 0.001, 0.01, 0.1]),
+                &["automation"],
+            )
+            .expect("a valid histogram"),
+            registry,
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 9] = [
+            Box::new(metrics.device_values.clone()),
+            Box::new(metrics.button_events.clone()),
+            Box::new(metrics.commands.clone()),
+            Box::new(metrics.bridge_requests.clone()),
+            Box::new(metrics.event_stream_reconnects.clone()),
+            Box::new(metrics.command_queue_depth.clone()),
+            Box::new(metrics.automation_runs.clone()),
+            Box::new(metrics.automation_commands.clone()),
+            Box::new(metrics.automation_durations.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metrics are registered once");
+        }
+        metrics
+    }
+
+    /// Updates the device gauges and button counters, `registry` must already have the event applied.
+    pub fn handle_event(&self, event: &Event, registry: &Registry) {
+        match event {
+            Event::DiscoveredDevices(devices) => {
+                for device in devices {
+                    self.update_device(device.id(), registry);
+                }
+            }
+            // Devices may have moved to another room or be gone, so every series is labelled anew
+            Event::DiscoveredRooms(_) | Event::DevicesRemoved(_) => {
+                self.device_values.reset();
+                for device in registry.devices() {
+                    self.update_device(device.id(), registry);
+                }
+            }
+            Event::PropertiesChanged { device_id, .. } => self.update_device(device_id, registry),
+            Event::ButtonPressed {
+                device_id, event, ..
+            } => {
+                let (device, room) = names(device_id, registry);
+                self.button_events
+                    .with_label_values(&[device_id, &device, &room, button_event_label(event)])
+                    .inc();
+            }
+            Event::DiscoveredScenes(_)
+            | Event::PresenceChanged(_)
+            | Event::AvailabilityChanged { .. }
+            | Event::PropertyWriteFailed { .. }
+            | Event::UpdateStateChanged { .. } => {}
+        }
+    }
+
+    pub fn command_executed(&self, command: &Command, succeeded: bool) {
+        let result = if succeeded { "ok" } else { "error" };
+        self.commands
+            .with_label_values(&[command.kind(), result])
+            .inc();
+    }
+
+    pub fn automation_ran(&self, automation: &str, duration: Duration, commands: usize) {
+        self.automation_runs.with_label_values(&[automation]).inc();
+        self.automation_commands
+            .with_label_values(&[automation])
+            .inc_by(commands as u64);
+        self.automation_durations
+            .with_label_values(&[automation])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Records a request to the bridge, `status` is `None` if no response was received.
+    pub(crate) fn bridge_request(&self, method: &str, status: Option<u16>, latency: Duration) {
+        let status = status.map_or("none".to_string(), |status| status.to_string());
+        self.bridge_requests
+            .with_label_values(&[method, &status])
+            .observe(latency.as_secs_f64());
+    }
+
+    pub(crate) fn event_stream_reconnected(&self) {
+        self.event_stream_reconnects.inc();
+    }
+
+    pub(crate) fn command_queue_depth(&self, depth: usize) {
+        self.command_queue_depth.set(depth as i64);
+    }
+
+    /// Every metric in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .unwrap_or_default()
+    }
+
+    fn update_device(&self, device_id: &str, registry: &Registry) {
+        let Some(device) = registry.device(device_id) else {
+            return;
+        };
+        let (name, room) = names(device_id, registry);
+        for property in device.properties().values() {
+            let value = match property {
+                Property::Boolean(property) => Some(if property.value() { 1.0 } else { 0.0 }),
+                Property::Number(property) => property.value(),
+                Property::Color(_) | Property::Enum(_) | Property::Text(_) => None,
+            };
+            let labels = [
+                device_id,
+                name.as_str(),
+                room.as_str(),
+                property.name().as_str(),
+                property_type_label(property.property_type()),
+            ];
+            match value {
+                Some(value) => self.device_values.with_label_values(&labels).set(value),
+                None => {
+                    let _ = self.device_values.remove_label_values(&labels);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+/// The name of the device and of the room it is in, empty if it is in none.
+fn names(device_id: &str, registry: &Registry) -> (String, String) {
+    let name = registry
+        .device(device_id)
+        .map(Device::name)
+        .cloned()
+        .unwrap_or_default();
+    let room = registry
+        .rooms()
+        .into_iter()
+        .find(|room| room.device_ids().iter().any(|id| id == device_id))
+        .map(|room| room.name().clone())
+        .unwrap_or_default();
+    (name, room)
+}
+
+/// The name the Hue API gives the button event.
+fn button_event_label(event: &ButtonEvent) -> &'static str {
+    match event {
+        ButtonEvent::InitialPress => "initial_press",
+        ButtonEvent::Repeat => "repeat",
+        ButtonEvent::ShortRelease => "short_release",
+        ButtonEvent::LongRelease => "long_release",
+        ButtonEvent::DoubleShortRelease => "double_short_release",
+        ButtonEvent::LongPress => "long_press",
+    }
+}
+
+fn property_type_label(property_type: &PropertyType) -> &'static str {
+    match property_type {
+        PropertyType::BatteryLevel => "battery_level",
+        PropertyType::BatteryState => "battery_state",
+        PropertyType::Brightness => "brightness",
+        PropertyType::Button => "button",
+        PropertyType::Color => "color",
+        PropertyType::ColorTemperature => "color_temperature",
+        PropertyType::Connectivity => "connectivity",
+        PropertyType::Dynamics => "dynamics",
+        PropertyType::DynamicsSpeed => "dynamics_speed",
+        PropertyType::Effect => "effect",
+        PropertyType::LightLevel => "light_level",
+        PropertyType::Motion => "motion",
+        PropertyType::On => "on",
+        PropertyType::Temperature => "temperature",
+        PropertyType::TimedEffect => "timed_effect",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::model::Room;
+    use crate::test_support;
+
+    fn light() -> Device {
+        test_support::light("light-1")
+            .named("Ceiling")
+            .on(true)
+            .brightness(80.0)
+            .build()
+    }
+
+    fn apply(metrics: &Metrics, registry: &mut Registry, event: Event) {
+        registry.apply(&event);
+        metrics.handle_event(&event, registry);
+    }
+
+    #[test]
+    fn labels_device_values_with_their_room() {
+        let metrics = Metrics::new();
+        let mut registry = Registry::new();
+
+        apply(
+            &metrics,
+            &mut registry,
+            Event::DiscoveredDevices(vec![light()]),
+        );
+        apply(
+            &metrics,
+            &mut registry,
+            Event::DiscoveredRooms(vec![Room::new(
+                "room-1".to_string(),
+                "Kitchen".to_string(),
+                vec!["light-1".to_string()],
+            )]),
+        );
+        apply(
+            &metrics,
+            &mut registry,
+            Event::PropertiesChanged {
+                device_id: "light-1".to_string(),
+                properties: HashMap::from([(
+                    "brightness".to_string(),
+                    crate::model::PropertyValue::Number(Some(40.0)),
+                )]),
+            },
+        );
+
+        let encoded = metrics.encode();
+        assert!(encoded.contains(
+            r#"chambrier_device_value{device="Ceiling",device_id="light-1",property="brightness",property_type="brightness",room="Kitchen"} 40"#
+        ));
+        assert!(encoded.contains(
+            r#"chambrier_device_value{device="Ceiling",device_id="light-1",property="on",property_type="on",room="Kitchen"} 1"#
+        ));
+        assert!(!encoded.contains(r#"room="""#));
+    }
+
+    #[test]
+    fn keeps_properties_of_the_same_type_apart() {
+        let metrics = Metrics::new();
+        let mut registry = Registry::new();
+
+        apply(
+            &metrics,
+            &mut registry,
+            Event::DiscoveredDevices(vec![test_support::sensor("sensor-1")
+                .named("Hallway")
+                .temperature("temperature", 21.5)
+                .temperature("temperature_outdoor", 8.0)
+                .build()]),
+        );
+        apply(
+            &metrics,
+            &mut registry,
+            Event::PropertiesChanged {
+                device_id: "sensor-1".to_string(),
+                properties: HashMap::from([(
+                    "temperature_outdoor".to_string(),
+                    crate::model::PropertyValue::Number(None),
+                )]),
+            },
+        );
+
+        let encoded = metrics.encode();
+        assert!(encoded.contains(
+            r#"chambrier_device_value{device="Hallway",device_id="sensor-1",property="temperature",property_type="temperature",room=""} 21.5"#
+        ));
+        assert!(!encoded.contains(r#"property="temperature_outdoor""#));
+    }
+
+    #[test]
+    fn counts_button_events_and_commands() {
+        let metrics = Metrics::new();
+        let registry = Registry::new();
+
+        metrics.handle_event(
+            &Event::ButtonPressed {
+                device_id: "switch-1".to_string(),
+                control_id: 1,
+                event: ButtonEvent::ShortRelease,
+                repeat_interval: Duration::from_millis(800),
+            },
+            &registry,
+        );
+        metrics.command_executed(
+            &Command::RecallScene {
+                scene_id: "scene-1".to_string(),
+                transition: None,
+            },
+            false,
+        );
+
+        let encoded = metrics.encode();
+        assert!(encoded.contains(
+            r#"chambrier_button_events_total{device="",device_id="switch-1",event="short_release",room=""} 1"#
+        ));
+        assert!(
+            encoded.contains(r#"chambrier_commands_total{kind="recall_scene",result="error"} 1"#)
+        );
+    }
+}
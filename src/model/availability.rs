@@ -0,0 +1,13 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Whether a device can be reached. Devices are assumed available until their integration reports otherwise.
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    #[default]
+    Available,
+    /// Reachable, but messages get lost, e.g. only those from the device arrive.
+    Degraded,
+    Unavailable,
+}
@@ -1,7 +1,11 @@
-use crate::model::{Common, PropertyType};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+use crate::model::{Common, PendingWrite, PropertyType};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct BooleanProperty {
+    #[serde(flatten)]
     common: Common,
     value: bool,
 }
@@ -21,7 +25,7 @@ impl BooleanProperty {
     }
 
     pub fn name(&self) -> &String {
-        &self.common.name()
+        self.common.name()
     }
 
     pub fn readonly(&self) -> bool {
@@ -29,14 +33,31 @@ impl BooleanProperty {
     }
 
     pub fn property_type(&self) -> &PropertyType {
-        &self.common.property_type()
+        self.common.property_type()
     }
 
     pub fn external_id(&self) -> Option<&String> {
         self.common.external_id()
     }
 
+    /// A write that was requested but not yet confirmed by the integration.
+    pub fn pending(&self) -> Option<&PendingWrite> {
+        self.common.pending()
+    }
+
+    pub(in crate::model) fn common(&self) -> &Common {
+        &self.common
+    }
+
+    pub(in crate::model) fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+
     pub fn value(&self) -> bool {
         self.value
     }
+
+    pub(crate) fn set_value(&mut self, value: bool) {
+        self.value = value;
+    }
 }
@@ -0,0 +1,376 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The white point of sRGB, what black is converted to since it has no chromaticity.
+const D65: Xy = Xy {
+    x: 0.3127,
+    y: 0.3290,
+};
+
+/// A chromaticity in the CIE 1931 colour space, i.e. a colour without its brightness.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Copy, Clone, Debug)]
+pub struct Xy {
+    x: f64,
+    y: f64,
+}
+
+impl Xy {
+    pub fn new(x: f64, y: f64) -> Self {
+        Xy { x, y }
+    }
+
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+
+    pub fn from_rgb(rgb: Rgb) -> Self {
+        let [red, green, blue] = [rgb.red, rgb.green, rgb.blue].map(|c| linear(c as f64 / 255.0));
+        let x = 0.4124 * red + 0.3576 * green + 0.1805 * blue;
+        let y = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+        let z = 0.0193 * red + 0.1192 * green + 0.9505 * blue;
+        let sum = x + y + z;
+        if sum <= 0.0 {
+            return D65;
+        }
+        Xy::new(x / sum, y / sum)
+    }
+
+    /// The colour at the highest brightness sRGB can show it, colours outside of sRGB are desaturated.
+    pub fn to_rgb(&self) -> Rgb {
+        if self.y <= 0.0 {
+            return Rgb::new(0, 0, 0);
+        }
+        let x = self.x / self.y;
+        let z = (1.0 - self.x - self.y) / self.y;
+        let red = 3.2406 * x - 1.5372 - 0.4986 * z;
+        let green = -0.9689 * x + 1.8758 + 0.0415 * z;
+        let blue = 0.0557 * x - 0.2040 + 1.0570 * z;
+        let [red, green, blue] = [red, green, blue].map(|c| c.max(0.0));
+        let maximum = red.max(green).max(blue);
+        if maximum <= 0.0 {
+            return Rgb::new(0, 0, 0);
+        }
+        let [red, green, blue] =
+            [red, green, blue].map(|c| (gamma(c / maximum) * 255.0).round() as u8);
+        Rgb::new(red, green, blue)
+    }
+
+    /// The colour of a black body at `kelvin`, i.e. of white light with that colour temperature. Temperatures are
+    /// limited to 1667 K to 25000 K, where the approximation holds.
+    pub fn from_kelvin(kelvin: f64) -> Self {
+        let t = kelvin.clamp(1667.0, 25000.0);
+        let x = if t <= 4000.0 {
+            -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910
+        } else {
+            -3.0258469e9 / t.powi(3) + 2.1070379e6 / t.powi(2) + 0.2226347e3 / t + 0.240390
+        };
+        let y = if t <= 2222.0 {
+            -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+        } else if t <= 4000.0 {
+            -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+        } else {
+            3.0817580 * x.powi(3) - 5.87338670 * x.powi(2) + 3.75112997 * x - 0.37001483
+        };
+        Xy::new(x, y)
+    }
+
+    pub fn from_mirek(mirek: f64) -> Self {
+        Xy::from_kelvin(1_000_000.0 / mirek)
+    }
+
+    pub fn distance(&self, other: &Xy) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+/// A colour in sRGB, each channel from 0 to 255.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Rgb {
+    red: u8,
+    green: u8,
+    blue: u8,
+}
+
+impl Rgb {
+    pub fn new(red: u8, green: u8, blue: u8) -> Self {
+        Rgb { red, green, blue }
+    }
+
+    pub fn red(&self) -> u8 {
+        self.red
+    }
+
+    pub fn green(&self) -> u8 {
+        self.green
+    }
+
+    pub fn blue(&self) -> u8 {
+        self.blue
+    }
+
+    /// Parses a colour like `#ff8800`, the `#` is optional.
+    pub fn from_hex(hex: &str) -> Result<Self, ColorError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        let channel = |i: usize| {
+            digits
+                .get(i..i + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+        };
+        match (digits.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(red), Some(green), Some(blue)) => Ok(Rgb::new(red, green, blue)),
+            _ => Err(ColorError::InvalidHex(hex.to_string())),
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+
+    pub fn from_hsv(hsv: Hsv) -> Self {
+        let hue = hsv.hue.rem_euclid(360.0) / 60.0;
+        let chroma = hsv.value * hsv.saturation;
+        let second = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (red, green, blue) = match hue as u8 {
+            0 => (chroma, second, 0.0),
+            1 => (second, chroma, 0.0),
+            2 => (0.0, chroma, second),
+            3 => (0.0, second, chroma),
+            4 => (second, 0.0, chroma),
+            _ => (chroma, 0.0, second),
+        };
+        let offset = hsv.value - chroma;
+        let [red, green, blue] = [red, green, blue].map(|c| ((c + offset) * 255.0).round() as u8);
+        Rgb::new(red, green, blue)
+    }
+
+    pub fn to_hsv(&self) -> Hsv {
+        let [red, green, blue] = [self.red, self.green, self.blue].map(|c| c as f64 / 255.0);
+        let maximum = red.max(green).max(blue);
+        let chroma = maximum - red.min(green).min(blue);
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if maximum == red {
+            60.0 * ((green - blue) / chroma).rem_euclid(6.0)
+        } else if maximum == green {
+            60.0 * ((blue - red) / chroma + 2.0)
+        } else {
+            60.0 * ((red - green) / chroma + 4.0)
+        };
+        let saturation = if maximum == 0.0 {
+            0.0
+        } else {
+            chroma / maximum
+        };
+        Hsv::new(hue, saturation, maximum)
+    }
+}
+
+/// A colour as hue in degrees, and saturation and value from 0 to 1.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Copy, Clone, Debug)]
+pub struct Hsv {
+    hue: f64,
+    saturation: f64,
+    value: f64,
+}
+
+impl Hsv {
+    pub fn new(hue: f64, saturation: f64, value: f64) -> Self {
+        Hsv {
+            hue,
+            saturation: saturation.clamp(0.0, 1.0),
+            value: value.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn hue(&self) -> f64 {
+        self.hue
+    }
+
+    pub fn saturation(&self) -> f64 {
+        self.saturation
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+/// The kinds of gamut Philips Hue lights have, older lights have A or B, newer ones C.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, Copy, Clone, Debug)]
+pub enum GamutType {
+    A,
+    B,
+    C,
+    #[serde(rename = "other")]
+    Other,
+}
+
+/// The colours a light can reproduce, the triangle between its red, green and blue.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Copy, Clone, Debug)]
+pub struct Gamut {
+    red: Xy,
+    green: Xy,
+    blue: Xy,
+}
+
+impl Gamut {
+    pub fn new(red: Xy, green: Xy, blue: Xy) -> Self {
+        Gamut { red, green, blue }
+    }
+
+    /// The gamut every light of `gamut_type` has, `None` for other lights.
+    pub fn of_type(gamut_type: GamutType) -> Option<Self> {
+        let gamut = |red: (f64, f64), green: (f64, f64), blue: (f64, f64)| {
+            Gamut::new(
+                Xy::new(red.0, red.1),
+                Xy::new(green.0, green.1),
+                Xy::new(blue.0, blue.1),
+            )
+        };
+        match gamut_type {
+            GamutType::A => Some(gamut((0.704, 0.296), (0.2151, 0.7106), (0.138, 0.08))),
+            GamutType::B => Some(gamut((0.675, 0.322), (0.409, 0.518), (0.167, 0.04))),
+            GamutType::C => Some(gamut((0.6915, 0.3083), (0.17, 0.7), (0.1532, 0.0475))),
+            GamutType::Other => None,
+        }
+    }
+
+    pub fn red(&self) -> Xy {
+        self.red
+    }
+
+    pub fn green(&self) -> Xy {
+        self.green
+    }
+
+    pub fn blue(&self) -> Xy {
+        self.blue
+    }
+
+    pub fn contains(&self, xy: &Xy) -> bool {
+        let side = |a: &Xy, b: &Xy| (b.x - a.x) * (xy.y - a.y) - (b.y - a.y) * (xy.x - a.x);
+        let sides = [
+            side(&self.red, &self.green),
+            side(&self.green, &self.blue),
+            side(&self.blue, &self.red),
+        ];
+        sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+    }
+
+    /// The closest colour to `xy` that the light can reproduce.
+    pub fn clamp(&self, xy: Xy) -> Xy {
+        if self.contains(&xy) {
+            return xy;
+        }
+        [
+            closest_on_segment(&self.red, &self.green, &xy),
+            closest_on_segment(&self.green, &self.blue, &xy),
+            closest_on_segment(&self.blue, &self.red, &xy),
+        ]
+        .into_iter()
+        .min_by(|a, b| a.distance(&xy).total_cmp(&b.distance(&xy)))
+        .unwrap_or(xy)
+    }
+}
+
+#[derive(Error, PartialEq, Debug)]
+pub enum ColorError {
+    #[error("'{0}' is not a colour like #ff8800")]
+    InvalidHex(String),
+}
+
+fn closest_on_segment(a: &Xy, b: &Xy, xy: &Xy) -> Xy {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length = dx * dx + dy * dy;
+    if length == 0.0 {
+        return *a;
+    }
+    let t = (((xy.x - a.x) * dx + (xy.y - a.y) * dy) / length).clamp(0.0, 1.0);
+    Xy::new(a.x + t * dx, a.y + t * dy)
+}
+
+/// Undoes the sRGB gamma of a channel from 0 to 1.
+fn linear(channel: f64) -> f64 {
+    if channel > 0.04045 {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    } else {
+        channel / 12.92
+    }
+}
+
+/// Applies the sRGB gamma to a linear channel from 0 to 1.
+fn gamma(channel: f64) -> f64 {
+    if channel > 0.0031308 {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    } else {
+        12.92 * channel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: Xy, actual: Xy) {
+        assert!(
+            expected.distance(&actual) < 0.001,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn converts_between_hex_rgb_and_xy() -> Result<(), ColorError> {
+        let orange = Rgb::from_hex("#ff8800")?;
+
+        assert_eq!(Rgb::new(255, 136, 0), orange);
+        assert_eq!("#ff8800", orange.to_hex());
+        assert_close(Xy::new(0.5336, 0.4145), Xy::from_rgb(orange));
+        assert_eq!(orange, Xy::from_rgb(orange).to_rgb());
+        assert_close(D65, Xy::from_rgb(Rgb::new(255, 255, 255)));
+        assert_eq!(
+            Err(ColorError::InvalidHex("ff88".to_string())),
+            Rgb::from_hex("ff88")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn converts_between_rgb_and_hsv() {
+        let orange = Rgb::new(255, 136, 0);
+        let hsv = orange.to_hsv();
+
+        assert_eq!(32.0, hsv.hue().round());
+        assert_eq!(1.0, hsv.saturation());
+        assert_eq!(orange, Rgb::from_hsv(hsv));
+        assert_eq!(
+            Rgb::new(0, 0, 255),
+            Rgb::from_hsv(Hsv::new(240.0, 1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn converts_colour_temperatures() {
+        assert_close(Xy::new(0.4599, 0.4106), Xy::from_kelvin(2700.0));
+        assert_close(Xy::new(0.3135, 0.3237), Xy::from_mirek(153.85));
+    }
+
+    #[test]
+    fn clamps_colours_into_the_gamut() {
+        let gamut = Gamut::of_type(GamutType::B).unwrap();
+        let inside = Xy::new(0.4, 0.4);
+        let green = Xy::from_rgb(Rgb::new(0, 255, 0));
+
+        assert_eq!(inside, gamut.clamp(inside));
+        assert!(!gamut.contains(&green));
+        let clamped = gamut.clamp(green);
+        assert_ne!(clamped, green);
+        assert!(clamped.distance(&green) <= gamut.green().distance(&green));
+    }
+}
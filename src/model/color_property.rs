@@ -0,0 +1,93 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{Common, Gamut, GamutType, PendingWrite, PropertyType, Xy};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ColorProperty {
+    #[serde(flatten)]
+    common: Common,
+    value: Xy,
+    /// The colours the light can reproduce, any colour is accepted if unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gamut: Option<Gamut>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gamut_type: Option<GamutType>,
+}
+
+impl ColorProperty {
+    pub fn new(
+        name: String,
+        readonly: bool,
+        property_type: PropertyType,
+        external_id: Option<String>,
+        value: Xy,
+    ) -> Self {
+        ColorProperty {
+            common: Common::new(name, readonly, property_type, external_id),
+            value,
+            gamut: None,
+            gamut_type: None,
+        }
+    }
+
+    /// The gamut of the light, `gamut` is taken from `gamut_type` if not given.
+    pub fn with_gamut(mut self, gamut: Option<Gamut>, gamut_type: GamutType) -> Self {
+        self.gamut = gamut.or_else(|| Gamut::of_type(gamut_type));
+        self.gamut_type = Some(gamut_type);
+        self
+    }
+
+    pub fn name(&self) -> &String {
+        self.common.name()
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.common.readonly()
+    }
+
+    pub fn property_type(&self) -> &PropertyType {
+        self.common.property_type()
+    }
+
+    pub fn external_id(&self) -> Option<&String> {
+        self.common.external_id()
+    }
+
+    /// A write that was requested but not yet confirmed by the integration.
+    pub fn pending(&self) -> Option<&PendingWrite> {
+        self.common.pending()
+    }
+
+    pub(in crate::model) fn common(&self) -> &Common {
+        &self.common
+    }
+
+    pub(in crate::model) fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+
+    pub fn value(&self) -> Xy {
+        self.value
+    }
+
+    pub(crate) fn set_value(&mut self, value: Xy) {
+        self.value = value;
+    }
+
+    pub fn gamut(&self) -> Option<&Gamut> {
+        self.gamut.as_ref()
+    }
+
+    pub fn gamut_type(&self) -> Option<GamutType> {
+        self.gamut_type
+    }
+
+    /// The closest colour to `xy` that the light can reproduce.
+    pub fn clamp(&self, xy: Xy) -> Xy {
+        match &self.gamut {
+            Some(gamut) => gamut.clamp(xy),
+            None => xy,
+        }
+    }
+}
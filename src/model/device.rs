@@ -1,7 +1,19 @@
-use crate::model::{BooleanProperty, NumberProperty};
+use crate::model::{
+    Availability, BooleanProperty, ColorProperty, EnumProperty, Firmware, NumberProperty,
+    TextProperty, Unit, UpdateState, Xy,
+};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use thiserror::Error;
 
-#[derive(Debug)]
+/// Integrations round the numbers they are sent, a reported number this close to a pending write confirms it.
+const CONFIRMATION_TOLERANCE: f64 = 0.5;
+/// The same for colours, as a distance in the CIE xy plane.
+const COLOR_CONFIRMATION_TOLERANCE: f64 = 0.01;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct Device {
     id: String,
     device_type: DeviceType,
@@ -9,11 +21,18 @@ pub struct Device {
     model_id: String,
     product_name: String,
     name: String,
+    /// The properties by their name, which is unique within the device.
     properties: HashMap<String, Property>,
+    /// The id of the device in its integration.
     external_id: Option<String>,
+    #[serde(default)]
+    availability: Availability,
+    #[serde(default)]
+    firmware: Option<Firmware>,
 }
 
 impl Device {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: String,
         device_type: DeviceType,
@@ -33,9 +52,21 @@ impl Device {
             name,
             properties,
             external_id,
+            availability: Availability::default(),
+            firmware: None,
         }
     }
 
+    pub fn with_availability(mut self, availability: Availability) -> Self {
+        self.availability = availability;
+        self
+    }
+
+    pub fn with_firmware(mut self, firmware: Firmware) -> Self {
+        self.firmware = Some(firmware);
+        self
+    }
+
     pub fn id(&self) -> &String {
         &self.id
     }
@@ -64,28 +95,284 @@ impl Device {
         &self.properties
     }
 
+    /// Applies a reported value, values that do not match the kind of the property are ignored.
+    pub(crate) fn update_property(&mut self, name: &str, value: &PropertyValue) {
+        if let Some(property) = self.properties.get_mut(name) {
+            property.update_value(value);
+        }
+    }
+
+    /// Checks whether `value` can be written to the property `name`.
+    pub fn validate(&self, name: &str, value: &PropertyValue) -> Result<(), PropertyError> {
+        self.property(name)?.validate(value)
+    }
+
+    /// Validates a write and keeps it as pending until the integration reports the value, see [`Property::request`].
+    pub fn request(
+        &mut self,
+        name: &str,
+        value: PropertyValue,
+        now: DateTime<Utc>,
+    ) -> Result<(), PropertyError> {
+        self.properties
+            .get_mut(name)
+            .ok_or_else(|| PropertyError::UnknownProperty(name.to_string()))?
+            .request(value, now)
+    }
+
+    pub(crate) fn cancel_pending(&mut self, name: &str) {
+        if let Some(property) = self.properties.get_mut(name) {
+            property.cancel_pending();
+        }
+    }
+
+    /// Keeps the writes that were pending on `previous`, the same device before it was discovered again, unless the
+    /// values it was discovered with confirm them.
+    pub(crate) fn keep_pending(&mut self, previous: &Device) {
+        for (name, property) in &mut self.properties {
+            if let Some(pending) = previous.properties.get(name).and_then(Property::pending) {
+                property.keep_pending(pending);
+            }
+        }
+    }
+
+    fn property(&self, name: &str) -> Result<&Property, PropertyError> {
+        self.properties
+            .get(name)
+            .ok_or_else(|| PropertyError::UnknownProperty(name.to_string()))
+    }
+
     pub fn external_id(&self) -> Option<&String> {
         self.external_id.as_ref()
     }
+
+    pub fn availability(&self) -> Availability {
+        self.availability
+    }
+
+    pub(crate) fn set_availability(&mut self, availability: Availability) {
+        self.availability = availability;
+    }
+
+    /// The firmware version and update state, unknown if the integration does not report them.
+    pub fn firmware(&self) -> Option<&Firmware> {
+        self.firmware.as_ref()
+    }
+
+    pub(crate) fn set_update_state(&mut self, update: UpdateState) {
+        if let Some(firmware) = &mut self.firmware {
+            firmware.set_update(update);
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
 pub enum DeviceType {
     Light,
+    Sensor,
+    Switch,
 }
 
-#[derive(Debug)]
+/// A property is tagged with its kind, e.g. `{"kind": "boolean", "name": "on", ..., "value": true}`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum Property {
     Boolean(BooleanProperty),
     Number(NumberProperty),
+    Color(ColorProperty),
+    Enum(EnumProperty),
+    Text(TextProperty),
+}
+
+impl Property {
+    pub fn name(&self) -> &String {
+        self.common().name()
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.common().readonly()
+    }
+
+    pub fn property_type(&self) -> &PropertyType {
+        self.common().property_type()
+    }
+
+    /// The last value the integration reported.
+    pub fn value(&self) -> PropertyValue {
+        match self {
+            Property::Boolean(property) => PropertyValue::Boolean(property.value()),
+            Property::Number(property) => PropertyValue::Number(property.value()),
+            Property::Color(property) => PropertyValue::Color(property.value()),
+            Property::Enum(property) => PropertyValue::Text(property.value().clone()),
+            Property::Text(property) => PropertyValue::Text(property.value().clone()),
+        }
+    }
+
+    /// A write that was requested but not yet confirmed by the integration.
+    pub fn pending(&self) -> Option<&PendingWrite> {
+        self.common().pending()
+    }
+
+    /// The value the property is about to have, for showing writes before they are confirmed.
+    pub fn desired_value(&self) -> PropertyValue {
+        self.pending()
+            .map_or_else(|| self.value(), |pending| pending.value().clone())
+    }
+
+    /// Checks whether `value` can be written: the property must be writable, of the same kind and within its bounds or
+    /// allowed values.
+    pub fn validate(&self, value: &PropertyValue) -> Result<(), PropertyError> {
+        if self.readonly() {
+            return Err(PropertyError::ReadOnly(self.name().clone()));
+        }
+        match (self, value) {
+            (Property::Boolean(_), PropertyValue::Boolean(_))
+            | (Property::Color(_), PropertyValue::Color(_))
+            | (Property::Text(_), PropertyValue::Text(_)) => Ok(()),
+            (Property::Enum(property), PropertyValue::Text(text)) => {
+                if property.values().contains(text) {
+                    Ok(())
+                } else {
+                    Err(PropertyError::NotAllowed {
+                        name: self.name().clone(),
+                        value: text.clone(),
+                        allowed: property.values().clone(),
+                    })
+                }
+            }
+            (Property::Number(property), PropertyValue::Number(Some(number))) => {
+                let minimum = property.minimum().unwrap_or(f64::MIN);
+                let maximum = property.maximum().unwrap_or(f64::MAX);
+                if (minimum..=maximum).contains(number) {
+                    Ok(())
+                } else {
+                    Err(PropertyError::OutOfRange {
+                        name: self.name().clone(),
+                        value: *number,
+                        minimum,
+                        maximum,
+                    })
+                }
+            }
+            _ => Err(PropertyError::WrongKind(self.name().clone())),
+        }
+    }
+
+    /// Converts a number given in `unit` to the unit of the property, e.g. kelvin to mirek.
+    pub fn convert(&self, value: f64, unit: &Unit) -> Result<f64, PropertyError> {
+        match self {
+            Property::Number(property) => {
+                unit.convert(value, property.unit())
+                    .ok_or_else(|| PropertyError::WrongUnit {
+                        name: self.name().clone(),
+                        expected: property.unit().clone(),
+                        given: unit.clone(),
+                    })
+            }
+            _ => Err(PropertyError::WrongKind(self.name().clone())),
+        }
+    }
+
+    /// Validates a write and keeps it as pending until the integration reports the same value. Numbers are rounded to
+    /// the precision of the property and colours moved into its gamut. Sending the write to the integration is up to
+    /// the caller.
+    pub fn request(
+        &mut self,
+        value: PropertyValue,
+        now: DateTime<Utc>,
+    ) -> Result<(), PropertyError> {
+        self.validate(&value)?;
+        let value = match (&*self, value) {
+            (Property::Number(property), PropertyValue::Number(Some(number))) => {
+                PropertyValue::Number(Some(property.round(number)))
+            }
+            (Property::Color(property), PropertyValue::Color(xy)) => {
+                PropertyValue::Color(property.clamp(xy))
+            }
+            (_, value) => value,
+        };
+        self.common_mut()
+            .set_pending(Some(PendingWrite { value, since: now }));
+        Ok(())
+    }
+
+    pub(crate) fn cancel_pending(&mut self) {
+        self.common_mut().set_pending(None);
+    }
+
+    fn keep_pending(&mut self, pending: &PendingWrite) {
+        if !confirms(pending.value(), &self.value()) {
+            self.common_mut().set_pending(Some(pending.clone()));
+        }
+    }
+
+    fn update_value(&mut self, value: &PropertyValue) {
+        if self
+            .pending()
+            .is_some_and(|pending| confirms(pending.value(), value))
+        {
+            self.cancel_pending();
+        }
+        match (self, value) {
+            (Property::Boolean(property), PropertyValue::Boolean(value)) => {
+                property.set_value(*value)
+            }
+            (Property::Number(property), PropertyValue::Number(value)) => {
+                property.set_value(*value)
+            }
+            (Property::Color(property), PropertyValue::Color(value)) => property.set_value(*value),
+            (Property::Enum(property), PropertyValue::Text(value)) => {
+                property.set_value(value.clone())
+            }
+            (Property::Text(property), PropertyValue::Text(value)) => {
+                property.set_value(value.clone())
+            }
+            _ => {}
+        }
+    }
+
+    fn common(&self) -> &Common {
+        match self {
+            Property::Boolean(property) => property.common(),
+            Property::Number(property) => property.common(),
+            Property::Color(property) => property.common(),
+            Property::Enum(property) => property.common(),
+            Property::Text(property) => property.common(),
+        }
+    }
+
+    fn common_mut(&mut self) -> &mut Common {
+        match self {
+            Property::Boolean(property) => property.common_mut(),
+            Property::Number(property) => property.common_mut(),
+            Property::Color(property) => property.common_mut(),
+            Property::Enum(property) => property.common_mut(),
+            Property::Text(property) => property.common_mut(),
+        }
+    }
 }
 
-#[derive(Debug)]
+/// A value as written to or reported for a property, a number is `null` while unknown and a colour is `{"x": .., "y": ..}`.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    Boolean(bool),
+    Number(Option<f64>),
+    Color(Xy),
+    /// The value of an enum or text property.
+    Text(String),
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub(in crate::model) struct Common {
     name: String,
     readonly: bool,
     property_type: PropertyType,
+    /// The id of the resource the property belongs to in its integration.
     external_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pending: Option<PendingWrite>,
 }
 
 impl Common {
@@ -100,6 +387,7 @@ impl Common {
             readonly,
             property_type,
             external_id,
+            pending: None,
         }
     }
 
@@ -118,17 +406,247 @@ impl Common {
     pub fn external_id(&self) -> Option<&String> {
         self.external_id.as_ref()
     }
+
+    pub fn pending(&self) -> Option<&PendingWrite> {
+        self.pending.as_ref()
+    }
+
+    fn set_pending(&mut self, pending: Option<PendingWrite>) {
+        self.pending = pending;
+    }
+}
+
+fn confirms(pending: &PropertyValue, reported: &PropertyValue) -> bool {
+    match (pending, reported) {
+        (PropertyValue::Number(Some(pending)), PropertyValue::Number(Some(reported))) => {
+            (pending - reported).abs() <= CONFIRMATION_TOLERANCE
+        }
+        (PropertyValue::Color(pending), PropertyValue::Color(reported)) => {
+            pending.distance(reported) <= COLOR_CONFIRMATION_TOLERANCE
+        }
+        (pending, reported) => pending == reported,
+    }
+}
+
+/// A value written to a property that the integration has not reported yet.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+pub struct PendingWrite {
+    value: PropertyValue,
+    /// When the write was requested.
+    since: DateTime<Utc>,
+}
+
+impl PendingWrite {
+    pub fn value(&self) -> &PropertyValue {
+        &self.value
+    }
+
+    pub fn since(&self) -> DateTime<Utc> {
+        self.since
+    }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
 pub enum PropertyType {
     BatteryLevel,
+    BatteryState,
     Brightness,
     Button,
     Color,
     ColorTemperature,
+    Connectivity,
+    Dynamics,
+    DynamicsSpeed,
+    Effect,
     LightLevel,
     Motion,
     On,
     Temperature,
+    TimedEffect,
+}
+
+#[derive(Error, PartialEq, Debug)]
+pub enum PropertyError {
+    #[error("unknown device '{0}'")]
+    UnknownDevice(String),
+    #[error("unknown property '{0}'")]
+    UnknownProperty(String),
+    #[error("property '{0}' is read-only")]
+    ReadOnly(String),
+    #[error("property '{0}' cannot hold a value of this kind")]
+    WrongKind(String),
+    #[error("{value} is not between {minimum} and {maximum} for property '{name}'")]
+    OutOfRange {
+        name: String,
+        value: f64,
+        minimum: f64,
+        maximum: f64,
+    },
+    #[error("'{value}' is not one of {allowed:?} for property '{name}'")]
+    NotAllowed {
+        name: String,
+        value: String,
+        allowed: Vec<String>,
+    },
+    #[error("property '{name}' is in {expected:?}, not {given:?}")]
+    WrongUnit {
+        name: String,
+        expected: Unit,
+        given: Unit,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{GamutType, Rgb};
+    use crate::test_support;
+    use chrono::TimeZone;
+
+    fn light() -> Device {
+        test_support::light("light-1")
+            .named("Ceiling")
+            .brightness(80.0)
+            .color_temperature(Some(366.0), 153.0, 500.0)
+            .motion(false)
+            .build()
+    }
+
+    #[test]
+    fn validates_writes() {
+        let device = light();
+
+        assert_eq!(
+            Ok(()),
+            device.validate("brightness", &PropertyValue::Number(Some(40.0)))
+        );
+        assert_eq!(
+            Err(PropertyError::OutOfRange {
+                name: "brightness".to_string(),
+                value: 1.0,
+                minimum: 2.0,
+                maximum: 100.0
+            }),
+            device.validate("brightness", &PropertyValue::Number(Some(1.0)))
+        );
+        assert_eq!(
+            Err(PropertyError::WrongKind("brightness".to_string())),
+            device.validate("brightness", &PropertyValue::Boolean(true))
+        );
+        assert_eq!(
+            Err(PropertyError::ReadOnly("motion".to_string())),
+            device.validate("motion", &PropertyValue::Boolean(true))
+        );
+        assert_eq!(
+            Err(PropertyError::UnknownProperty("color".to_string())),
+            device.validate("color", &PropertyValue::Boolean(true))
+        );
+        assert!(matches!(
+            device.properties()["brightness"].convert(2700.0, &Unit::Kelvin),
+            Err(PropertyError::WrongUnit { .. })
+        ));
+    }
+
+    #[test]
+    fn keeps_a_write_pending_until_the_value_is_reported() -> Result<(), PropertyError> {
+        let mut device = light();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 20, 0, 0).unwrap();
+
+        device.request("brightness", PropertyValue::Number(Some(40.0)), now)?;
+        let brightness = &device.properties()["brightness"];
+        assert_eq!(PropertyValue::Number(Some(80.0)), brightness.value());
+        assert_eq!(
+            PropertyValue::Number(Some(40.0)),
+            brightness.desired_value()
+        );
+        assert_eq!(Some(now), brightness.pending().map(|p| p.since()));
+
+        device.update_property("brightness", &PropertyValue::Number(Some(60.0)));
+        assert!(device.properties()["brightness"].pending().is_some());
+
+        device.update_property("brightness", &PropertyValue::Number(Some(40.0)));
+        assert!(device.properties()["brightness"].pending().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn converts_and_rounds_numbers_to_the_property() -> Result<(), PropertyError> {
+        let mut device = light();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 20, 0, 0).unwrap();
+
+        let mirek = device.properties()["color_temperature"].convert(2700.0, &Unit::Kelvin)?;
+        device.request("color_temperature", PropertyValue::Number(Some(mirek)), now)?;
+        device.request("brightness", PropertyValue::Number(Some(24.11)), now)?;
+
+        let properties = device.properties();
+        assert_eq!(
+            PropertyValue::Number(Some(370.0)),
+            properties["color_temperature"].desired_value()
+        );
+        assert_eq!(
+            PropertyValue::Number(Some(24.11)),
+            properties["brightness"].desired_value()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn moves_requested_colours_into_the_gamut() -> Result<(), PropertyError> {
+        let mut color = Property::Color(
+            ColorProperty::new(
+                "color".to_string(),
+                false,
+                PropertyType::Color,
+                None,
+                Xy::new(0.4599, 0.4106),
+            )
+            .with_gamut(None, GamutType::B),
+        );
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 20, 0, 0).unwrap();
+        let green = Xy::from_rgb(Rgb::new(0, 255, 0));
+
+        color.request(PropertyValue::Color(green), now)?;
+        let PropertyValue::Color(requested) = color.desired_value() else {
+            panic!("the desired value is not a colour");
+        };
+        assert_ne!(requested, green);
+        assert!(requested.distance(&Xy::new(0.409, 0.518)) < 0.1);
+
+        color.update_value(&PropertyValue::Color(Xy::new(
+            requested.x() + 0.001,
+            requested.y(),
+        )));
+        assert!(color.pending().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn only_accepts_allowed_values_for_enums() {
+        let dynamics = Property::Enum(EnumProperty::new(
+            "dynamics".to_string(),
+            false,
+            PropertyType::Dynamics,
+            None,
+            "none".to_string(),
+            vec!["none".to_string(), "dynamic_palette".to_string()],
+        ));
+
+        assert_eq!(
+            Ok(()),
+            dynamics.validate(&PropertyValue::Text("dynamic_palette".to_string()))
+        );
+        assert_eq!(
+            Err(PropertyError::NotAllowed {
+                name: "dynamics".to_string(),
+                value: "sparkle".to_string(),
+                allowed: vec!["none".to_string(), "dynamic_palette".to_string()],
+            }),
+            dynamics.validate(&PropertyValue::Text("sparkle".to_string()))
+        );
+        assert_eq!(
+            Err(PropertyError::WrongKind("dynamics".to_string())),
+            dynamics.validate(&PropertyValue::Boolean(true))
+        );
+    }
 }
@@ -0,0 +1,72 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{Common, PendingWrite, PropertyType};
+
+/// A property that is one of a fixed list of values, e.g. the state of a battery.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct EnumProperty {
+    #[serde(flatten)]
+    common: Common,
+    value: String,
+    /// Every value the property can have, only these can be written.
+    values: Vec<String>,
+}
+
+impl EnumProperty {
+    pub fn new(
+        name: String,
+        readonly: bool,
+        property_type: PropertyType,
+        external_id: Option<String>,
+        value: String,
+        values: Vec<String>,
+    ) -> Self {
+        EnumProperty {
+            common: Common::new(name, readonly, property_type, external_id),
+            value,
+            values,
+        }
+    }
+
+    pub fn name(&self) -> &String {
+        self.common.name()
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.common.readonly()
+    }
+
+    pub fn property_type(&self) -> &PropertyType {
+        self.common.property_type()
+    }
+
+    pub fn external_id(&self) -> Option<&String> {
+        self.common.external_id()
+    }
+
+    /// A write that was requested but not yet confirmed by the integration.
+    pub fn pending(&self) -> Option<&PendingWrite> {
+        self.common.pending()
+    }
+
+    pub(in crate::model) fn common(&self) -> &Common {
+        &self.common
+    }
+
+    pub(in crate::model) fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+
+    pub fn value(&self) -> &String {
+        &self.value
+    }
+
+    pub(crate) fn set_value(&mut self, value: String) {
+        self.value = value;
+    }
+
+    pub fn values(&self) -> &Vec<String> {
+        &self.values
+    }
+}
@@ -0,0 +1,49 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The software running on a device and whether a newer version is on its way.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug)]
+pub struct Firmware {
+    version: String,
+    update: UpdateState,
+    /// The id of the integration's resource that installs updates, if the device can be updated.
+    external_id: Option<String>,
+}
+
+impl Firmware {
+    pub fn new(version: String, update: UpdateState, external_id: Option<String>) -> Firmware {
+        Firmware {
+            version,
+            update,
+            external_id,
+        }
+    }
+
+    pub fn version(&self) -> &String {
+        &self.version
+    }
+
+    pub fn update(&self) -> UpdateState {
+        self.update
+    }
+
+    pub fn external_id(&self) -> Option<&String> {
+        self.external_id.as_ref()
+    }
+
+    pub(crate) fn set_update(&mut self, update: UpdateState) {
+        self.update = update;
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateState {
+    #[default]
+    NoUpdate,
+    /// A newer version is known, but still being transferred to the device.
+    UpdatePending,
+    /// The newer version is on the device and is installed once asked to.
+    ReadyToInstall,
+    Installing,
+}
@@ -1,7 +1,32 @@
+mod availability;
 mod boolean_property;
+mod button;
+mod color;
+mod color_property;
 mod device;
+mod enum_property;
+mod firmware;
 mod number_property;
+mod presence;
+mod room;
+mod scene;
+mod text_property;
+mod unit;
 
+pub use availability::Availability;
 pub use boolean_property::BooleanProperty;
+pub use button::ButtonEvent;
+pub use color::{ColorError, Gamut, GamutType, Hsv, Rgb, Xy};
+pub use color_property::ColorProperty;
 pub use device::*;
-pub use number_property::{NumberProperty, Unit};
+pub use enum_property::EnumProperty;
+pub use firmware::{Firmware, UpdateState};
+pub use number_property::NumberProperty;
+pub use presence::Presence;
+pub use room::Room;
+pub use scene::Scene;
+pub use text_property::TextProperty;
+pub use unit::{
+    celsius_to_fahrenheit, fahrenheit_to_celsius, kelvin_to_mirek, light_level_to_lux,
+    lux_to_light_level, mirek_to_kelvin, Unit,
+};
@@ -1,24 +1,33 @@
-use crate::model::{Common, PropertyType};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+use crate::model::{Common, PendingWrite, PropertyType, Unit};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct NumberProperty {
+    #[serde(flatten)]
     common: Common,
     unit: Unit,
-    value: Option<usize>,
-    minimum: Option<usize>,
-    maximum: Option<usize>,
+    /// Unknown until the device reports it.
+    value: Option<f64>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    /// The precision of the value, values are rounded to a multiple of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    step: Option<f64>,
 }
 
 impl NumberProperty {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: String,
         readonly: bool,
         property_type: PropertyType,
         external_id: Option<String>,
         unit: Unit,
-        value: Option<usize>,
-        minimum: Option<usize>,
-        maximum: Option<usize>,
+        value: Option<f64>,
+        minimum: Option<f64>,
+        maximum: Option<f64>,
     ) -> Self {
         NumberProperty {
             common: Common::new(name, readonly, property_type, external_id),
@@ -26,11 +35,17 @@ impl NumberProperty {
             value,
             minimum,
             maximum,
+            step: None,
         }
     }
 
+    pub fn with_step(mut self, step: f64) -> Self {
+        self.step = Some(step);
+        self
+    }
+
     pub fn name(&self) -> &String {
-        &self.common.name()
+        self.common.name()
     }
 
     pub fn readonly(&self) -> bool {
@@ -38,34 +53,55 @@ impl NumberProperty {
     }
 
     pub fn property_type(&self) -> &PropertyType {
-        &self.common.property_type()
+        self.common.property_type()
     }
 
     pub fn external_id(&self) -> Option<&String> {
         self.common.external_id()
     }
 
+    /// A write that was requested but not yet confirmed by the integration.
+    pub fn pending(&self) -> Option<&PendingWrite> {
+        self.common.pending()
+    }
+
+    pub(in crate::model) fn common(&self) -> &Common {
+        &self.common
+    }
+
+    pub(in crate::model) fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+
     pub fn unit(&self) -> &Unit {
         &self.unit
     }
 
-    pub fn value(&self) -> Option<usize> {
+    pub fn value(&self) -> Option<f64> {
         self.value
     }
 
-    pub fn minimum(&self) -> Option<usize> {
+    pub(crate) fn set_value(&mut self, value: Option<f64>) {
+        self.value = value;
+    }
+
+    pub fn minimum(&self) -> Option<f64> {
         self.minimum
     }
 
-    pub fn maximum(&self) -> Option<usize> {
+    pub fn maximum(&self) -> Option<f64> {
         self.maximum
     }
-}
 
-#[derive(Debug)]
-pub enum Unit {
-    Percentage,
-    Lumen,
-    Celcius,
-    Kelvin,
+    pub fn step(&self) -> Option<f64> {
+        self.step
+    }
+
+    /// Rounds `value` to the precision of the property.
+    pub fn round(&self, value: f64) -> f64 {
+        match self.step {
+            Some(step) if step > 0.0 => (value / step).round() * step,
+            _ => value,
+        }
+    }
 }
@@ -0,0 +1,12 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Whether anyone is home. Automations use it to decide what to do, e.g. vacation mode only runs while away.
+#[derive(Serialize, Deserialize, JsonSchema, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    #[default]
+    Home,
+    Away,
+    Sleeping,
+}
@@ -0,0 +1,44 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Room {
+    id: String,
+    name: String,
+    device_ids: Vec<String>,
+    /// The integration's group that controls every light of the room at once, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    external_id: Option<String>,
+}
+
+impl Room {
+    pub fn new(id: String, name: String, device_ids: Vec<String>) -> Self {
+        Room {
+            id,
+            name,
+            device_ids,
+            external_id: None,
+        }
+    }
+
+    pub fn with_external_id(mut self, external_id: String) -> Self {
+        self.external_id = Some(external_id);
+        self
+    }
+
+    pub fn id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn device_ids(&self) -> &Vec<String> {
+        &self.device_ids
+    }
+
+    pub fn external_id(&self) -> Option<&String> {
+        self.external_id.as_ref()
+    }
+}
@@ -0,0 +1,28 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Scene {
+    id: String,
+    name: String,
+    /// The room the scene lights, if it is not for a zone.
+    room_id: Option<String>,
+}
+
+impl Scene {
+    pub fn new(id: String, name: String, room_id: Option<String>) -> Self {
+        Scene { id, name, room_id }
+    }
+
+    pub fn id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn room_id(&self) -> Option<&String> {
+        self.room_id.as_ref()
+    }
+}
@@ -0,0 +1,64 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{Common, PendingWrite, PropertyType};
+
+/// A property holding any text.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct TextProperty {
+    #[serde(flatten)]
+    common: Common,
+    value: String,
+}
+
+impl TextProperty {
+    pub fn new(
+        name: String,
+        readonly: bool,
+        property_type: PropertyType,
+        external_id: Option<String>,
+        value: String,
+    ) -> Self {
+        TextProperty {
+            common: Common::new(name, readonly, property_type, external_id),
+            value,
+        }
+    }
+
+    pub fn name(&self) -> &String {
+        self.common.name()
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.common.readonly()
+    }
+
+    pub fn property_type(&self) -> &PropertyType {
+        self.common.property_type()
+    }
+
+    pub fn external_id(&self) -> Option<&String> {
+        self.common.external_id()
+    }
+
+    /// A write that was requested but not yet confirmed by the integration.
+    pub fn pending(&self) -> Option<&PendingWrite> {
+        self.common.pending()
+    }
+
+    pub(in crate::model) fn common(&self) -> &Common {
+        &self.common
+    }
+
+    pub(in crate::model) fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+
+    pub fn value(&self) -> &String {
+        &self.value
+    }
+
+    pub(crate) fn set_value(&mut self, value: String) {
+        self.value = value;
+    }
+}
@@ -0,0 +1,90 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Unit {
+    Percentage,
+    /// The light level as Philips Hue reports it, `10000 * log10(lux) + 1`. Snapshots from before
+    /// the rename call it `lumen`.
+    #[serde(alias = "lumen")]
+    LightLevel,
+    Lux,
+    Celcius,
+    Fahrenheit,
+    Kelvin,
+    /// Micro reciprocal degrees, one million divided by the colour temperature in kelvin.
+    Mirek,
+}
+
+impl Unit {
+    /// Converts `value` from this unit to `to`, `None` if the units measure different things.
+    pub fn convert(&self, value: f64, to: &Unit) -> Option<f64> {
+        match (self, to) {
+            (from, to) if from == to => Some(value),
+            (Unit::Mirek, Unit::Kelvin) => Some(mirek_to_kelvin(value)),
+            (Unit::Kelvin, Unit::Mirek) => Some(kelvin_to_mirek(value)),
+            (Unit::Celcius, Unit::Fahrenheit) => Some(celsius_to_fahrenheit(value)),
+            (Unit::Fahrenheit, Unit::Celcius) => Some(fahrenheit_to_celsius(value)),
+            (Unit::LightLevel, Unit::Lux) => Some(light_level_to_lux(value)),
+            (Unit::Lux, Unit::LightLevel) => Some(lux_to_light_level(value)),
+            _ => None,
+        }
+    }
+}
+
+pub fn mirek_to_kelvin(mirek: f64) -> f64 {
+    1_000_000.0 / mirek
+}
+
+pub fn kelvin_to_mirek(kelvin: f64) -> f64 {
+    1_000_000.0 / kelvin
+}
+
+pub fn celsius_to_fahrenheit(celsius: f64) -> f64 {
+    celsius * 9.0 / 5.0 + 32.0
+}
+
+pub fn fahrenheit_to_celsius(fahrenheit: f64) -> f64 {
+    (fahrenheit - 32.0) * 5.0 / 9.0
+}
+
+/// Converts a Philips Hue light level to lux.
+pub fn light_level_to_lux(light_level: f64) -> f64 {
+    10f64.powf((light_level - 1.0) / 10_000.0)
+}
+
+/// Converts lux to a Philips Hue light level, darkness is level 0.
+pub fn lux_to_light_level(lux: f64) -> f64 {
+    if lux <= 0.0 {
+        0.0
+    } else {
+        10_000.0 * lux.log10() + 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: f64, actual: Option<f64>) {
+        let actual = actual.expect("the units convert");
+        assert!(
+            (expected - actual).abs() < 0.01,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn converts_between_units_of_the_same_quantity() {
+        assert_close(2702.7, Unit::Mirek.convert(370.0, &Unit::Kelvin));
+        assert_close(153.85, Unit::Kelvin.convert(6500.0, &Unit::Mirek));
+        assert_close(-4.0, Unit::Celcius.convert(-20.0, &Unit::Fahrenheit));
+        assert_close(21.0, Unit::Fahrenheit.convert(69.8, &Unit::Celcius));
+        assert_close(100.0, Unit::LightLevel.convert(20001.0, &Unit::Lux));
+        assert_close(10001.0, Unit::Lux.convert(10.0, &Unit::LightLevel));
+        assert_eq!(None, Unit::Percentage.convert(40.0, &Unit::Kelvin));
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::event::Event;
+use crate::model::{Device, Presence, PropertyError, PropertyValue, Room, Scene};
+
+/// The version of the snapshot format, raised whenever a change would break existing readers.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The last known state of every device, room and scene, kept up to date by applying events.
+#[derive(Default)]
+pub struct Registry {
+    devices: HashMap<String, Device>,
+    rooms: HashMap<String, Room>,
+    scenes: HashMap<String, Scene>,
+    presence: Presence,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Restores a registry from a snapshot, e.g. one that was persisted or exported by another instance.
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<Registry, SnapshotError> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+        Ok(Registry {
+            devices: by_id(snapshot.devices, Device::id),
+            rooms: by_id(snapshot.rooms, Room::id),
+            scenes: by_id(snapshot.scenes, Scene::id),
+            presence: snapshot.presence,
+        })
+    }
+
+    /// Everything the registry knows, sorted by id so that equal registries give equal snapshots.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            devices: sorted_by_id(self.devices(), Device::id),
+            rooms: sorted_by_id(self.rooms(), Room::id),
+            scenes: sorted_by_id(self.scenes(), Scene::id),
+            presence: self.presence,
+        }
+    }
+
+    pub fn apply(&mut self, event: &Event) {
+        match event {
+            Event::DiscoveredDevices(devices) => {
+                for device in devices {
+                    let mut device = device.clone();
+                    if let Some(previous) = self.devices.get(device.id()) {
+                        device.keep_pending(previous);
+                    }
+                    self.devices.insert(device.id().clone(), device);
+                }
+            }
+            Event::DevicesRemoved(device_ids) => {
+                for device_id in device_ids {
+                    self.devices.remove(device_id);
+                }
+            }
+            Event::DiscoveredRooms(rooms) => {
+                for room in rooms {
+                    self.rooms.insert(room.id().clone(), room.clone());
+                }
+            }
+            Event::DiscoveredScenes(scenes) => {
+                for scene in scenes {
+                    self.scenes.insert(scene.id().clone(), scene.clone());
+                }
+            }
+            Event::PropertiesChanged {
+                device_id,
+                properties,
+            } => {
+                if let Some(device) = self.devices.get_mut(device_id) {
+                    for (name, value) in properties {
+                        device.update_property(name, value);
+                    }
+                }
+            }
+            Event::ButtonPressed { .. } => {}
+            Event::PresenceChanged(presence) => self.presence = *presence,
+            Event::AvailabilityChanged {
+                device_id,
+                availability,
+            } => {
+                if let Some(device) = self.devices.get_mut(device_id) {
+                    device.set_availability(*availability);
+                }
+            }
+            Event::PropertyWriteFailed {
+                device_id,
+                property,
+                ..
+            } => {
+                if let Some(device) = self.devices.get_mut(device_id) {
+                    device.cancel_pending(property);
+                }
+            }
+            Event::UpdateStateChanged { device_id, update } => {
+                if let Some(device) = self.devices.get_mut(device_id) {
+                    device.set_update_state(*update);
+                }
+            }
+        }
+    }
+
+    /// Checks whether all of `properties` can be written to the device, see [`Device::validate`].
+    pub fn validate(
+        &self,
+        device_id: &str,
+        properties: &HashMap<String, PropertyValue>,
+    ) -> Result<(), PropertyError> {
+        let device = self
+            .devices
+            .get(device_id)
+            .ok_or_else(|| PropertyError::UnknownDevice(device_id.to_string()))?;
+        for (name, value) in properties {
+            device.validate(name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Validates writes to the properties of a device and keeps them as pending until the device reports them. Nothing
+    /// is kept if any of them is invalid.
+    pub fn request(
+        &mut self,
+        device_id: &str,
+        properties: &HashMap<String, PropertyValue>,
+        now: DateTime<Utc>,
+    ) -> Result<(), PropertyError> {
+        self.validate(device_id, properties)?;
+        let device = self
+            .devices
+            .get_mut(device_id)
+            .ok_or_else(|| PropertyError::UnknownDevice(device_id.to_string()))?;
+        for (name, value) in properties {
+            device.request(name, value.clone(), now)?;
+        }
+        Ok(())
+    }
+
+    /// Gives up on the writes that have been pending for longer than `timeout`, returning an event for each of them.
+    pub fn expire_pending(&mut self, now: DateTime<Utc>, timeout: Duration) -> Vec<Event> {
+        let mut events = vec![];
+        for device in self.devices.values_mut() {
+            let expired: Vec<String> = device
+                .properties()
+                .iter()
+                .filter(|(_, property)| {
+                    property
+                        .pending()
+                        .is_some_and(|pending| now >= pending.since() + timeout)
+                })
+                .map(|(name, _)| name.clone())
+                .collect();
+            for name in expired {
+                device.cancel_pending(&name);
+                events.push(Event::PropertyWriteFailed {
+                    device_id: device.id().clone(),
+                    property: name,
+                    error: format!("not confirmed within {}s", timeout.as_secs()),
+                });
+            }
+        }
+        events
+    }
+
+    pub fn device(&self, id: &str) -> Option<&Device> {
+        self.devices.get(id)
+    }
+
+    pub fn devices(&self) -> Vec<&Device> {
+        self.devices.values().collect()
+    }
+
+    pub fn rooms(&self) -> Vec<&Room> {
+        self.rooms.values().collect()
+    }
+
+    pub fn scenes(&self) -> Vec<&Scene> {
+        self.scenes.values().collect()
+    }
+
+    pub fn presence(&self) -> Presence {
+        self.presence
+    }
+
+    /// Finds a device by its id or by its name.
+    pub fn find_device(&self, id_or_name: &str) -> Option<&Device> {
+        self.devices
+            .get(id_or_name)
+            .or_else(|| self.devices.values().find(|d| d.name() == id_or_name))
+    }
+
+    /// Finds a room by its id or by its name.
+    pub fn find_room(&self, id_or_name: &str) -> Option<&Room> {
+        self.rooms
+            .get(id_or_name)
+            .or_else(|| self.rooms.values().find(|r| r.name() == id_or_name))
+    }
+
+    /// Finds a scene by its id or by its name, a name is only looked up within the room if one is given.
+    pub fn find_scene(&self, id_or_name: &str, room_id: Option<&String>) -> Option<&Scene> {
+        self.scenes.get(id_or_name).or_else(|| {
+            self.scenes
+                .values()
+                .find(|s| s.name() == id_or_name && (room_id.is_none() || s.room_id() == room_id))
+        })
+    }
+
+    /// Finds the devices whose id or name best matches `query`, for names typed by people: an exact id, then the name
+    /// ignoring case, then names containing the query, then names containing its letters in order. More than one
+    /// device is returned when the query is ambiguous.
+    pub fn search_devices(&self, query: &str) -> Vec<&Device> {
+        search(self.devices(), query, |d| d.id(), |d| d.name())
+    }
+
+    /// Like [`Registry::search_devices`], for rooms.
+    pub fn search_rooms(&self, query: &str) -> Vec<&Room> {
+        search(self.rooms(), query, |r| r.id(), |r| r.name())
+    }
+
+    /// Like [`Registry::search_devices`], for scenes. Only scenes of the room are searched if one is given.
+    pub fn search_scenes(&self, query: &str, room_id: Option<&String>) -> Vec<&Scene> {
+        let scenes = self
+            .scenes()
+            .into_iter()
+            .filter(|s| room_id.is_none() || s.room_id() == room_id)
+            .collect();
+        search(scenes, query, |s| s.id(), |s| s.name())
+    }
+
+    /// Resolves a room or device, given by id or name, to the ids of the devices it covers. Rooms take precedence.
+    pub fn resolve_device_ids(&self, target: &str) -> Vec<String> {
+        if let Some(room) = self.find_room(target) {
+            let mut device_ids = room.device_ids().clone();
+            device_ids.retain(|id| self.devices.contains_key(id));
+            device_ids
+        } else {
+            self.find_device(target)
+                .map(|device| vec![device.id().clone()])
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Everything a registry knows, in a versioned JSON representation so that it can be persisted and read by other
+/// tools.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Snapshot {
+    /// The version of the format the snapshot was written in.
+    version: u32,
+    devices: Vec<Device>,
+    rooms: Vec<Room>,
+    scenes: Vec<Scene>,
+    #[serde(default)]
+    presence: Presence,
+}
+
+impl Snapshot {
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn devices(&self) -> &Vec<Device> {
+        &self.devices
+    }
+
+    pub fn rooms(&self) -> &Vec<Room> {
+        &self.rooms
+    }
+
+    pub fn scenes(&self) -> &Vec<Scene> {
+        &self.scenes
+    }
+
+    pub fn presence(&self) -> Presence {
+        self.presence
+    }
+
+    /// The JSON Schema of snapshots, for tools that read or write them.
+    pub fn schema() -> RootSchema {
+        schemars::schema_for!(Snapshot)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("snapshot version {0} is not supported, expected version {SNAPSHOT_VERSION}")]
+    UnsupportedVersion(u32),
+}
+
+fn by_id<T>(items: Vec<T>, id: impl Fn(&T) -> &String) -> HashMap<String, T> {
+    items
+        .into_iter()
+        .map(|item| (id(&item).clone(), item))
+        .collect()
+}
+
+fn sorted_by_id<T: Clone>(items: Vec<&T>, id: impl Fn(&T) -> &String) -> Vec<T> {
+    let mut items: Vec<T> = items.into_iter().cloned().collect();
+    items.sort_by(|a, b| id(a).cmp(id(b)));
+    items
+}
+
+fn search<'a, T>(
+    items: Vec<&'a T>,
+    query: &str,
+    id: impl Fn(&T) -> &String,
+    name: impl Fn(&T) -> &String,
+) -> Vec<&'a T> {
+    let query = query.to_lowercase();
+    let tiers: [&dyn Fn(&T) -> bool; 4] = [
+        &|item| id(item).to_lowercase() == query,
+        &|item| name(item).to_lowercase() == query,
+        &|item| name(item).to_lowercase().contains(&query),
+        &|item| is_subsequence(&query, &name(item).to_lowercase()),
+    ];
+    for matches in tiers {
+        let mut found: Vec<&T> = items.iter().copied().filter(|item| matches(item)).collect();
+        if !found.is_empty() {
+            found.sort_by(|a, b| name(a).cmp(name(b)));
+            return found;
+        }
+    }
+    vec![]
+}
+
+fn is_subsequence(query: &str, name: &str) -> bool {
+    let mut name = name.chars();
+    query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|c| name.any(|n| n == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::model::{Availability, BooleanProperty, Property, PropertyType};
+    use crate::test_support;
+
+    fn registry() -> Registry {
+        let device = |id: &str, name: &str| test_support::light(id).named(name).build();
+        let mut registry = Registry::new();
+        registry.apply(&Event::DiscoveredDevices(vec![
+            device("1", "Kitchen ceiling"),
+            device("2", "Kitchen counter"),
+            device("3", "Living room floor lamp"),
+        ]));
+        registry
+    }
+
+    fn lamp(brightness: f64) -> Device {
+        test_support::light("4")
+            .named("Hallway")
+            .brightness(brightness)
+            .build()
+    }
+
+    fn names(devices: Vec<&Device>) -> Vec<&str> {
+        devices.iter().map(|d| d.name().as_str()).collect()
+    }
+
+    #[test]
+    fn searches_by_id_then_name() {
+        let registry = registry();
+
+        assert_eq!(vec!["Kitchen counter"], names(registry.search_devices("2")));
+        assert_eq!(
+            vec!["Kitchen ceiling"],
+            names(registry.search_devices("kitchen CEILING"))
+        );
+    }
+
+    #[test]
+    fn searches_partial_names() {
+        let registry = registry();
+
+        assert_eq!(
+            vec!["Kitchen ceiling", "Kitchen counter"],
+            names(registry.search_devices("kitchen"))
+        );
+        assert_eq!(
+            vec!["Living room floor lamp"],
+            names(registry.search_devices("floor"))
+        );
+        assert_eq!(
+            vec!["Living room floor lamp"],
+            names(registry.search_devices("lvrm lamp"))
+        );
+        assert!(registry.search_devices("garage").is_empty());
+    }
+
+    #[test]
+    fn keeps_pending_writes_when_a_device_is_discovered_again() -> Result<(), PropertyError> {
+        let mut registry = Registry::new();
+        registry.apply(&Event::DiscoveredDevices(vec![lamp(20.0)]));
+        let brightness =
+            HashMap::from([("brightness".to_string(), PropertyValue::Number(Some(80.0)))]);
+        registry.request("4", &brightness, Utc::now())?;
+
+        registry.apply(&Event::DiscoveredDevices(vec![lamp(30.0)]));
+
+        let property = &registry.device("4").unwrap().properties()["brightness"];
+        assert_eq!(PropertyValue::Number(Some(30.0)), property.value());
+        assert_eq!(
+            Some(&PropertyValue::Number(Some(80.0))),
+            property.pending().map(|pending| pending.value())
+        );
+
+        registry.apply(&Event::DiscoveredDevices(vec![lamp(80.0)]));
+
+        let property = &registry.device("4").unwrap().properties()["brightness"];
+        assert!(property.pending().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn forgets_removed_devices() {
+        let mut registry = registry();
+
+        registry.apply(&Event::DevicesRemoved(vec!["2".to_string()]));
+
+        assert!(registry.device("2").is_none());
+        assert_eq!(2, registry.devices().len());
+    }
+
+    #[test]
+    fn restores_a_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+        let mut registry = registry();
+        registry.apply(&Event::DiscoveredDevices(vec![test_support::light("4")
+            .named("Hallway")
+            .on(true)
+            .brightness(24.0)
+            .availability(Availability::Degraded)
+            .build()]));
+        registry.apply(&Event::DiscoveredRooms(vec![Room::new(
+            "room-1".to_string(),
+            "Kitchen".to_string(),
+            vec!["1".to_string(), "2".to_string()],
+        )]));
+        registry.apply(&Event::PresenceChanged(Presence::Away));
+
+        let json = serde_json::to_value(registry.snapshot())?;
+        let restored = Registry::from_snapshot(serde_json::from_value(json.clone())?)?;
+
+        assert_eq!(json, serde_json::to_value(restored.snapshot())?);
+        let hallway = restored.device("4").expect("the device is restored");
+        assert_eq!(Availability::Degraded, hallway.availability());
+        assert_eq!(
+            crate::model::PropertyValue::Number(Some(24.0)),
+            hallway.properties()["brightness"].value()
+        );
+        assert_eq!(Presence::Away, restored.presence());
+        Ok(())
+    }
+
+    #[test]
+    fn tags_properties_with_their_kind() -> Result<(), serde_json::Error> {
+        let property = Property::Boolean(BooleanProperty::new(
+            "motion".to_string(),
+            true,
+            PropertyType::Motion,
+            None,
+            false,
+        ));
+
+        assert_eq!(
+            serde_json::json!({
+                "kind": "boolean",
+                "name": "motion",
+                "readonly": true,
+                "property_type": "motion",
+                "external_id": null,
+                "value": false,
+            }),
+            serde_json::to_value(&property)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_other_snapshot_versions() -> Result<(), serde_json::Error> {
+        let snapshot =
+            serde_json::from_str(r#"{"version":2,"devices":[],"rooms":[],"scenes":[]}"#)?;
+
+        assert!(matches!(
+            Registry::from_snapshot(snapshot),
+            Err(SnapshotError::UnsupportedVersion(2))
+        ));
+        Ok(())
+    }
+
+    /// Changes to the schema must be deliberate: update `schemas/snapshot.schema.json`, and raise
+    /// [`SNAPSHOT_VERSION`] if existing snapshots no longer match it.
+    #[test]
+    fn keeps_the_schema_stable() -> Result<(), Box<dyn std::error::Error>> {
+        let published: Value =
+            serde_json::from_str(&fs::read_to_string("schemas/snapshot.schema.json")?)?;
+
+        assert_eq!(published, serde_json::to_value(Snapshot::schema())?);
+        Ok(())
+    }
+
+    #[test]
+    fn expires_writes_that_are_not_confirmed() -> Result<(), PropertyError> {
+        let mut registry = Registry::new();
+        registry.apply(&Event::DiscoveredDevices(vec![test_support::light("1")
+            .named("Ceiling")
+            .on(false)
+            .build()]));
+        let now = Utc::now();
+        let on = HashMap::from([("on".to_string(), PropertyValue::Boolean(true))]);
+
+        assert_eq!(
+            Err(PropertyError::UnknownProperty("dim".to_string())),
+            registry.request(
+                "1",
+                &HashMap::from([("dim".to_string(), PropertyValue::Boolean(true))]),
+                now
+            )
+        );
+        registry.request("1", &on, now)?;
+        assert!(registry
+            .expire_pending(now + Duration::from_secs(5), Duration::from_secs(10))
+            .is_empty());
+        let events =
+            registry.expire_pending(now + Duration::from_secs(10), Duration::from_secs(10));
+
+        assert!(matches!(
+            &events[..],
+            [Event::PropertyWriteFailed { device_id, property, .. }]
+                if device_id == "1" && property == "on"
+        ));
+        assert!(registry.device("1").unwrap().properties()["on"]
+            .pending()
+            .is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,504 @@
+use std::collections::VecDeque;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::time::{interval, sleep_until, Instant as TokioInstant};
+use tracing::{debug, debug_span, error, info, info_span, warn, Instrument};
+
+use chambrier::automation::{
+    AdaptiveLighting, AdaptiveLightingConfig, ButtonBinding, ButtonBindings, Fades, LightHistory,
+    Occupancy, OccupancyConfig, Vacation, VacationConfig,
+};
+use chambrier::command::Command;
+use chambrier::control_server::{ControlRequest, ControlServer};
+use chambrier::event::Event;
+use chambrier::health::DeviceHealth;
+use chambrier::hue::{
+    FakeBridge, HueClient, HueCommandQueue, HueController, HueObserver, Recorder, Recording,
+    ReplayClock,
+};
+use chambrier::metrics::Metrics;
+use chambrier::model::{Presence, PropertyError};
+use chambrier::registry::Registry;
+use chambrier::simulator::{Simulator, SimulatorConfig};
+use chambrier::status_server::StatusServer;
+use chambrier::virtual_devices::{VirtualDeviceConfig, VirtualDevices};
+
+/// How long a light gets to report a value it was set to before the write is considered failed.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs the automations until the events run out, i.e. forever when following a bridge or simulating a house.
+pub async fn serve() -> Result<(), Box<dyn Error>> {
+    let replay = replay()?;
+    // Automations see the time of the recording while replaying, so e.g. adaptive lighting follows the recorded day
+    let clock = replay
+        .as_ref()
+        .map(|(recording, speed)| recording.clock(*speed));
+    let now = || clock.as_ref().map_or_else(Utc::now, ReplayClock::now);
+    let recorder = match env::var("RECORD") {
+        Ok(path) => Some(Recorder::create(path)?),
+        Err(_) => None,
+    };
+    let mut simulator = simulator()?;
+    let fake_bridge = fake_bridge().await?;
+    let metrics = Metrics::new();
+    let health = DeviceHealth::new();
+    // The sender is kept here so that the loop still runs when there is no server to send commands
+    let (external_commands, mut external_receiver) = mpsc::channel(100);
+    let _status_server = status_server(&metrics, &health).await?;
+    let _control_server = control_server(&external_commands).await?;
+    let client = if replay.is_some() || simulator.is_some() {
+        None
+    } else if let Some(bridge) = &fake_bridge {
+        Some(HueClient::with_endpoint(
+            bridge.endpoint(),
+            bridge.application_key(),
+        )?)
+    } else {
+        Some(HueClient::new()?)
+    }
+    .map(|client| client.with_metrics(metrics.clone()))
+    .map(|client| match &recorder {
+        Some(recorder) => client.with_recorder(recorder.clone()),
+        None => client,
+    });
+    // Commands are not executed while replaying, the recorded home is not this one
+    let mut queue = client.clone().map(|client| {
+        HueCommandQueue::new(HueController::new(client)).with_metrics(metrics.clone())
+    });
+    let mut registry = Registry::new();
+    let mut adaptive_lighting = adaptive_lighting_config()?.map(AdaptiveLighting::new);
+    let mut button_bindings = ButtonBindings::new(button_bindings()?);
+    let mut occupancy = Occupancy::new(occupancy_configs()?);
+    let mut vacation = Vacation::new(vacation_config()?, light_history()?);
+    let mut virtual_devices = VirtualDevices::new(virtual_device_configs()?);
+    let mut fades = Fades::new();
+
+    // The loop stops once the sender is gone, which only happens when a replay has ended, so nothing else may keep one
+    let (sender, mut receiver) = mpsc::channel(100);
+    // Events that originate in chambrier itself, handled before the next one is received and in the order they arose
+    let mut feedback = VecDeque::new();
+    feedback.push_back(virtual_devices.discover(&registry));
+    if let Some(presence) = presence()? {
+        info!(?presence, "restoring the presence");
+        feedback.push_back(Event::PresenceChanged(presence));
+    }
+    if let Some(simulator) = simulator.as_ref() {
+        info!("simulating a house");
+        feedback.extend(simulator.discover());
+    } else if let (Some((recording, speed)), Some(clock)) = (replay, clock.clone()) {
+        info!(speed, "replaying a recording of Philips Hue");
+        tokio::spawn(
+            async move {
+                match recording.replay(sender, &clock).await {
+                    Ok(()) => info!("the recording has ended"),
+                    Err(e) => error!(error = %e, "replaying failed"),
+                }
+            }
+            .instrument(info_span!("integration", name = "replay")),
+        );
+    } else if let Some(client) = client {
+        info!("retrieving devices from Philips Hue");
+        let mut observer = HueObserver::new(client);
+        if let Some(recorder) = recorder {
+            observer = observer.with_recorder(recorder);
+        }
+        tokio::spawn(async move {
+            if let Err(e) = observer.observe(sender).await {
+                error!(error = %e, "observing Philips Hue failed");
+            }
+        });
+    }
+
+    let mut adaptive_lighting_ticks = interval(
+        adaptive_lighting
+            .as_ref()
+            .map_or(Duration::from_secs(300), |a| a.interval()),
+    );
+    let mut ticks = interval(Duration::from_millis(100));
+    loop {
+        let ready_at = queue.as_ref().and_then(HueCommandQueue::ready_at);
+        let commands = tokio::select! {
+            event = next_event(&mut feedback, &mut receiver) => {
+                let Some(event) = event else {
+                    info!("no more events, stopping");
+                    break;
+                };
+                let span = info_span!("event", kind = event.kind());
+                let _entered = span.enter();
+                debug!(?event, "received");
+                registry.apply(&event);
+                if let Event::PresenceChanged(presence) = &event {
+                    if let Err(e) = save_presence(*presence) {
+                        error!(error = %e, "saving the presence failed");
+                    }
+                }
+                metrics.handle_event(&event, &registry);
+                health.handle_event(&event, now());
+                if let Some(queue) = queue.as_mut() {
+                    queue.handle_event(&event);
+                }
+                fades.handle_event(&event);
+                feedback.extend(virtual_devices.handle_event(&event, &registry));
+
+                let now = now();
+                let mut commands = run_automation(&metrics, "button_bindings", || {
+                    button_bindings.handle_event(&event, &registry, now)
+                });
+                commands.extend(run_automation(&metrics, "occupancy", || {
+                    occupancy.handle_event(&event, &registry, now)
+                }));
+                let history_length = vacation.history().entries().len();
+                commands.extend(run_automation(&metrics, "vacation", || {
+                    vacation.handle_event(&event, &registry, now)
+                }));
+                if vacation.history().entries().len() != history_length {
+                    if let Err(e) = save_light_history(vacation.history()) {
+                        error!(error = %e, "saving the light history failed");
+                    }
+                }
+                if let Some(adaptive_lighting) = adaptive_lighting.as_mut() {
+                    commands.extend(run_automation(&metrics, "adaptive_lighting", || {
+                        adaptive_lighting.handle_event(&event, now)
+                    }));
+                }
+                commands
+            }
+            Some(request) = external_receiver.recv() => {
+                let command = request.command().clone();
+                debug!(?command, "received from the control server");
+                let result = validate(&command, &registry);
+                let accepted = result.is_ok();
+                request.reply(result);
+                if accepted { vec![command] } else { vec![] }
+            }
+            _ = adaptive_lighting_ticks.tick() => {
+                adaptive_lighting
+                    .as_mut()
+                    .map(|a| {
+                        run_automation(&metrics, "adaptive_lighting", || a.tick(now()))
+                    })
+                    .unwrap_or_default()
+            }
+            _ = ticks.tick() => {
+                let now = now();
+                let mut commands = run_automation(&metrics, "button_bindings", || {
+                    button_bindings.poll(&registry, now)
+                });
+                commands.extend(run_automation(&metrics, "occupancy", || {
+                    occupancy.tick(&registry, now)
+                }));
+                commands.extend(run_automation(&metrics, "vacation", || {
+                    vacation.tick(&registry, now)
+                }));
+                commands.extend(run_automation(&metrics, "fades", || fades.tick(now)));
+                feedback.extend(simulator.as_mut().map(|s| s.tick(now)).unwrap_or_default());
+                for event in registry.expire_pending(now, WRITE_TIMEOUT) {
+                    warn!(?event, "a write was not confirmed");
+                    feedback.push_back(event);
+                }
+                commands
+            }
+            // Sending is not part of the branch, a request must not be abandoned when another branch is ready first
+            _ = sleep_until(ready_at.unwrap_or_else(TokioInstant::now)), if ready_at.is_some() => {
+                let sent = match queue.as_mut() {
+                    Some(queue) => queue.send_next().await,
+                    None => None,
+                };
+                if let Some((commands, result)) = sent {
+                    for command in commands {
+                        metrics.command_executed(&command, result.is_ok());
+                        if let Err(e) = &result {
+                            warn!(?command, error = %e, "executing a command failed");
+                            feedback.extend(write_failures(&command, &e.to_string()));
+                        }
+                    }
+                }
+                vec![]
+            }
+        };
+
+        for command in start_fades(&mut fades, commands, &registry, now(), &metrics) {
+            if let Command::SetPresence(presence) = command {
+                feedback.push_back(Event::PresenceChanged(presence));
+                continue;
+            }
+            match virtual_devices.execute(&command, &registry) {
+                Some(Ok(event)) => {
+                    feedback.push_back(event);
+                    continue;
+                }
+                Some(Err(e)) => {
+                    metrics.command_executed(&command, false);
+                    warn!(?command, error = %e, "rejected an invalid command");
+                    continue;
+                }
+                None => {}
+            }
+            if simulator.is_none() && queue.is_none() {
+                info!(?command, "not executing a command during replay");
+                continue;
+            }
+            if let Command::ControlDevice {
+                device_id,
+                properties,
+                ..
+            } = &command
+            {
+                if let Err(e) = registry.request(device_id, properties, now()) {
+                    metrics.command_executed(&command, false);
+                    warn!(?command, error = %e, "rejected an invalid command");
+                    continue;
+                }
+            }
+            // Commands for the bridge wait in the queue, their results are reported once they are sent
+            let Some(simulator) = simulator.as_mut() else {
+                if let Some(queue) = queue.as_mut() {
+                    debug!(?command, "queued");
+                    queue.push(command);
+                }
+                continue;
+            };
+            let result = simulator
+                .execute(&command, now())
+                .map_err(|e| e.to_string());
+            metrics.command_executed(&command, result.is_ok());
+            if let Err(e) = result {
+                warn!(?command, error = %e, "executing a command failed");
+                feedback.extend(write_failures(&command, &e));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the commands that start a fade with its first step, the later steps are sent as the fades tick.
+fn start_fades(
+    fades: &mut Fades,
+    commands: Vec<Command>,
+    registry: &Registry,
+    now: DateTime<Utc>,
+    metrics: &Metrics,
+) -> Vec<Command> {
+    let mut started = vec![];
+    for command in commands {
+        let Command::StartFade {
+            device_id,
+            targets,
+            duration,
+        } = &command
+        else {
+            started.push(command);
+            continue;
+        };
+        match fades.start(device_id, targets.clone(), *duration, registry, now) {
+            Ok(steps) => {
+                metrics.command_executed(&command, true);
+                started.extend(steps);
+            }
+            Err(e) => {
+                metrics.command_executed(&command, false);
+                warn!(?command, error = %e, "rejected an invalid fade");
+            }
+        }
+    }
+    started
+}
+
+/// The writes of a command that failed, so that they are no longer pending.
+fn write_failures(command: &Command, error: &str) -> Vec<Event> {
+    let Command::ControlDevice {
+        device_id,
+        properties,
+        ..
+    } = command
+    else {
+        return vec![];
+    };
+    properties
+        .keys()
+        .map(|property| Event::PropertyWriteFailed {
+            device_id: device_id.clone(),
+            property: property.clone(),
+            error: error.to_string(),
+        })
+        .collect()
+}
+
+/// Runs an automation in a span of its own, so its logs and the commands it issued can be told apart.
+fn run_automation(
+    metrics: &Metrics,
+    name: &'static str,
+    run: impl FnOnce() -> Vec<Command>,
+) -> Vec<Command> {
+    let span = debug_span!("automation", name);
+    let _entered = span.enter();
+    let start = Instant::now();
+    let commands = run();
+    metrics.automation_ran(name, start.elapsed(), commands.len());
+    if !commands.is_empty() {
+        debug!(?commands, "issued commands");
+    }
+    commands
+}
+
+/// Serves metrics for Prometheus and the health summary at `METRICS_ADDRESS`, e.g. `0.0.0.0:9091`, if set.
+async fn status_server(
+    metrics: &Metrics,
+    health: &DeviceHealth,
+) -> Result<Option<StatusServer>, Box<dyn Error>> {
+    let Ok(address) = env::var("METRICS_ADDRESS") else {
+        return Ok(None);
+    };
+    let server = StatusServer::start(address.parse()?, metrics.clone(), health.clone()).await?;
+    info!(address = %server.address(), "serving metrics and health");
+    Ok(Some(server))
+}
+
+/// Accepts the presence and controls devices, virtual switches included, at `CONTROL_ADDRESS`, e.g. `127.0.0.1:9092`,
+/// if set. Nothing is served without it, as anyone who can reach the address can change the house.
+async fn control_server(
+    commands: &Sender<ControlRequest>,
+) -> Result<Option<ControlServer>, Box<dyn Error>> {
+    let Ok(address) = env::var("CONTROL_ADDRESS") else {
+        return Ok(None);
+    };
+    let server = ControlServer::start(address.parse()?, commands.clone()).await?;
+    info!(address = %server.address(), "accepting control requests");
+    Ok(Some(server))
+}
+
+/// Checks a command from the control server before it is accepted, so that the client learns about invalid writes.
+fn validate(command: &Command, registry: &Registry) -> Result<(), PropertyError> {
+    match command {
+        Command::ControlDevice {
+            device_id,
+            properties,
+            ..
+        } => registry.validate(device_id, properties),
+        _ => Ok(()),
+    }
+}
+
+/// The next event that originates in chambrier itself, or else the next one received. Returns right away when there
+/// is one of the former, so it is never lost to another branch of the loop.
+async fn next_event(
+    feedback: &mut VecDeque<Event>,
+    receiver: &mut Receiver<Event>,
+) -> Option<Event> {
+    match feedback.pop_front() {
+        Some(event) => Some(event),
+        None => receiver.recv().await,
+    }
+}
+
+/// Replays the recording `REPLAY` points to instead of observing the bridge, at `REPLAY_SPEED` times real time.
+fn replay() -> Result<Option<(Recording, f64)>, Box<dyn Error>> {
+    let Ok(path) = env::var("REPLAY") else {
+        return Ok(None);
+    };
+    let speed = match env::var("REPLAY_SPEED") {
+        Ok(speed) => speed.parse()?,
+        Err(_) => 1.0,
+    };
+    Ok(Some((Recording::load(path)?, speed)))
+}
+
+/// Simulates the house described by the JSON file `SIMULATOR` points to instead of using a bridge.
+fn simulator() -> Result<Option<Simulator>, Box<dyn Error>> {
+    let Ok(path) = env::var("SIMULATOR") else {
+        return Ok(None);
+    };
+    let config: SimulatorConfig = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(Some(Simulator::new(config)))
+}
+
+/// Runs against a fake bridge serving the fixture `FAKE_BRIDGE` points to, for development without a bridge at hand.
+async fn fake_bridge() -> Result<Option<FakeBridge>, Box<dyn Error>> {
+    let Ok(path) = env::var("FAKE_BRIDGE") else {
+        return Ok(None);
+    };
+    Ok(Some(FakeBridge::start(&fs::read_to_string(path)?).await?))
+}
+
+/// Adaptive lighting is enabled by listing the devices and rooms in `ADAPTIVE_LIGHTING`, separated by commas.
+fn adaptive_lighting_config() -> Result<Option<AdaptiveLightingConfig>, Box<dyn Error>> {
+    let Ok(targets) = env::var("ADAPTIVE_LIGHTING") else {
+        return Ok(None);
+    };
+    let latitude = env::var("LATITUDE")?.parse()?;
+    let longitude = env::var("LONGITUDE")?.parse()?;
+    let targets = targets.split(',').map(|t| t.trim().to_string()).collect();
+
+    Ok(Some(AdaptiveLightingConfig::new(
+        latitude, longitude, targets,
+    )))
+}
+
+/// Button bindings are read from the JSON file `BUTTON_BINDINGS` points to.
+fn button_bindings() -> Result<Vec<ButtonBinding>, Box<dyn Error>> {
+    let Ok(path) = env::var("BUTTON_BINDINGS") else {
+        return Ok(vec![]);
+    };
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Motion-activated rooms are read from the JSON file `OCCUPANCY` points to.
+fn occupancy_configs() -> Result<Vec<OccupancyConfig>, Box<dyn Error>> {
+    let Ok(path) = env::var("OCCUPANCY") else {
+        return Ok(vec![]);
+    };
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Vacation mode is configured by the JSON file `VACATION` points to, by default it replays every light.
+fn vacation_config() -> Result<VacationConfig, Box<dyn Error>> {
+    let Ok(path) = env::var("VACATION") else {
+        return Ok(VacationConfig::new());
+    };
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// The light history is kept in the JSON file `LIGHT_HISTORY` points to, if set, so it survives restarts.
+fn light_history() -> Result<LightHistory, Box<dyn Error>> {
+    match env::var("LIGHT_HISTORY").map(fs::read_to_string) {
+        Ok(Ok(history)) => Ok(serde_json::from_str(&history)?),
+        _ => Ok(LightHistory::default()),
+    }
+}
+
+fn save_light_history(history: &LightHistory) -> Result<(), Box<dyn Error>> {
+    if let Ok(path) = env::var("LIGHT_HISTORY") {
+        fs::write(path, serde_json::to_string(history)?)?;
+    }
+    Ok(())
+}
+
+/// The last presence is kept in the JSON file `PRESENCE` points to, if set, so that a restart while away does not
+/// end vacation mode.
+fn presence() -> Result<Option<Presence>, Box<dyn Error>> {
+    match env::var("PRESENCE").map(fs::read_to_string) {
+        Ok(Ok(presence)) => Ok(Some(serde_json::from_str(&presence)?)),
+        _ => Ok(None),
+    }
+}
+
+fn save_presence(presence: Presence) -> Result<(), Box<dyn Error>> {
+    if let Ok(path) = env::var("PRESENCE") {
+        fs::write(path, serde_json::to_string(&presence)?)?;
+    }
+    Ok(())
+}
+
+/// Virtual switches and template sensors are read from the JSON file `VIRTUAL_DEVICES` points to.
+fn virtual_device_configs() -> Result<Vec<VirtualDeviceConfig>, Box<dyn Error>> {
+    let Ok(path) = env::var("VIRTUAL_DEVICES") else {
+        return Ok(vec![]);
+    };
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
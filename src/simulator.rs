@@ -0,0 +1,859 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveTime, Timelike, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::model::{
+    Availability, BooleanProperty, Device, DeviceType, NumberProperty, Property, PropertyType,
+    PropertyValue, Room, Scene, Unit,
+};
+
+/// The time a light takes to report a change, like a bridge does.
+const RESPONSE_DELAY: Duration = Duration::from_millis(200);
+
+/// The transition the bridge uses when none is given.
+const DEFAULT_TRANSITION: Duration = Duration::from_millis(400);
+
+/// How long a motion sensor keeps reporting motion after it fired.
+const MOTION_DURATION: Duration = Duration::from_secs(30);
+
+const MANUFACTURER: &str = "chambrier";
+
+/// The scenes every simulated room has, with the brightness and colour temperature (mirek) they set.
+const SCENES: [(&str, usize, usize); 2] = [("Bright", 100, 233), ("Relax", 56, 447)];
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SimulatedRoom {
+    name: String,
+    #[serde(default = "default_lights")]
+    lights: usize,
+    #[serde(default)]
+    motion_sensor: bool,
+}
+
+fn default_lights() -> usize {
+    1
+}
+
+impl SimulatedRoom {
+    pub fn new(name: String, lights: usize, motion_sensor: bool) -> Self {
+        SimulatedRoom {
+            name,
+            lights,
+            motion_sensor,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SimulatorConfig {
+    rooms: Vec<SimulatedRoom>,
+    /// The average time between motion in a room.
+    #[serde(default = "default_motion_interval_seconds")]
+    motion_interval_seconds: u64,
+    /// Temperatures follow a daily curve, from the minimum at 4:00 to the maximum at 16:00 local time.
+    #[serde(default = "default_minimum_temperature")]
+    minimum_temperature: f64,
+    #[serde(default = "default_maximum_temperature")]
+    maximum_temperature: f64,
+    /// The battery percentage a sensor loses per day.
+    #[serde(default = "default_battery_drain")]
+    battery_drain: f64,
+    /// How often a device becomes unreachable per day, on average.
+    #[serde(default = "default_outages")]
+    outages: f64,
+}
+
+fn default_motion_interval_seconds() -> u64 {
+    900
+}
+
+fn default_minimum_temperature() -> f64 {
+    17.0
+}
+
+fn default_maximum_temperature() -> f64 {
+    22.0
+}
+
+fn default_battery_drain() -> f64 {
+    0.5
+}
+
+fn default_outages() -> f64 {
+    0.1
+}
+
+impl SimulatorConfig {
+    pub fn new(rooms: Vec<SimulatedRoom>) -> Self {
+        SimulatorConfig {
+            rooms,
+            motion_interval_seconds: default_motion_interval_seconds(),
+            minimum_temperature: default_minimum_temperature(),
+            maximum_temperature: default_maximum_temperature(),
+            battery_drain: default_battery_drain(),
+            outages: default_outages(),
+        }
+    }
+
+    pub fn with_motion_interval(mut self, interval: Duration) -> Self {
+        self.motion_interval_seconds = interval.as_secs().max(1);
+        self
+    }
+
+    pub fn with_temperatures(mut self, minimum: f64, maximum: f64) -> Self {
+        self.minimum_temperature = minimum;
+        self.maximum_temperature = maximum.max(minimum);
+        self
+    }
+
+    pub fn with_battery_drain(mut self, percentage_per_day: f64) -> Self {
+        self.battery_drain = percentage_per_day;
+        self
+    }
+
+    pub fn with_outages(mut self, per_day: f64) -> Self {
+        self.outages = per_day;
+        self
+    }
+
+    /// The temperature at `time` of day, to a tenth of a degree like real sensors report it.
+    fn temperature_at(&self, time: NaiveTime) -> f64 {
+        let hours = time.num_seconds_from_midnight() as f64 / 3600.0;
+        let factor = (1.0 - (2.0 * PI * (hours - 4.0) / 24.0).cos()) / 2.0;
+        let range = self.maximum_temperature - self.minimum_temperature;
+        ((self.minimum_temperature + range * factor) * 10.0).round() / 10.0
+    }
+}
+
+/// A virtual house to develop and demo automations without hardware. It takes the place of the bridge: it reports its
+/// devices, applies commands with the delays real lights have, and makes its sensors report motion, temperature and
+/// battery levels over time. Now and then a device becomes unreachable for a few minutes and rejects commands.
+pub struct Simulator {
+    config: SimulatorConfig,
+    devices: HashMap<String, Device>,
+    rooms: Vec<Room>,
+    scenes: Vec<Scene>,
+    pending: Vec<PendingChange>,
+    sensors: HashMap<String, SensorState>,
+    unreachable_until: HashMap<String, DateTime<Utc>>,
+    last_tick: Option<DateTime<Utc>>,
+    rng: StdRng,
+}
+
+struct PendingChange {
+    at: DateTime<Utc>,
+    device_id: String,
+    name: String,
+    value: PropertyValue,
+}
+
+struct SensorState {
+    next_motion: Option<DateTime<Utc>>,
+    motion_until: Option<DateTime<Utc>>,
+    battery: f64,
+}
+
+impl Simulator {
+    pub fn new(config: SimulatorConfig) -> Self {
+        Simulator::with_rng(config, StdRng::from_entropy())
+    }
+
+    /// A simulator that behaves the same on every run.
+    pub fn with_seed(config: SimulatorConfig, seed: u64) -> Self {
+        Simulator::with_rng(config, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(config: SimulatorConfig, rng: StdRng) -> Self {
+        let mut devices = HashMap::new();
+        let mut rooms = vec![];
+        let mut scenes = vec![];
+        let mut sensors = HashMap::new();
+        for simulated in &config.rooms {
+            let room_id = slug(&simulated.name);
+            let mut device_ids = vec![];
+            for number in 1..=simulated.lights {
+                let light = light(
+                    format!("{}-light-{}", room_id, number),
+                    format!("{} light {}", simulated.name, number),
+                );
+                device_ids.push(light.id().clone());
+                devices.insert(light.id().clone(), light);
+            }
+            if simulated.motion_sensor {
+                let sensor = motion_sensor(
+                    format!("{}-sensor", room_id),
+                    format!("{} sensor", simulated.name),
+                    config.minimum_temperature,
+                );
+                device_ids.push(sensor.id().clone());
+                sensors.insert(
+                    sensor.id().clone(),
+                    SensorState {
+                        next_motion: None,
+                        motion_until: None,
+                        battery: 100.0,
+                    },
+                );
+                devices.insert(sensor.id().clone(), sensor);
+            }
+            for (name, _, _) in SCENES {
+                scenes.push(Scene::new(
+                    format!("{}-{}", room_id, slug(name)),
+                    name.to_string(),
+                    Some(room_id.clone()),
+                ));
+            }
+            rooms.push(Room::new(room_id, simulated.name.clone(), device_ids));
+        }
+
+        Simulator {
+            config,
+            devices,
+            rooms,
+            scenes,
+            pending: vec![],
+            sensors,
+            unreachable_until: HashMap::new(),
+            last_tick: None,
+            rng,
+        }
+    }
+
+    /// The devices, rooms and scenes of the house, like the observer sends them for a bridge.
+    pub fn discover(&self) -> Vec<Event> {
+        let mut devices: Vec<Device> = self.devices.values().cloned().collect();
+        devices.sort_by(|a, b| a.id().cmp(b.id()));
+        vec![
+            Event::DiscoveredDevices(devices),
+            Event::DiscoveredRooms(self.rooms.clone()),
+            Event::DiscoveredScenes(self.scenes.clone()),
+        ]
+    }
+
+    pub fn is_reachable(&self, device_id: &str, now: DateTime<Utc>) -> bool {
+        self.unreachable_until
+            .get(device_id)
+            .is_none_or(|until| now >= *until)
+    }
+
+    /// Applies a command. The changes are reported by [`Simulator::tick`] once the lights would report them.
+    pub fn execute(&mut self, command: &Command, now: DateTime<Utc>) -> Result<(), SimulatorError> {
+        match command {
+            Command::ControlDevice {
+                device_id,
+                properties,
+                transition,
+            } => self.control(device_id, properties, *transition, now),
+            Command::RecallScene {
+                scene_id,
+                transition,
+            } => {
+                let scene = self
+                    .scenes
+                    .iter()
+                    .find(|scene| scene.id() == scene_id)
+                    .ok_or_else(|| SimulatorError::UnknownScene(scene_id.clone()))?;
+                let (_, brightness, mirek) = SCENES
+                    .iter()
+                    .find(|(name, _, _)| name == scene.name())
+                    .copied()
+                    .ok_or_else(|| SimulatorError::UnknownScene(scene_id.clone()))?;
+                let properties = HashMap::from([
+                    ("on".to_string(), PropertyValue::Boolean(true)),
+                    (
+                        "brightness".to_string(),
+                        PropertyValue::Number(Some(brightness as f64)),
+                    ),
+                    (
+                        "color_temperature".to_string(),
+                        PropertyValue::Number(Some(mirek as f64)),
+                    ),
+                ]);
+                let device_ids = self
+                    .rooms
+                    .iter()
+                    .find(|room| Some(room.id()) == scene.room_id())
+                    .map(|room| room.device_ids().clone())
+                    .unwrap_or_default();
+                for device_id in device_ids {
+                    let is_light = self
+                        .devices
+                        .get(&device_id)
+                        .is_some_and(|d| matches!(d.device_type(), DeviceType::Light));
+                    if is_light && self.is_reachable(&device_id, now) {
+                        self.control(&device_id, &properties, *transition, now)?;
+                    }
+                }
+                Ok(())
+            }
+            // Breathing changes none of the properties, so there is nothing to report
+            Command::Alert { device_id } => {
+                let device = self
+                    .devices
+                    .get(device_id)
+                    .ok_or_else(|| SimulatorError::UnknownDevice(device_id.clone()))?;
+                if !matches!(device.device_type(), DeviceType::Light) {
+                    return Err(SimulatorError::NotALight(device_id.clone()));
+                }
+                if !self.is_reachable(device_id, now) {
+                    return Err(SimulatorError::Unreachable(device_id.clone()));
+                }
+                Ok(())
+            }
+            Command::SetPresence(_) | Command::StartFade { .. } => Ok(()),
+            // The simulated devices do not report firmware, so there is never an update to install
+            Command::InstallUpdate { device_id } => match self.devices.get(device_id) {
+                Some(_) => Err(SimulatorError::NoUpdateReady(device_id.clone())),
+                None => Err(SimulatorError::UnknownDevice(device_id.clone())),
+            },
+        }
+    }
+
+    /// Reports the changes that are due: lights finishing their transitions, motion, temperature and batteries.
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        let elapsed = self
+            .last_tick
+            .and_then(|last| (now - last).to_std().ok())
+            .unwrap_or_default();
+        self.last_tick = Some(now);
+
+        let mut events = self.pending_changes(now);
+        events.extend(self.simulate_outages(elapsed, now));
+        let mut sensor_ids: Vec<String> = self.sensors.keys().cloned().collect();
+        sensor_ids.sort();
+        for sensor_id in sensor_ids {
+            events.extend(self.simulate_sensor(&sensor_id, elapsed, now));
+        }
+        events
+    }
+
+    fn control(
+        &mut self,
+        device_id: &str,
+        properties: &HashMap<String, PropertyValue>,
+        transition: Option<Duration>,
+        now: DateTime<Utc>,
+    ) -> Result<(), SimulatorError> {
+        let device = self
+            .devices
+            .get(device_id)
+            .ok_or_else(|| SimulatorError::UnknownDevice(device_id.to_string()))?;
+        if !self.is_reachable(device_id, now) {
+            return Err(SimulatorError::Unreachable(device_id.to_string()));
+        }
+        if let Some(name) = properties.keys().find(|name| {
+            device
+                .properties()
+                .get(*name)
+                .is_none_or(Property::readonly)
+        }) {
+            return Err(SimulatorError::UnsupportedProperty(name.clone()));
+        }
+
+        // On and off is reported right away, the other properties once the transition has finished
+        let transition = transition.unwrap_or(DEFAULT_TRANSITION).max(RESPONSE_DELAY);
+        for (name, value) in properties {
+            let delay = if name == "on" {
+                RESPONSE_DELAY
+            } else {
+                transition
+            };
+            self.pending
+                .retain(|p| p.device_id != device_id || p.name != *name);
+            self.pending.push(PendingChange {
+                at: now + delay,
+                device_id: device_id.to_string(),
+                name: name.clone(),
+                value: value.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn pending_changes(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        let (due, pending): (Vec<PendingChange>, Vec<PendingChange>) =
+            self.pending.drain(..).partition(|p| p.at <= now);
+        self.pending = pending;
+
+        let mut changed: Vec<(String, HashMap<String, PropertyValue>)> = vec![];
+        for change in due {
+            let Some(device) = self.devices.get_mut(&change.device_id) else {
+                continue;
+            };
+            if device.properties().get(&change.name).map(|p| p.value())
+                == Some(change.value.clone())
+            {
+                continue;
+            }
+            device.update_property(&change.name, &change.value);
+            match changed.iter_mut().find(|(id, _)| *id == change.device_id) {
+                Some((_, properties)) => {
+                    properties.insert(change.name, change.value);
+                }
+                None => changed.push((
+                    change.device_id,
+                    HashMap::from([(change.name, change.value)]),
+                )),
+            }
+        }
+
+        changed
+            .into_iter()
+            .map(|(device_id, properties)| Event::PropertiesChanged {
+                device_id,
+                properties,
+            })
+            .collect()
+    }
+
+    /// Makes devices unreachable at random and reports the ones that recovered.
+    fn simulate_outages(&mut self, elapsed: Duration, now: DateTime<Utc>) -> Vec<Event> {
+        let mut recovered: Vec<String> = self
+            .unreachable_until
+            .iter()
+            .filter(|(_, until)| now >= **until)
+            .map(|(device_id, _)| device_id.clone())
+            .collect();
+        recovered.sort();
+        let mut events = vec![];
+        for device_id in recovered {
+            self.unreachable_until.remove(&device_id);
+            events.push(Event::AvailabilityChanged {
+                device_id,
+                availability: Availability::Available,
+            });
+        }
+
+        let chance = self.config.outages * elapsed.as_secs_f64() / 86_400.0;
+        let mut device_ids: Vec<String> = self.devices.keys().cloned().collect();
+        device_ids.sort();
+        for device_id in device_ids {
+            if self.is_reachable(&device_id, now) && self.rng.gen_bool(chance.clamp(0.0, 1.0)) {
+                let minutes = self.rng.gen_range(1..=10);
+                self.unreachable_until
+                    .insert(device_id.clone(), now + Duration::from_secs(minutes * 60));
+                events.push(Event::AvailabilityChanged {
+                    device_id,
+                    availability: Availability::Unavailable,
+                });
+            }
+        }
+        events
+    }
+
+    fn simulate_sensor(
+        &mut self,
+        sensor_id: &str,
+        elapsed: Duration,
+        now: DateTime<Utc>,
+    ) -> Vec<Event> {
+        let reachable = self.is_reachable(sensor_id, now);
+        let mean_interval = self.config.motion_interval_seconds as f64;
+        let next_motion =
+            now + Duration::from_secs_f64(self.rng.gen_range(0.0..2.0 * mean_interval));
+        let temperature = self.config.temperature_at(now.with_timezone(&Local).time());
+        let drain = self.config.battery_drain * elapsed.as_secs_f64() / 86_400.0;
+        let Some(state) = self.sensors.get_mut(sensor_id) else {
+            return vec![];
+        };
+
+        let mut properties = HashMap::new();
+        let motion = match (state.next_motion, state.motion_until) {
+            (None, _) => {
+                state.next_motion = Some(next_motion);
+                None
+            }
+            (Some(at), _) if now >= at && reachable => {
+                state.next_motion = Some(next_motion);
+                state.motion_until = Some(now + MOTION_DURATION);
+                Some(true)
+            }
+            (_, Some(until)) if now >= until => {
+                state.motion_until = None;
+                Some(false)
+            }
+            _ => None,
+        };
+        if let Some(motion) = motion {
+            properties.insert("motion".to_string(), PropertyValue::Boolean(motion));
+        }
+        state.battery = (state.battery - drain).max(0.0);
+        let battery = state.battery.ceil();
+
+        let Some(device) = self.devices.get_mut(sensor_id) else {
+            return vec![];
+        };
+        for (name, value) in [
+            ("temperature", PropertyValue::Number(Some(temperature))),
+            ("battery_level", PropertyValue::Number(Some(battery))),
+        ] {
+            if reachable && device.properties().get(name).map(|p| p.value()) != Some(value.clone())
+            {
+                properties.insert(name.to_string(), value);
+            }
+        }
+        for (name, value) in &properties {
+            device.update_property(name, value);
+        }
+
+        if properties.is_empty() {
+            vec![]
+        } else {
+            vec![Event::PropertiesChanged {
+                device_id: sensor_id.to_string(),
+                properties,
+            }]
+        }
+    }
+}
+
+fn slug(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn light(id: String, name: String) -> Device {
+    let properties = HashMap::from([
+        (
+            "on".to_string(),
+            Property::Boolean(BooleanProperty::new(
+                "on".to_string(),
+                false,
+                PropertyType::On,
+                None,
+                false,
+            )),
+        ),
+        (
+            "brightness".to_string(),
+            Property::Number(NumberProperty::new(
+                "brightness".to_string(),
+                false,
+                PropertyType::Brightness,
+                None,
+                Unit::Percentage,
+                Some(100.0),
+                Some(1.0),
+                Some(100.0),
+            )),
+        ),
+        (
+            "color_temperature".to_string(),
+            Property::Number(NumberProperty::new(
+                "color_temperature".to_string(),
+                false,
+                PropertyType::ColorTemperature,
+                None,
+                Unit::Mirek,
+                Some(366.0),
+                Some(153.0),
+                Some(500.0),
+            )),
+        ),
+    ]);
+    Device::new(
+        id,
+        DeviceType::Light,
+        MANUFACTURER.to_string(),
+        "simulated_light".to_string(),
+        "Simulated light".to_string(),
+        name,
+        properties,
+        None,
+    )
+}
+
+fn motion_sensor(id: String, name: String, temperature: f64) -> Device {
+    let properties = HashMap::from([
+        (
+            "motion".to_string(),
+            Property::Boolean(BooleanProperty::new(
+                "motion".to_string(),
+                true,
+                PropertyType::Motion,
+                None,
+                false,
+            )),
+        ),
+        (
+            "temperature".to_string(),
+            Property::Number(
+                NumberProperty::new(
+                    "temperature".to_string(),
+                    true,
+                    PropertyType::Temperature,
+                    None,
+                    Unit::Celcius,
+                    Some(temperature),
+                    None,
+                    None,
+                )
+                .with_step(0.1),
+            ),
+        ),
+        (
+            "battery_level".to_string(),
+            Property::Number(NumberProperty::new(
+                "battery_level".to_string(),
+                true,
+                PropertyType::BatteryLevel,
+                None,
+                Unit::Percentage,
+                Some(100.0),
+                Some(0.0),
+                Some(100.0),
+            )),
+        ),
+    ]);
+    Device::new(
+        id,
+        DeviceType::Sensor,
+        MANUFACTURER.to_string(),
+        "simulated_motion_sensor".to_string(),
+        "Simulated motion sensor".to_string(),
+        name,
+        properties,
+        None,
+    )
+}
+
+#[derive(Error, Debug)]
+pub enum SimulatorError {
+    #[error("unknown device '{0}'")]
+    UnknownDevice(String),
+    #[error("unknown scene '{0}'")]
+    UnknownScene(String),
+    #[error("device '{0}' is unreachable")]
+    Unreachable(String),
+    #[error("property '{0}' cannot be controlled")]
+    UnsupportedProperty(String),
+    #[error("device '{0}' is not a light")]
+    NotALight(String),
+    #[error("device '{0}' has no update ready to install")]
+    NoUpdateReady(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn simulator(config: SimulatorConfig) -> Simulator {
+        Simulator::with_seed(config.with_outages(0.0), 42)
+    }
+
+    fn house() -> SimulatorConfig {
+        SimulatorConfig::new(vec![
+            SimulatedRoom::new("Living room".to_string(), 2, true),
+            SimulatedRoom::new("Hallway".to_string(), 1, false),
+        ])
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 15, 12, 0, 0).unwrap()
+    }
+
+    fn changed(events: &[Event], device_id: &str, name: &str) -> Option<PropertyValue> {
+        events.iter().find_map(|event| match event {
+            Event::PropertiesChanged {
+                device_id: id,
+                properties,
+            } if id == device_id => properties.get(name).cloned(),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn discovers_the_house() {
+        let events = simulator(house()).discover();
+
+        match &events[..] {
+            [Event::DiscoveredDevices(devices), Event::DiscoveredRooms(rooms), Event::DiscoveredScenes(scenes)] =>
+            {
+                assert_eq!(4, devices.len());
+                assert_eq!(2, rooms.len());
+                assert_eq!(4, scenes.len());
+                let living_room = rooms.iter().find(|r| r.id() == "living-room").unwrap();
+                assert_eq!(
+                    &vec![
+                        "living-room-light-1".to_string(),
+                        "living-room-light-2".to_string(),
+                        "living-room-sensor".to_string()
+                    ],
+                    living_room.device_ids()
+                );
+            }
+            events => panic!("Unexpected events {:?}", events),
+        }
+    }
+
+    #[test]
+    fn reports_changes_after_the_transition() {
+        let mut simulator = simulator(house());
+        simulator.tick(now());
+        let command = Command::ControlDevice {
+            device_id: "hallway-light-1".to_string(),
+            properties: HashMap::from([
+                ("on".to_string(), PropertyValue::Boolean(true)),
+                ("brightness".to_string(), PropertyValue::Number(Some(40.0))),
+            ]),
+            transition: Some(Duration::from_secs(2)),
+        };
+
+        simulator.execute(&command, now()).unwrap();
+
+        assert!(simulator.tick(now()).is_empty());
+        let events = simulator.tick(now() + RESPONSE_DELAY);
+        assert_eq!(
+            Some(PropertyValue::Boolean(true)),
+            changed(&events, "hallway-light-1", "on")
+        );
+        assert_eq!(None, changed(&events, "hallway-light-1", "brightness"));
+        let events = simulator.tick(now() + Duration::from_secs(2));
+        assert_eq!(
+            Some(PropertyValue::Number(Some(40.0))),
+            changed(&events, "hallway-light-1", "brightness")
+        );
+    }
+
+    #[test]
+    fn recalls_a_scene() {
+        let mut simulator = simulator(house());
+        let command = Command::RecallScene {
+            scene_id: "living-room-relax".to_string(),
+            transition: None,
+        };
+
+        simulator.execute(&command, now()).unwrap();
+
+        let events = simulator.tick(now() + DEFAULT_TRANSITION);
+        for light in ["living-room-light-1", "living-room-light-2"] {
+            assert_eq!(
+                Some(PropertyValue::Number(Some(56.0))),
+                changed(&events, light, "brightness")
+            );
+        }
+        assert_eq!(None, changed(&events, "hallway-light-1", "on"));
+    }
+
+    #[test]
+    fn rejects_readonly_properties() {
+        let mut simulator = simulator(house());
+        let command = Command::ControlDevice {
+            device_id: "living-room-sensor".to_string(),
+            properties: HashMap::from([("motion".to_string(), PropertyValue::Boolean(true))]),
+            transition: None,
+        };
+
+        let result = simulator.execute(&command, now());
+
+        assert!(matches!(
+            result,
+            Err(SimulatorError::UnsupportedProperty(_))
+        ));
+    }
+
+    #[test]
+    fn fires_motion_and_clears_it() {
+        let mut simulator = simulator(house().with_motion_interval(Duration::from_secs(60)));
+        simulator.tick(now());
+
+        let mut motion = vec![];
+        for second in 1..=600 {
+            let events = simulator.tick(now() + Duration::from_secs(second));
+            if let Some(PropertyValue::Boolean(value)) =
+                changed(&events, "living-room-sensor", "motion")
+            {
+                motion.push(value);
+            }
+        }
+
+        assert!(motion.len() >= 2);
+        assert_eq!([true, false], motion[..2]);
+    }
+
+    #[test]
+    fn follows_a_daily_temperature_curve() {
+        let config = house().with_temperatures(16.0, 24.0);
+
+        assert_eq!(
+            16.0,
+            config.temperature_at(NaiveTime::from_hms_opt(4, 0, 0).unwrap())
+        );
+        assert_eq!(
+            20.0,
+            config.temperature_at(NaiveTime::from_hms_opt(10, 0, 0).unwrap())
+        );
+        assert_eq!(
+            24.0,
+            config.temperature_at(NaiveTime::from_hms_opt(16, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn drains_batteries() {
+        let mut simulator = simulator(house().with_battery_drain(1.0));
+        simulator.tick(now());
+
+        let events = simulator.tick(now() + Duration::from_secs(10 * 86_400));
+
+        assert_eq!(
+            changed(&events, "living-room-sensor", "battery_level"),
+            Some(PropertyValue::Number(Some(90.0)))
+        );
+    }
+
+    #[test]
+    fn makes_devices_unreachable() {
+        let mut simulator = Simulator::with_seed(house().with_outages(1_000_000.0), 42);
+        simulator.tick(now());
+        let events = simulator.tick(now() + Duration::from_secs(1));
+        let command = Command::ControlDevice {
+            device_id: "hallway-light-1".to_string(),
+            properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(true))]),
+            transition: None,
+        };
+
+        let result = simulator.execute(&command, now() + Duration::from_secs(1));
+
+        assert!(matches!(result, Err(SimulatorError::Unreachable(_))));
+        assert!(simulator.is_reachable("hallway-light-1", now() + Duration::from_secs(601)));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            Event::AvailabilityChanged { device_id, availability: Availability::Unavailable }
+                if device_id == "hallway-light-1"
+        )));
+        let events = simulator.tick(now() + Duration::from_secs(601));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            Event::AvailabilityChanged { device_id, availability: Availability::Available }
+                if device_id == "hallway-light-1"
+        )));
+    }
+
+    #[test]
+    fn deserializes_a_config() -> Result<(), serde_json::Error> {
+        let config: SimulatorConfig = serde_json::from_str(
+            r#"{"rooms": [{"name": "Kitchen", "lights": 3, "motion_sensor": true}], "outages": 0}"#,
+        )?;
+
+        let events = Simulator::new(config).discover();
+
+        match &events[0] {
+            Event::DiscoveredDevices(devices) => assert_eq!(4, devices.len()),
+            event => panic!("Unexpected event {:?}", event),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+use crate::health::DeviceHealth;
+use crate::metrics::Metrics;
+
+/// Serves the metrics at `/metrics` for Prometheus to scrape, and the health summary as JSON at `/health`. It only
+/// reports, requests that change the house go to the [`crate::control_server::ControlServer`].
+pub struct StatusServer {
+    address: SocketAddr,
+    server: JoinHandle<()>,
+}
+
+impl StatusServer {
+    pub async fn start(
+        address: SocketAddr,
+        metrics: Metrics,
+        health: DeviceHealth,
+    ) -> Result<StatusServer, StatusServerError> {
+        let server = Server::try_bind(&address)?.serve(make_service_fn(move |_| {
+            let (metrics, health) = (metrics.clone(), health.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let response = handle(request, &metrics, &health);
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        }));
+        let address = server.local_addr();
+        let server = tokio::spawn(async move {
+            let _ = server.await;
+        });
+        Ok(StatusServer { address, server })
+    }
+
+    /// The address the server listens on, with the actual port if port 0 was asked for.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn handle(request: Request<Body>, metrics: &Metrics, health: &DeviceHealth) -> Response<Body> {
+    let response = Response::builder();
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => response
+            .header("content-type", "text/plain; version=0.0.4")
+            .body(metrics.encode().into()),
+        (&Method::GET, "/health") => response.header("content-type", "application/json").body(
+            serde_json::to_string(&health.summary())
+                .unwrap_or_default()
+                .into(),
+        ),
+        _ => response
+            .status(StatusCode::NOT_FOUND)
+            .body("Not Found".into()),
+    }
+    .expect("a valid response")
+}
+
+#[derive(Error, Debug)]
+pub enum StatusServerError {
+    #[error("cannot listen for requests")]
+    BindError(#[from] hyper::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::event::Event;
+    use crate::model::Availability;
+    use crate::test_support;
+
+    async fn server(metrics: Metrics, health: DeviceHealth) -> StatusServer {
+        StatusServer::start("127.0.0.1:0".parse().unwrap(), metrics, health)
+            .await
+            .expect("the server starts")
+    }
+
+    #[tokio::test]
+    async fn serves_the_metrics() -> Result<(), Box<dyn std::error::Error>> {
+        let metrics = Metrics::new();
+        metrics.event_stream_reconnected();
+        let server = server(metrics, DeviceHealth::new()).await;
+
+        let body = reqwest::get(format!("http://{}/metrics", server.address()))
+            .await?
+            .text()
+            .await?;
+
+        assert!(body.contains("chambrier_event_stream_reconnects_total 1"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn serves_the_health_summary() -> Result<(), Box<dyn std::error::Error>> {
+        let health = DeviceHealth::new();
+        let sensor = test_support::sensor("sensor-1")
+            .named("Garden sensor")
+            .availability(Availability::Unavailable)
+            .build();
+        health.handle_event(&Event::DiscoveredDevices(vec![sensor]), chrono::Utc::now());
+        let server = server(Metrics::new(), health).await;
+
+        let body: Value = reqwest::get(format!("http://{}/health", server.address()))
+            .await?
+            .json()
+            .await?;
+
+        assert_eq!(1, body["devices"]);
+        assert_eq!("Garden sensor", body["problems"][0]["name"]);
+        assert_eq!("unavailable", body["problems"][0]["availability"]);
+        Ok(())
+    }
+}
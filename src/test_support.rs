@@ -0,0 +1,171 @@
+//! Devices for tests, built up from only the properties a test is about.
+
+use std::collections::HashMap;
+
+use crate::model::{
+    Availability, BooleanProperty, Device, DeviceType, NumberProperty, Property, PropertyType, Unit,
+};
+
+const MANUFACTURER: &str = "Signify Netherlands B.V.";
+
+pub struct DeviceBuilder {
+    id: String,
+    device_type: DeviceType,
+    model_id: &'static str,
+    product_name: &'static str,
+    name: String,
+    properties: HashMap<String, Property>,
+    availability: Availability,
+}
+
+/// A Hue color lamp named `Light <id>`, without properties. Its light resource is `light-<id>`.
+pub fn light(id: &str) -> DeviceBuilder {
+    DeviceBuilder::new(id, DeviceType::Light, "LCT015", "Hue color lamp", "Light")
+}
+
+/// A Hue motion sensor named `Sensor <id>`, without properties.
+pub fn sensor(id: &str) -> DeviceBuilder {
+    DeviceBuilder::new(
+        id,
+        DeviceType::Sensor,
+        "SML001",
+        "Hue motion sensor",
+        "Sensor",
+    )
+}
+
+/// A Hue dimmer switch named `Switch <id>`, without properties.
+pub fn switch(id: &str) -> DeviceBuilder {
+    DeviceBuilder::new(
+        id,
+        DeviceType::Switch,
+        "RWL021",
+        "Hue dimmer switch",
+        "Switch",
+    )
+}
+
+impl DeviceBuilder {
+    fn new(
+        id: &str,
+        device_type: DeviceType,
+        model_id: &'static str,
+        product_name: &'static str,
+        kind: &str,
+    ) -> Self {
+        DeviceBuilder {
+            id: id.to_string(),
+            device_type,
+            model_id,
+            product_name,
+            name: format!("{} {}", kind, id),
+            properties: HashMap::new(),
+            availability: Availability::default(),
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn availability(mut self, availability: Availability) -> Self {
+        self.availability = availability;
+        self
+    }
+
+    pub fn on(self, on: bool) -> Self {
+        let external_id = self.external_id();
+        self.property(Property::Boolean(BooleanProperty::new(
+            "on".to_string(),
+            false,
+            PropertyType::On,
+            external_id,
+            on,
+        )))
+    }
+
+    /// A brightness from 2 to 100 percent, the range of Hue lights.
+    pub fn brightness(self, brightness: f64) -> Self {
+        self.brightness_from(brightness, 2.0)
+    }
+
+    pub fn brightness_from(self, brightness: f64, minimum: f64) -> Self {
+        let external_id = self.external_id();
+        self.property(Property::Number(NumberProperty::new(
+            "brightness".to_string(),
+            false,
+            PropertyType::Brightness,
+            external_id,
+            Unit::Percentage,
+            Some(brightness),
+            Some(minimum),
+            Some(100.0),
+        )))
+    }
+
+    /// A colour temperature in mirek, unknown if `None`, between `minimum` and `maximum`.
+    pub fn color_temperature(self, mirek: Option<f64>, minimum: f64, maximum: f64) -> Self {
+        let external_id = self.external_id();
+        self.property(Property::Number(
+            NumberProperty::new(
+                "color_temperature".to_string(),
+                false,
+                PropertyType::ColorTemperature,
+                external_id,
+                Unit::Mirek,
+                mirek,
+                Some(minimum),
+                Some(maximum),
+            )
+            .with_step(1.0),
+        ))
+    }
+
+    pub fn motion(self, motion: bool) -> Self {
+        self.property(Property::Boolean(BooleanProperty::new(
+            "motion".to_string(),
+            true,
+            PropertyType::Motion,
+            None,
+            motion,
+        )))
+    }
+
+    pub fn temperature(self, name: &str, celsius: f64) -> Self {
+        self.property(Property::Number(NumberProperty::new(
+            name.to_string(),
+            true,
+            PropertyType::Temperature,
+            None,
+            Unit::Celcius,
+            Some(celsius),
+            None,
+            None,
+        )))
+    }
+
+    /// Any other property, replacing one with the same name.
+    pub fn property(mut self, property: Property) -> Self {
+        self.properties.insert(property.name().clone(), property);
+        self
+    }
+
+    pub fn build(self) -> Device {
+        Device::new(
+            self.id,
+            self.device_type,
+            MANUFACTURER.to_string(),
+            self.model_id.to_string(),
+            self.product_name.to_string(),
+            self.name,
+            self.properties,
+            None,
+        )
+        .with_availability(self.availability)
+    }
+
+    fn external_id(&self) -> Option<String> {
+        matches!(self.device_type, DeviceType::Light).then(|| format!("light-{}", self.id))
+    }
+}
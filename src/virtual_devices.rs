@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::model::{
+    BooleanProperty, Device, DeviceType, NumberProperty, Property, PropertyError, PropertyType,
+    PropertyValue,
+};
+use crate::registry::Registry;
+
+const MANUFACTURER: &str = "chambrier";
+
+/// Devices that only exist in chambrier. Their ids must not clash with those of the bridge.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VirtualDeviceConfig {
+    /// A switch with an `on` property that can be controlled like a light, e.g. to toggle rules. From outside chambrier
+    /// it is switched through the control server.
+    Switch {
+        id: String,
+        name: String,
+        #[serde(default)]
+        on: bool,
+    },
+    /// A sensor whose value is computed from the properties of other devices.
+    TemplateSensor {
+        id: String,
+        name: String,
+        template: Template,
+    },
+}
+
+impl VirtualDeviceConfig {
+    fn id(&self) -> &String {
+        match self {
+            VirtualDeviceConfig::Switch { id, .. } => id,
+            VirtualDeviceConfig::TemplateSensor { id, .. } => id,
+        }
+    }
+}
+
+/// Targets are rooms or devices, given by id or name.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Template {
+    /// `on` is true if any light of the targets is on.
+    AnyOn(Vec<String>),
+    /// `on` is true if every light of the targets is on.
+    AllOn(Vec<String>),
+    /// The average of a number property, e.g. the temperature of a floor. The sensor gets a property with
+    /// the same name, type and unit.
+    Average {
+        targets: Vec<String>,
+        property: String,
+    },
+    Minimum {
+        targets: Vec<String>,
+        property: String,
+    },
+    Maximum {
+        targets: Vec<String>,
+        property: String,
+    },
+}
+
+impl Template {
+    /// The name of the property of the template sensor, which is also the property it reads from its sources.
+    fn property(&self) -> &str {
+        match self {
+            Template::AnyOn(_) | Template::AllOn(_) => "on",
+            Template::Average { property, .. }
+            | Template::Minimum { property, .. }
+            | Template::Maximum { property, .. } => property,
+        }
+    }
+
+    fn targets(&self) -> &Vec<String> {
+        match self {
+            Template::AnyOn(targets) | Template::AllOn(targets) => targets,
+            Template::Average { targets, .. }
+            | Template::Minimum { targets, .. }
+            | Template::Maximum { targets, .. } => targets,
+        }
+    }
+
+    fn sources<'a>(&self, registry: &'a Registry) -> Vec<&'a Property> {
+        let mut device_ids: Vec<String> = self
+            .targets()
+            .iter()
+            .flat_map(|target| registry.resolve_device_ids(target))
+            .collect();
+        device_ids.sort();
+        device_ids.dedup();
+
+        device_ids
+            .iter()
+            .filter_map(|id| registry.device(id)?.properties().get(self.property()))
+            .collect()
+    }
+
+    fn evaluate(&self, registry: &Registry) -> PropertyValue {
+        let sources = self.sources(registry);
+        let mut on = sources.iter().filter_map(|p| match p.value() {
+            PropertyValue::Boolean(on) => Some(on),
+            _ => None,
+        });
+        let numbers: Vec<f64> = sources
+            .iter()
+            .filter_map(|p| match p.value() {
+                PropertyValue::Number(number) => number,
+                _ => None,
+            })
+            .collect();
+
+        match self {
+            Template::AnyOn(_) => PropertyValue::Boolean(on.any(|on| on)),
+            Template::AllOn(_) => {
+                let on: Vec<bool> = on.collect();
+                PropertyValue::Boolean(!on.is_empty() && on.iter().all(|on| *on))
+            }
+            Template::Average { .. } => PropertyValue::Number(
+                (!numbers.is_empty()).then(|| numbers.iter().sum::<f64>() / numbers.len() as f64),
+            ),
+            Template::Minimum { .. } => {
+                PropertyValue::Number(numbers.iter().copied().reduce(f64::min))
+            }
+            Template::Maximum { .. } => {
+                PropertyValue::Number(numbers.iter().copied().reduce(f64::max))
+            }
+        }
+    }
+
+    /// The property of the template sensor, numbers take their type and unit from the first source that is known.
+    fn to_property(&self, registry: &Registry) -> Option<Property> {
+        let name = self.property().to_string();
+        match self.evaluate(registry) {
+            PropertyValue::Boolean(on) => Some(Property::Boolean(BooleanProperty::new(
+                name,
+                true,
+                PropertyType::On,
+                None,
+                on,
+            ))),
+            PropertyValue::Number(value) => {
+                let Some(Property::Number(source)) = self.sources(registry).first().copied() else {
+                    return None;
+                };
+                Some(Property::Number(NumberProperty::new(
+                    name,
+                    true,
+                    source.property_type().clone(),
+                    None,
+                    source.unit().clone(),
+                    value,
+                    source.minimum(),
+                    source.maximum(),
+                )))
+            }
+            PropertyValue::Color(_) | PropertyValue::Text(_) => None,
+        }
+    }
+}
+
+/// Keeps the state of the virtual devices. They enter the registry through the same events as devices of the bridge,
+/// so everything that works with a `Device` works with them too.
+pub struct VirtualDevices {
+    configs: Vec<VirtualDeviceConfig>,
+    switches: HashMap<String, bool>,
+    values: HashMap<String, PropertyValue>,
+}
+
+impl VirtualDevices {
+    pub fn new(configs: Vec<VirtualDeviceConfig>) -> Self {
+        let switches = configs
+            .iter()
+            .filter_map(|config| match config {
+                VirtualDeviceConfig::Switch { id, on, .. } => Some((id.clone(), *on)),
+                _ => None,
+            })
+            .collect();
+        VirtualDevices {
+            configs,
+            switches,
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn owns(&self, device_id: &str) -> bool {
+        self.configs.iter().any(|config| config.id() == device_id)
+    }
+
+    /// The virtual devices as far as they can be built. A template sensor for a number property only appears once one
+    /// of its sources is known, as it takes its type and unit from it.
+    pub fn discover(&mut self, registry: &Registry) -> Event {
+        let mut devices = vec![];
+        for config in &self.configs {
+            let device = match config {
+                VirtualDeviceConfig::Switch { id, name, .. } => {
+                    let on = self.switches.get(id).copied().unwrap_or_default();
+                    let property =
+                        BooleanProperty::new("on".to_string(), false, PropertyType::On, None, on);
+                    let property = Property::Boolean(property);
+                    device(id, name, DeviceType::Switch, "virtual_switch", property)
+                }
+                VirtualDeviceConfig::TemplateSensor { id, name, template } => {
+                    let Some(property) = template.to_property(registry) else {
+                        continue;
+                    };
+                    self.values.insert(id.clone(), property.value());
+                    device(id, name, DeviceType::Sensor, "template_sensor", property)
+                }
+            };
+            devices.push(device);
+        }
+        Event::DiscoveredDevices(devices)
+    }
+
+    /// Rediscovers the virtual devices when devices of the bridge are discovered, and recomputes the template sensors
+    /// when any property changes. Must be called after the registry has applied the event.
+    pub fn handle_event(&mut self, event: &Event, registry: &Registry) -> Vec<Event> {
+        match event {
+            Event::DiscoveredDevices(devices) if devices.iter().any(|d| !self.owns(d.id())) => {
+                vec![self.discover(registry)]
+            }
+            Event::PropertiesChanged { .. } => self.evaluate(registry),
+            _ => vec![],
+        }
+    }
+
+    /// Executes a command for a virtual switch, returning the event that reports its new state. The writes are validated
+    /// against the device in `registry`, like those to any other device. Returns `None` if the command is not for a
+    /// virtual device.
+    pub fn execute(
+        &mut self,
+        command: &Command,
+        registry: &Registry,
+    ) -> Option<Result<Event, PropertyError>> {
+        let Command::ControlDevice {
+            device_id,
+            properties,
+            ..
+        } = command
+        else {
+            return None;
+        };
+        if !self.owns(device_id) {
+            return None;
+        }
+        if let Err(e) = registry.validate(device_id, properties) {
+            return Some(Err(e));
+        }
+
+        let mut changed = HashMap::new();
+        if let (Some(switch), Some(PropertyValue::Boolean(on))) =
+            (self.switches.get_mut(device_id), properties.get("on"))
+        {
+            *switch = *on;
+            changed.insert("on".to_string(), PropertyValue::Boolean(*on));
+        }
+        Some(Ok(Event::PropertiesChanged {
+            device_id: device_id.clone(),
+            properties: changed,
+        }))
+    }
+
+    fn evaluate(&mut self, registry: &Registry) -> Vec<Event> {
+        let mut events = vec![];
+        for config in &self.configs {
+            let VirtualDeviceConfig::TemplateSensor { id, template, .. } = config else {
+                continue;
+            };
+            if registry.device(id).is_none() {
+                continue;
+            }
+            let value = template.evaluate(registry);
+            if self.values.get(id) != Some(&value) {
+                self.values.insert(id.clone(), value.clone());
+                events.push(Event::PropertiesChanged {
+                    device_id: id.clone(),
+                    properties: HashMap::from([(template.property().to_string(), value)]),
+                });
+            }
+        }
+        events
+    }
+}
+
+fn device(
+    id: &str,
+    name: &str,
+    device_type: DeviceType,
+    model_id: &str,
+    property: Property,
+) -> Device {
+    let product_name = match device_type {
+        DeviceType::Switch => "Virtual switch",
+        _ => "Template sensor",
+    };
+    let property_name = property.name().clone();
+    Device::new(
+        id.to_string(),
+        device_type,
+        MANUFACTURER.to_string(),
+        model_id.to_string(),
+        product_name.to_string(),
+        name.to_string(),
+        HashMap::from([(property_name, property)]),
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Room, Unit};
+    use crate::test_support;
+
+    fn light(id: &str, on: bool) -> Device {
+        test_support::light(id).on(on).build()
+    }
+
+    fn thermometer(id: &str, temperature: f64) -> Device {
+        test_support::sensor(id)
+            .temperature("temperature", temperature)
+            .build()
+    }
+
+    fn registry(devices: Vec<Device>) -> Registry {
+        let mut registry = Registry::new();
+        let device_ids = devices.iter().map(|d| d.id().clone()).collect();
+        registry.apply(&Event::DiscoveredDevices(devices));
+        registry.apply(&Event::DiscoveredRooms(vec![Room::new(
+            "room".to_string(),
+            "Upstairs".to_string(),
+            device_ids,
+        )]));
+        registry
+    }
+
+    fn guests_switch() -> VirtualDevices {
+        VirtualDevices::new(vec![VirtualDeviceConfig::Switch {
+            id: "guests".to_string(),
+            name: "Guests".to_string(),
+            on: false,
+        }])
+    }
+
+    fn control(properties: HashMap<String, PropertyValue>) -> Command {
+        Command::ControlDevice {
+            device_id: "guests".to_string(),
+            properties,
+            transition: None,
+        }
+    }
+
+    fn discovered(event: &Event) -> &Vec<Device> {
+        match event {
+            Event::DiscoveredDevices(devices) => devices,
+            _ => panic!("Expected discovered devices, got {:?}", event),
+        }
+    }
+
+    #[test]
+    fn discovers_a_switch_and_switches_it() {
+        let mut registry = Registry::new();
+        let mut virtual_devices = guests_switch();
+
+        let event = virtual_devices.discover(&registry);
+        let devices = discovered(&event);
+        assert_eq!(1, devices.len());
+        assert!(matches!(devices[0].device_type(), DeviceType::Switch));
+        assert_eq!(
+            Some(PropertyValue::Boolean(false)),
+            devices[0].properties().get("on").map(|p| p.value())
+        );
+        registry.apply(&event);
+
+        let command = control(HashMap::from([(
+            "on".to_string(),
+            PropertyValue::Boolean(true),
+        )]));
+        match virtual_devices.execute(&command, &registry) {
+            Some(Ok(Event::PropertiesChanged {
+                device_id,
+                properties,
+            })) => {
+                assert_eq!("guests", device_id);
+                assert_eq!(Some(&PropertyValue::Boolean(true)), properties.get("on"));
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_writes_to_a_switch() {
+        let mut registry = Registry::new();
+        let mut virtual_devices = guests_switch();
+        registry.apply(&virtual_devices.discover(&registry));
+
+        let unknown = control(HashMap::from([(
+            "brightness".to_string(),
+            PropertyValue::Number(Some(50.0)),
+        )]));
+        let wrong_kind = control(HashMap::from([(
+            "on".to_string(),
+            PropertyValue::Text("yes".to_string()),
+        )]));
+
+        assert!(matches!(
+            virtual_devices.execute(&unknown, &registry),
+            Some(Err(PropertyError::UnknownProperty(name))) if name == "brightness"
+        ));
+        assert!(matches!(
+            virtual_devices.execute(&wrong_kind, &registry),
+            Some(Err(PropertyError::WrongKind(name))) if name == "on"
+        ));
+        let event = virtual_devices.discover(&registry);
+        assert_eq!(
+            PropertyValue::Boolean(false),
+            discovered(&event)[0].properties()["on"].value()
+        );
+    }
+
+    #[test]
+    fn ignores_commands_for_other_devices() {
+        let mut virtual_devices = VirtualDevices::new(vec![]);
+
+        let command = Command::RecallScene {
+            scene_id: "relax".to_string(),
+            transition: None,
+        };
+
+        assert!(virtual_devices
+            .execute(&command, &Registry::new())
+            .is_none());
+    }
+
+    #[test]
+    fn computes_whether_any_light_is_on() {
+        let mut registry = registry(vec![light("1", false), light("2", false)]);
+        let mut virtual_devices = VirtualDevices::new(vec![VirtualDeviceConfig::TemplateSensor {
+            id: "any".to_string(),
+            name: "Any light on".to_string(),
+            template: Template::AnyOn(vec!["Upstairs".to_string()]),
+        }]);
+        registry.apply(&virtual_devices.discover(&registry));
+
+        let changed = Event::PropertiesChanged {
+            device_id: "2".to_string(),
+            properties: HashMap::from([("on".to_string(), PropertyValue::Boolean(true))]),
+        };
+        registry.apply(&changed);
+        let events = virtual_devices.handle_event(&changed, &registry);
+
+        assert_eq!(1, events.len());
+        registry.apply(&events[0]);
+        assert_eq!(
+            PropertyValue::Boolean(true),
+            registry.device("any").unwrap().properties()["on"].value()
+        );
+        assert!(virtual_devices.handle_event(&changed, &registry).is_empty());
+    }
+
+    #[test]
+    fn averages_a_number_property() {
+        let registry = registry(vec![thermometer("1", 18.0), thermometer("2", 21.0)]);
+        let mut virtual_devices = VirtualDevices::new(vec![VirtualDeviceConfig::TemplateSensor {
+            id: "upstairs".to_string(),
+            name: "Upstairs temperature".to_string(),
+            template: Template::Average {
+                targets: vec!["Upstairs".to_string()],
+                property: "temperature".to_string(),
+            },
+        }]);
+
+        let event = virtual_devices.discover(&registry);
+        let devices = discovered(&event);
+
+        match devices[0].properties().get("temperature") {
+            Some(Property::Number(temperature)) => {
+                assert_eq!(Some(19.5), temperature.value());
+                assert_eq!(&Unit::Celcius, temperature.unit());
+                assert!(temperature.readonly());
+            }
+            property => panic!("Unexpected property {:?}", property),
+        }
+    }
+
+    #[test]
+    fn waits_for_a_source_of_a_number_template() {
+        let mut virtual_devices = VirtualDevices::new(vec![VirtualDeviceConfig::TemplateSensor {
+            id: "upstairs".to_string(),
+            name: "Upstairs temperature".to_string(),
+            template: Template::Maximum {
+                targets: vec!["Upstairs".to_string()],
+                property: "temperature".to_string(),
+            },
+        }]);
+
+        let event = virtual_devices.discover(&Registry::new());
+
+        assert!(discovered(&event).is_empty());
+    }
+
+    #[test]
+    fn deserializes_virtual_devices() -> Result<(), serde_json::Error> {
+        let configs: Vec<VirtualDeviceConfig> = serde_json::from_str(
+            r#"[
+                {"type": "switch", "id": "guests", "name": "Guests"},
+                {
+                    "type": "template_sensor",
+                    "id": "upstairs",
+                    "name": "Upstairs temperature",
+                    "template": {"average": {"targets": ["Upstairs"], "property": "temperature"}}
+                }
+            ]"#,
+        )?;
+
+        assert_eq!(2, configs.len());
+        match &configs[1] {
+            VirtualDeviceConfig::TemplateSensor { template, .. } => assert_eq!(
+                &Template::Average {
+                    targets: vec!["Upstairs".to_string()],
+                    property: "temperature".to_string()
+                },
+                template
+            ),
+            config => panic!("Unexpected config {:?}", config),
+        }
+        Ok(())
+    }
+}